@@ -0,0 +1,541 @@
+//! Classification of build failures from their logs
+//!
+//! The classifier scans the log for well-known failure signatures and reports
+//! the most specific one found, together with the offending line and a few
+//! lines of context. Root causes (e.g. a compiler error) take precedence over
+//! their consequences (e.g. "dependencies of derivation ... failed to build").
+
+use crate::types::{Build, LogResponse, Run};
+use serde::Serialize;
+use std::fmt;
+
+/// Number of lines shown before the offending line
+const CONTEXT_BEFORE: usize = 3;
+/// Number of lines shown after the offending line
+const CONTEXT_AFTER: usize = 5;
+
+/// Probable cause of a failed build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The output hash of a fixed-output derivation did not match
+    HashMismatch {
+        /// Hash specified in the derivation
+        expected: Option<String>,
+        /// Hash of the fetched output
+        got: Option<String>,
+    },
+    /// The build ran out of memory or was killed by the OOM killer
+    OutOfMemory,
+    /// The build exceeded its time limit
+    Timeout,
+    /// Fetching sources or dependencies over the network failed
+    NetworkFailure,
+    /// Nix evaluation failed before anything was built
+    EvaluationError,
+    /// The test suite failed
+    TestFailure,
+    /// The compiler reported an error
+    CompilerError,
+    /// A dependency failed to build
+    DependencyFailed {
+        /// Store paths of the derivations that failed
+        derivations: Vec<String>,
+    },
+    /// No known failure signature was found
+    Unknown,
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::HashMismatch { expected, got } => write!(
+                f,
+                "Fixed-output hash mismatch (expected {}, got {})",
+                expected.as_deref().unwrap_or("unknown"),
+                got.as_deref().unwrap_or("unknown")
+            ),
+            FailureKind::OutOfMemory => write!(f, "Out of memory"),
+            FailureKind::Timeout => write!(f, "Timeout"),
+            FailureKind::NetworkFailure => write!(f, "Network fetch failure"),
+            FailureKind::EvaluationError => write!(f, "Nix evaluation error"),
+            FailureKind::TestFailure => write!(f, "Test failure"),
+            FailureKind::CompilerError => write!(f, "Compiler error"),
+            FailureKind::DependencyFailed { derivations } if derivations.is_empty() => {
+                write!(f, "Dependency failed")
+            }
+            FailureKind::DependencyFailed { derivations } => {
+                write!(f, "Dependency failed ({})", derivations.join(", "))
+            }
+            FailureKind::Unknown => write!(f, "Unknown failure"),
+        }
+    }
+}
+
+/// Result of analysing a failed build's log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+pub struct Diagnosis {
+    /// Probable cause of the failure
+    #[serde(flatten)]
+    pub kind: FailureKind,
+    /// The line identifying the failure
+    pub message: String,
+    /// 1-based number of that line in the log, if any line matched
+    pub line: Option<usize>,
+    /// The offending line with surrounding context
+    pub excerpt: Vec<String>,
+}
+
+/// Diagnosis of a failed build or run of a commit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+pub struct FailureReport {
+    /// Package of the failed build
+    pub package: Option<String>,
+    /// Target system of the failed build
+    pub system: Option<String>,
+    /// ID of the failed build
+    pub build_id: Option<String>,
+    /// Label of the failed run, for failures not tied to a build
+    pub run: Option<String>,
+    /// What went wrong
+    pub diagnosis: Diagnosis,
+}
+
+impl FailureReport {
+    /// Diagnose a failed build from its logs
+    pub fn for_build(build: &Build, logs: &LogResponse) -> Self {
+        Self {
+            package: Some(build.package.clone()),
+            system: build.system.clone(),
+            build_id: Some(build.id.clone()),
+            run: None,
+            diagnosis: diagnose(logs),
+        }
+    }
+
+    /// Diagnose a failed run from its error message, if it reported one
+    ///
+    /// Runs only evaluate the flake, so unrecognized run errors are reported as
+    /// evaluation errors.
+    pub fn for_run(run: &Run) -> Option<Self> {
+        let error = run.error.as_deref()?;
+        let mut diagnosis = diagnose_lines(&error.lines().collect::<Vec<_>>());
+        if diagnosis.kind == FailureKind::Unknown {
+            diagnosis.kind = FailureKind::EvaluationError;
+        }
+        Some(Self {
+            package: None,
+            system: None,
+            build_id: None,
+            run: Some(run.label()),
+            diagnosis,
+        })
+    }
+
+    /// Heading identifying the failed build or run
+    pub fn title(&self) -> String {
+        match (&self.package, &self.run) {
+            (Some(package), _) => format!(
+                "{} ({})",
+                package,
+                self.system.as_deref().unwrap_or("unknown")
+            ),
+            (None, Some(run)) => run.clone(),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+}
+
+/// Failure signatures in order of precedence, matched case-insensitively
+///
+/// The kinds carry no details; [`classify`] extracts them from the log.
+const SIGNATURES: &[(FailureKind, &[&str])] = &[
+    (
+        FailureKind::HashMismatch {
+            expected: None,
+            got: None,
+        },
+        &[
+            "hash mismatch in fixed-output derivation",
+            "hash mismatch importing path",
+        ],
+    ),
+    (
+        FailureKind::OutOfMemory,
+        &[
+            "out of memory",
+            "cannot allocate memory",
+            "killed signal terminated program",
+            "oom-kill",
+            "oom killer",
+            "std::bad_alloc",
+            "killed by signal 9",
+        ],
+    ),
+    (
+        FailureKind::Timeout,
+        &[
+            "timed out after",
+            "build timed out",
+            "build timeout",
+            "max-silent-time",
+        ],
+    ),
+    (
+        FailureKind::NetworkFailure,
+        &[
+            "could not resolve host",
+            "temporary failure in name resolution",
+            "unable to download",
+            "connection refused",
+            "connection reset by peer",
+            "network is unreachable",
+            "failed to connect to",
+            "ssl connect error",
+            "curl: (",
+        ],
+    ),
+    (
+        FailureKind::EvaluationError,
+        &[
+            "while evaluating",
+            "evaluation aborted",
+            "undefined variable",
+            "infinite recursion encountered",
+            "does not provide attribute",
+            "error: attribute",
+            "called with unexpected argument",
+            "called without required argument",
+        ],
+    ),
+    (
+        FailureKind::TestFailure,
+        &[
+            "test result: failed",
+            "tests failed",
+            "test failed",
+            "=== fail",
+            "--- fail",
+            "failed (failures=",
+            "check phase failed",
+        ],
+    ),
+    (
+        FailureKind::CompilerError,
+        &[
+            "error[e",
+            "could not compile",
+            ": error:",
+            "fatal error:",
+            "syntaxerror",
+            "cannot find symbol",
+            "undefined reference to",
+            "compilation failed",
+        ],
+    ),
+    (
+        FailureKind::DependencyFailed {
+            derivations: Vec::new(),
+        },
+        &[
+            "dependencies of derivation",
+            "dependencies couldn't be built",
+            "dependency failed",
+        ],
+    ),
+];
+
+/// Find the failure signature matched by a log line, if any
+///
+/// Only the kind of failure is returned, without the details that
+/// [`diagnose_lines`] extracts from the surrounding log.
+///
+/// # Example
+/// ```rust
+/// # use garnix_insights::analysis::{signature, FailureKind};
+/// assert_eq!(signature("  = note: out of memory"), Some(&FailureKind::OutOfMemory));
+/// assert_eq!(signature("   Compiling foo v0.1.0"), None);
+/// ```
+pub fn signature(line: &str) -> Option<&'static FailureKind> {
+    let lowercase = line.to_lowercase();
+    SIGNATURES
+        .iter()
+        .find(|(_, patterns)| matches_any(&lowercase, patterns))
+        .map(|(kind, _)| kind)
+}
+
+fn matches_any(lowercase: &str, patterns: &[&str]) -> bool {
+    patterns.iter().any(|pattern| lowercase.contains(pattern))
+}
+
+/// Classify the failure of a build from its logs
+pub fn diagnose(logs: &LogResponse) -> Diagnosis {
+    let lines: Vec<&str> = logs
+        .logs
+        .iter()
+        .flat_map(|entry| entry.log_message.lines())
+        .collect();
+    diagnose_lines(&lines)
+}
+
+/// Classify a failure from raw log lines
+pub fn diagnose_lines<S: AsRef<str>>(lines: &[S]) -> Diagnosis {
+    let lowercase: Vec<String> = lines
+        .iter()
+        .map(|line| line.as_ref().to_lowercase())
+        .collect();
+
+    for (kind, patterns) in SIGNATURES {
+        let found = lowercase
+            .iter()
+            .position(|line| matches_any(line, patterns));
+        if let Some(index) = found {
+            return diagnosis_at(lines, index, classify(kind, lines, index));
+        }
+    }
+
+    // Fall back to the last line mentioning an error, or the last line
+    match lowercase.iter().rposition(|line| line.contains("error")) {
+        Some(index) => diagnosis_at(lines, index, FailureKind::Unknown),
+        None => match lines
+            .iter()
+            .rposition(|line| !line.as_ref().trim().is_empty())
+        {
+            Some(index) => diagnosis_at(lines, index, FailureKind::Unknown),
+            None => Diagnosis {
+                kind: FailureKind::Unknown,
+                message: "No log output".to_string(),
+                line: None,
+                excerpt: Vec::new(),
+            },
+        },
+    }
+}
+
+/// Fill in the details of a matched signature from the log
+fn classify<S: AsRef<str>>(kind: &FailureKind, lines: &[S], index: usize) -> FailureKind {
+    match kind {
+        FailureKind::HashMismatch { .. } => {
+            let following = &lines[index..lines.len().min(index + 10)];
+            let value = |labels: &[&str]| {
+                following.iter().find_map(|line| {
+                    let line = line.as_ref().trim();
+                    labels
+                        .iter()
+                        .find_map(|label| line.strip_prefix(label))
+                        .map(|value| value.trim().to_string())
+                })
+            };
+            FailureKind::HashMismatch {
+                expected: value(&["specified:", "wanted:"]),
+                got: value(&["got:"]),
+            }
+        }
+        FailureKind::DependencyFailed { .. } => {
+            let mut derivations: Vec<String> = Vec::new();
+            for line in lines {
+                let line = line.as_ref();
+                if line.contains("builder for") && line.contains("failed") {
+                    if let Some(path) = quoted(line) {
+                        if !derivations.iter().any(|known| known == path) {
+                            derivations.push(path.to_string());
+                        }
+                    }
+                }
+            }
+            FailureKind::DependencyFailed { derivations }
+        }
+        kind => kind.clone(),
+    }
+}
+
+fn diagnosis_at<S: AsRef<str>>(lines: &[S], index: usize, kind: FailureKind) -> Diagnosis {
+    let start = index.saturating_sub(CONTEXT_BEFORE);
+    let end = lines.len().min(index + CONTEXT_AFTER + 1);
+    Diagnosis {
+        kind,
+        message: lines[index].as_ref().trim().to_string(),
+        line: Some(index + 1),
+        excerpt: lines[start..end]
+            .iter()
+            .map(|line| line.as_ref().to_string())
+            .collect(),
+    }
+}
+
+/// Extract the first quoted string, accepting both `'...'` and Nix's older `‘...’`
+fn quoted(line: &str) -> Option<&str> {
+    let (open, close) = if line.contains('‘') {
+        ('‘', '’')
+    } else {
+        ('\'', '\'')
+    };
+    let start = line.find(open)? + open.len_utf8();
+    let end = start + line[start..].find(close)?;
+    Some(&line[start..end])
+}
+
+/// Format failure reports as markdown
+pub fn format_failure_reports(commit_id: &str, reports: &[FailureReport]) -> String {
+    if reports.is_empty() {
+        return format!("No failed builds found for commit {}", commit_id);
+    }
+
+    let mut output = format!("# Failure diagnosis for {}\n", commit_id);
+    for report in reports {
+        let diagnosis = &report.diagnosis;
+        output.push_str(&format!(
+            "\n## {}\n- **Cause:** {}\n",
+            report.title(),
+            diagnosis.kind
+        ));
+        if let Some(build_id) = &report.build_id {
+            output.push_str(&format!("- **Build ID:** {}\n", build_id));
+        }
+        match diagnosis.line {
+            Some(line) => output.push_str(&format!("- **Line {}:** {}\n", line, diagnosis.message)),
+            None => output.push_str(&format!("- **Message:** {}\n", diagnosis.message)),
+        }
+        if !diagnosis.excerpt.is_empty() {
+            output.push_str(&format!("\n```\n{}\n```\n", diagnosis.excerpt.join("\n")));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind_of(log: &str) -> FailureKind {
+        diagnose_lines(&log.lines().collect::<Vec<_>>()).kind
+    }
+
+    #[test]
+    fn test_hash_mismatch() {
+        let log = "\
+building '/nix/store/abc-source.drv'...
+error: hash mismatch in fixed-output derivation '/nix/store/abc-source.drv':
+         specified: sha256-AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=
+            got:    sha256-BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB=
+error: 1 dependencies of derivation '/nix/store/def-app.drv' failed to build";
+
+        let diagnosis = diagnose_lines(&log.lines().collect::<Vec<_>>());
+        assert_eq!(
+            diagnosis.kind,
+            FailureKind::HashMismatch {
+                expected: Some("sha256-AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_string()),
+                got: Some("sha256-BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB=".to_string()),
+            }
+        );
+        assert_eq!(diagnosis.line, Some(2));
+        assert!(diagnosis.message.starts_with("error: hash mismatch"));
+        assert_eq!(diagnosis.excerpt.len(), 5);
+    }
+
+    #[test]
+    fn test_root_cause_wins_over_dependency_failure() {
+        let log = "\
+   Compiling foo v0.1.0
+error[E0425]: cannot find value `x` in this scope
+error: could not compile `foo` (bin \"foo\") due to 1 previous error
+error: builder for '/nix/store/abc-foo.drv' failed with exit code 101
+error: 1 dependencies of derivation '/nix/store/def-app.drv' failed to build";
+        assert_eq!(kind_of(log), FailureKind::CompilerError);
+
+        let log = "\
+error: builder for '/nix/store/abc-foo.drv' failed with exit code 1
+error: 1 dependencies of derivation '/nix/store/def-app.drv' failed to build";
+        assert_eq!(
+            kind_of(log),
+            FailureKind::DependencyFailed {
+                derivations: vec!["/nix/store/abc-foo.drv".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn test_classification() {
+        assert_eq!(
+            kind_of("error: undefined variable 'foo'\n       at /nix/store/x/flake.nix:3:5"),
+            FailureKind::EvaluationError
+        );
+        assert_eq!(
+            kind_of("test tests::it_works ... FAILED\ntest result: FAILED. 1 passed; 1 failed"),
+            FailureKind::TestFailure
+        );
+        assert_eq!(
+            kind_of("main.c:3:1: error: expected ';' before '}' token"),
+            FailureKind::CompilerError
+        );
+        assert_eq!(
+            kind_of("c++: fatal error: Killed signal terminated program cc1plus"),
+            FailureKind::OutOfMemory
+        );
+        assert_eq!(
+            kind_of("building of '/nix/store/abc.drv' timed out after 3600 seconds"),
+            FailureKind::Timeout
+        );
+        assert_eq!(
+            kind_of("curl: (6) Could not resolve host: example.com\nerror: unable to download"),
+            FailureKind::NetworkFailure
+        );
+        assert_eq!(
+            kind_of("builder for ‘/nix/store/old-style.drv’ failed\ndependency failed"),
+            FailureKind::DependencyFailed {
+                derivations: vec!["/nix/store/old-style.drv".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn test_unknown_failure() {
+        let diagnosis = diagnose_lines(&["starting", "Error: something odd", "exiting", ""]);
+        assert_eq!(diagnosis.kind, FailureKind::Unknown);
+        assert_eq!(diagnosis.line, Some(2));
+
+        let diagnosis = diagnose_lines(&["all quiet", "bye"]);
+        assert_eq!(diagnosis.message, "bye");
+
+        let diagnosis = diagnose(&LogResponse {
+            finished: true,
+            logs: vec![],
+        });
+        assert_eq!(diagnosis.line, None);
+        assert!(diagnosis.excerpt.is_empty());
+    }
+
+    #[test]
+    fn test_run_report() {
+        let run = Run {
+            name: Some("flake check".to_string()),
+            status: Some("Failed".to_string()),
+            error: Some("error: flake has no outputs".to_string()),
+            ..Run::default()
+        };
+        let report = FailureReport::for_run(&run).unwrap();
+        assert_eq!(report.title(), "flake check");
+        assert_eq!(report.diagnosis.kind, FailureKind::EvaluationError);
+        assert!(FailureReport::for_run(&Run::default()).is_none());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["diagnosis"]["kind"], "evaluation_error");
+        assert_eq!(json["diagnosis"]["line"], 1);
+    }
+
+    #[test]
+    fn test_format_failure_reports() {
+        let run = Run {
+            name: Some("flake check".to_string()),
+            error: Some("error: undefined variable 'foo'".to_string()),
+            ..Run::default()
+        };
+        let report = FailureReport::for_run(&run).unwrap();
+        let formatted = format_failure_reports("abc123", &[report]);
+        assert!(formatted.contains("## flake check"));
+        assert!(formatted.contains("**Cause:** Nix evaluation error"));
+        assert!(formatted.contains("**Line 1:** error: undefined variable 'foo'"));
+
+        assert!(format_failure_reports("abc123", &[]).contains("No failed builds"));
+    }
+}
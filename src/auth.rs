@@ -0,0 +1,360 @@
+//! Token providers supplying the JWT used to authenticate against Garnix.io
+
+use crate::error::{GarnixError, GarnixResult};
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+/// Tokens expiring within this window are reported as expiring soon
+pub const EXPIRY_WARNING_WINDOW: chrono::Duration = chrono::Duration::hours(24);
+
+/// Source of the JWT token used by [`GarnixClient`](crate::GarnixClient)
+///
+/// Providers are asked for the token before every request, so implementations
+/// can pick up rotated secrets without restarting.
+#[async_trait]
+pub trait TokenProvider: Send + Sync {
+    /// Get the current token
+    async fn token(&self) -> GarnixResult<String>;
+
+    /// Describe where the token comes from, without revealing it
+    fn describe(&self) -> String;
+}
+
+/// Claims decoded from a JWT payload
+///
+/// The signature is not verified; this is only meant for reporting and for
+/// refusing tokens that are known to have expired.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TokenClaims {
+    /// Subject (`sub`) of the token
+    pub subject: Option<String>,
+    /// When the token was issued (`iat`)
+    pub issued_at: Option<DateTime<Utc>>,
+    /// When the token expires (`exp`)
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl TokenClaims {
+    /// Decode the claims of a JWT without verifying its signature
+    ///
+    /// # Errors
+    /// Returns a validation error if the token is not a well-formed JWT
+    pub fn decode(token: &str) -> GarnixResult<Self> {
+        let payload = match token.trim().split('.').collect::<Vec<_>>()[..] {
+            [_, payload, _] => payload,
+            _ => return Err(GarnixError::validation("Token is not a JWT")),
+        };
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(payload.trim_end_matches('='))
+            .map_err(|e| GarnixError::validation(format!("Invalid JWT payload encoding: {}", e)))?;
+        let claims: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(&bytes)
+            .map_err(|e| GarnixError::validation(format!("Invalid JWT payload: {}", e)))?;
+
+        let timestamp = |name: &str| {
+            claims
+                .get(name)
+                .and_then(serde_json::Value::as_f64)
+                .and_then(|secs| DateTime::from_timestamp(secs as i64, 0))
+        };
+
+        Ok(Self {
+            subject: claims.get("sub").and_then(|sub| {
+                sub.as_str()
+                    .map(str::to_string)
+                    .or_else(|| Some(sub.to_string()))
+            }),
+            issued_at: timestamp("iat"),
+            expires_at: timestamp("exp"),
+        })
+    }
+
+    /// Time left until the token expires, negative once it has expired
+    pub fn time_remaining(&self) -> Option<chrono::Duration> {
+        self.expires_at.map(|exp| exp - Utc::now())
+    }
+
+    /// Check if the token has already expired
+    pub fn is_expired(&self) -> bool {
+        self.time_remaining()
+            .is_some_and(|remaining| remaining <= chrono::Duration::zero())
+    }
+
+    /// Check if the token expires within [`EXPIRY_WARNING_WINDOW`]
+    pub fn expires_soon(&self) -> bool {
+        self.time_remaining()
+            .is_some_and(|remaining| remaining <= EXPIRY_WARNING_WINDOW)
+    }
+}
+
+/// Trim a token and reject empty values
+fn normalize(token: &str, source: &dyn TokenProvider) -> GarnixResult<String> {
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(GarnixError::config(format!(
+            "Token from {} is empty",
+            source.describe()
+        )));
+    }
+    Ok(token.to_string())
+}
+
+/// Token provider returning a fixed value
+pub struct StaticTokenProvider {
+    token: String,
+}
+
+impl StaticTokenProvider {
+    /// Create a provider for a fixed token
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenProvider for StaticTokenProvider {
+    async fn token(&self) -> GarnixResult<String> {
+        normalize(&self.token, self)
+    }
+
+    fn describe(&self) -> String {
+        "static value".to_string()
+    }
+}
+
+/// Token provider reading an environment variable on every request
+pub struct EnvTokenProvider {
+    variable: String,
+}
+
+impl EnvTokenProvider {
+    /// Create a provider reading the given environment variable
+    pub fn new(variable: impl Into<String>) -> Self {
+        Self {
+            variable: variable.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenProvider for EnvTokenProvider {
+    async fn token(&self) -> GarnixResult<String> {
+        let token = std::env::var(&self.variable).map_err(|_| {
+            GarnixError::config(format!("Environment variable {} is not set", self.variable))
+        })?;
+        normalize(&token, self)
+    }
+
+    fn describe(&self) -> String {
+        format!("environment variable {}", self.variable)
+    }
+}
+
+/// Token provider reading a file, re-reading it whenever it changes
+///
+/// Suitable for systemd credentials or agenix/sops secrets that are rotated
+/// while the process is running.
+pub struct FileTokenProvider {
+    path: PathBuf,
+    cache: Mutex<Option<(SystemTime, String)>>,
+}
+
+impl FileTokenProvider {
+    /// Create a provider reading the given file
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cache: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenProvider for FileTokenProvider {
+    async fn token(&self) -> GarnixResult<String> {
+        let read_error = |e: std::io::Error| {
+            GarnixError::config(format!(
+                "Failed to read token file {}: {}",
+                self.path.display(),
+                e
+            ))
+        };
+
+        let modified = tokio::fs::metadata(&self.path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .map_err(read_error)?;
+
+        if let Some((cached_at, token)) = &*self.cache.lock().unwrap() {
+            if *cached_at == modified {
+                return Ok(token.clone());
+            }
+        }
+
+        debug!("Reading token from {}", self.path.display());
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(read_error)?;
+        let token = normalize(&content, self)?;
+        *self.cache.lock().unwrap() = Some((modified, token.clone()));
+        Ok(token)
+    }
+
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+}
+
+/// Token provider using the output of a shell command (e.g. `pass show garnix`)
+///
+/// The command runs once; its output is reused for the lifetime of the provider
+/// so that password managers do not prompt on every request.
+pub struct CommandTokenProvider {
+    command: String,
+    cache: OnceCell<String>,
+}
+
+impl CommandTokenProvider {
+    /// Create a provider running the given shell command
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            cache: OnceCell::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenProvider for CommandTokenProvider {
+    async fn token(&self) -> GarnixResult<String> {
+        self.cache
+            .get_or_try_init(|| async {
+                debug!("Running token command");
+                let output = tokio::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(&self.command)
+                    .output()
+                    .await
+                    .map_err(|e| {
+                        GarnixError::config(format!("Failed to run token command: {}", e))
+                    })?;
+                if !output.status.success() {
+                    return Err(GarnixError::config(format!(
+                        "Token command exited with {}",
+                        output.status
+                    )));
+                }
+                normalize(&String::from_utf8_lossy(&output.stdout), self)
+            })
+            .await
+            .cloned()
+    }
+
+    fn describe(&self) -> String {
+        format!("command `{}`", self.command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt(payload: serde_json::Value) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        format!(
+            "{}.{}.signature",
+            engine.encode(r#"{"alg":"HS256","typ":"JWT"}"#),
+            engine.encode(payload.to_string())
+        )
+    }
+
+    #[test]
+    fn test_decode_claims() {
+        let token = jwt(serde_json::json!({
+            "sub": "user-42",
+            "iat": 1_700_000_000,
+            "exp": 4_102_444_800u64
+        }));
+        let claims = TokenClaims::decode(&token).unwrap();
+        assert_eq!(claims.subject.as_deref(), Some("user-42"));
+        assert_eq!(claims.issued_at.unwrap().timestamp(), 1_700_000_000);
+        assert!(!claims.is_expired());
+        assert!(!claims.expires_soon());
+
+        let numeric_subject = jwt(serde_json::json!({ "sub": 7 }));
+        let claims = TokenClaims::decode(&numeric_subject).unwrap();
+        assert_eq!(claims.subject.as_deref(), Some("7"));
+        assert_eq!(claims.expires_at, None);
+        assert!(!claims.is_expired());
+
+        assert!(TokenClaims::decode("not-a-jwt").is_err());
+        assert!(TokenClaims::decode("a.!!!.c").is_err());
+    }
+
+    #[test]
+    fn test_claims_expiry() {
+        let expired = jwt(serde_json::json!({ "exp": Utc::now().timestamp() - 60 }));
+        let claims = TokenClaims::decode(&expired).unwrap();
+        assert!(claims.is_expired());
+        assert!(claims.expires_soon());
+
+        let soon = jwt(serde_json::json!({ "exp": Utc::now().timestamp() + 3600 }));
+        let claims = TokenClaims::decode(&soon).unwrap();
+        assert!(!claims.is_expired());
+        assert!(claims.expires_soon());
+    }
+
+    #[tokio::test]
+    async fn test_static_provider() {
+        let provider = StaticTokenProvider::new(" token \n");
+        assert_eq!(provider.token().await.unwrap(), "token");
+        assert!(StaticTokenProvider::new("").token().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_env_provider() {
+        let provider = EnvTokenProvider::new("GARNIX_TEST_UNSET_VARIABLE");
+        assert!(matches!(
+            provider.token().await,
+            Err(GarnixError::ConfigError(_))
+        ));
+        assert!(provider.describe().contains("GARNIX_TEST_UNSET_VARIABLE"));
+    }
+
+    #[tokio::test]
+    async fn test_file_provider_rereads_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        std::fs::write(&path, "first\n").unwrap();
+
+        let provider = FileTokenProvider::new(&path);
+        assert_eq!(provider.token().await.unwrap(), "first");
+
+        std::fs::write(&path, "second\n").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(provider.token().await.unwrap(), "second");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(provider.token().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_command_provider() {
+        let provider = CommandTokenProvider::new("echo command-token");
+        assert_eq!(provider.token().await.unwrap(), "command-token");
+        assert!(provider.describe().contains("echo command-token"));
+
+        let failing = CommandTokenProvider::new("exit 3");
+        assert!(failing.token().await.is_err());
+    }
+}
@@ -1,11 +1,25 @@
 //! Command-line interface for Garnix Insights
 
-use crate::client::GarnixClient;
+use crate::analysis::format_failure_reports;
+use crate::auth::TokenClaims;
+use crate::client::{GarnixClient, WaitOptions, WaitOutcome};
+use crate::compare::format_comparison;
+pub use crate::config::OutputFormat;
+use crate::config::{Config, TokenPolicy};
 use crate::error::{GarnixError, GarnixResult};
-use crate::mcp::{negotiate_version, GarnixMcpServer};
+use crate::excerpt::{self, ExcerptOptions, Region};
+use crate::git::{self, RepoSlug};
+use crate::mcp::{check_tool_names, negotiate_version, GarnixMcpServer, McpSessions};
 use crate::server::GarnixHttpServer;
+use crate::types::{BuildFilter, BuildLogs, BuildTransition, GarnixResponse, LogResponse};
+use crate::{build_status_json, format_duration};
 use clap::{Parser, Subcommand};
-use tracing::{error, info};
+use futures::StreamExt;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
 
 /// Garnix Insights - Fetch CI build status from Garnix.io
 #[derive(Parser, Debug)]
@@ -21,7 +35,7 @@ pub struct Cli {
     #[arg(long, env = "GARNIX_JWT_TOKEN")]
     pub jwt_token: Option<String>,
 
-    /// Git commit ID to fetch build status for (required for fetch command)
+    /// Git commit ID or revision (e.g. HEAD, main, v1.0, HEAD~3) to fetch build status for
     #[arg(long)]
     pub commit_id: Option<String>,
 
@@ -29,24 +43,117 @@ pub struct Cli {
     #[arg(short, long)]
     pub verbose: bool,
 
-    /// Output format for results
-    #[arg(long, default_value = "human")]
-    pub format: OutputFormat,
+    /// Output format for results (defaults to the profile's format, then human)
+    #[arg(long)]
+    pub format: Option<OutputFormat>,
+
+    /// Path to the configuration file (defaults to $XDG_CONFIG_HOME/garnix-insights/config.toml)
+    #[arg(long, env = "GARNIX_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Configuration profile to use
+    #[arg(long, env = "GARNIX_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Expected repository (owner/name) to cross-check results against
+    #[arg(long)]
+    pub repo: Option<String>,
 
     /// MCP protocol version: latest|stable|legacy|YYYY-MM-DD
     #[arg(long, env = "GARNIX_MCP_PROTOCOL_VERSION")]
     pub mcp_version: Option<String>,
+
+    /// Whether MCP tool calls may pass their own token (defaults to the profile's policy, then optional)
+    #[arg(long, env = "GARNIX_MCP_TOKEN_POLICY")]
+    pub mcp_token_policy: Option<TokenPolicy>,
+
+    /// MCP tools to offer, comma-separated (defaults to the profile's tools, then all but opt-in ones)
+    #[arg(long, env = "GARNIX_MCP_TOOLS", value_delimiter = ',')]
+    pub mcp_tools: Option<Vec<String>>,
 }
 
-/// Available output formats
-#[derive(clap::ValueEnum, Debug, Clone)]
-pub enum OutputFormat {
-    /// Human-readable output with colors and emojis
-    Human,
-    /// JSON output
-    Json,
-    /// Plain text output
-    Plain,
+/// Process exit status reported by the CLI
+///
+/// The CLI can be used directly as a CI gate; the exit code tells apart build
+/// outcomes from failures to query Garnix at all:
+///
+/// | Code | Meaning                                   |
+/// |------|-------------------------------------------|
+/// | 0    | All builds succeeded                      |
+/// | 1    | Any other error (config, parse, API, ...) |
+/// | 2    | At least one build failed                 |
+/// | 3    | Builds are still pending                  |
+/// | 4    | At least one build was cancelled          |
+/// | 5    | Timed out waiting for pending builds      |
+/// | 6    | Authentication failed                     |
+/// | 7    | Commit or build not found                 |
+/// | 8    | Network error talking to Garnix           |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Command succeeded and all builds passed
+    Success = 0,
+    /// Command failed with an error
+    Error = 1,
+    /// Some builds failed
+    BuildsFailed = 2,
+    /// Some builds are still pending
+    BuildsPending = 3,
+    /// Some builds were cancelled
+    BuildsCancelled = 4,
+    /// Timed out while builds were still pending
+    TimedOut = 5,
+    /// Authentication with Garnix failed
+    AuthError = 6,
+    /// The requested commit or build does not exist
+    NotFound = 7,
+    /// Garnix could not be reached
+    NetworkError = 8,
+}
+
+impl ExitStatus {
+    /// Get the numeric process exit code
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Derive the exit status from a build status response
+    ///
+    /// Failures take precedence over pending builds, which take precedence
+    /// over cancelled builds.
+    pub fn from_response(response: &GarnixResponse) -> Self {
+        let summary = &response.summary;
+        if summary.failed > 0 || !response.failed_runs().is_empty() {
+            ExitStatus::BuildsFailed
+        } else if summary.pending > 0 {
+            ExitStatus::BuildsPending
+        } else if summary.cancelled > 0 {
+            ExitStatus::BuildsCancelled
+        } else {
+            ExitStatus::Success
+        }
+    }
+
+    /// Derive the exit status from the outcome of waiting for builds
+    ///
+    /// Known failures take precedence over timing out, e.g. before any build
+    /// was created.
+    pub fn from_wait_outcome(outcome: &WaitOutcome) -> Self {
+        match Self::from_response(outcome.response()) {
+            ExitStatus::BuildsFailed => ExitStatus::BuildsFailed,
+            _ if outcome.is_timed_out() => ExitStatus::TimedOut,
+            status => status,
+        }
+    }
+
+    /// Derive the exit status from an error
+    pub fn from_error(error: &GarnixError) -> Self {
+        match error.category() {
+            "authentication" => ExitStatus::AuthError,
+            "not_found" => ExitStatus::NotFound,
+            "network" => ExitStatus::NetworkError,
+            _ => ExitStatus::Error,
+        }
+    }
 }
 
 /// Available commands
@@ -57,9 +164,57 @@ pub enum Commands {
         /// JWT authentication token
         #[arg(long, env = "GARNIX_JWT_TOKEN")]
         jwt_token: Option<String>,
-        /// Git commit ID
-        #[arg(long)]
+        /// Git commit ID or revision, resolved against the repository in the current directory
+        #[arg(long, default_value = "HEAD")]
+        commit_id: String,
+    },
+    /// Wait until all builds for a commit have finished
+    ///
+    /// Exits with 0 when all builds succeeded, 2 when some failed, 4 when some
+    /// were cancelled and 5 when the timeout elapsed first.
+    Wait {
+        /// JWT authentication token
+        #[arg(long, env = "GARNIX_JWT_TOKEN")]
+        jwt_token: Option<String>,
+        /// Git commit ID or revision, resolved against the repository in the current directory
+        #[arg(long, default_value = "HEAD")]
         commit_id: String,
+        /// Seconds between two polls of the build status
+        #[arg(long, default_value = "10")]
+        interval: u64,
+        /// Maximum number of seconds to wait (0 waits indefinitely)
+        #[arg(long, default_value = "1800")]
+        timeout: u64,
+    },
+    /// Explain why the builds of a commit failed, by classifying their logs
+    Diagnose {
+        /// JWT authentication token
+        #[arg(long, env = "GARNIX_JWT_TOKEN")]
+        jwt_token: Option<String>,
+        /// Git commit ID or revision, resolved against the repository in the current directory
+        #[arg(long, default_value = "HEAD")]
+        commit_id: String,
+        /// Only diagnose builds of this package
+        #[arg(long)]
+        package: Option<String>,
+        /// Only diagnose builds for this system
+        #[arg(long)]
+        system: Option<String>,
+    },
+    /// Compare the builds of two commits, e.g. a branch with main
+    ///
+    /// Exits with 2 when builds fail in the head commit that did not fail in
+    /// the base commit, and with 0 otherwise.
+    Compare {
+        /// JWT authentication token
+        #[arg(long, env = "GARNIX_JWT_TOKEN")]
+        jwt_token: Option<String>,
+        /// Git commit ID or revision to compare against
+        #[arg(long, default_value = "main")]
+        base: String,
+        /// Git commit ID or revision to check
+        #[arg(long, default_value = "HEAD")]
+        head: String,
     },
     /// Start HTTP server mode
     Server {
@@ -69,6 +224,9 @@ pub enum Commands {
         /// Port to bind the server to
         #[arg(long, default_value = "8080")]
         port: u16,
+        /// Bearer secret required on `/mcp` (defaults to the profile's secret)
+        #[arg(long, env = "GARNIX_MCP_SECRET", hide_env_values = true)]
+        mcp_secret: Option<String>,
     },
     /// Start MCP (Model Context Protocol) server mode
     Mcp,
@@ -76,16 +234,50 @@ pub enum Commands {
     ValidateToken {
         /// JWT authentication token to validate
         #[arg(long, env = "GARNIX_JWT_TOKEN")]
-        jwt_token: String,
+        jwt_token: Option<String>,
     },
-    /// Get build logs for a specific build
+    /// Get build logs for a specific build, or for the builds of a commit
     Logs {
         /// JWT authentication token
         #[arg(long, env = "GARNIX_JWT_TOKEN")]
-        jwt_token: String,
+        jwt_token: Option<String>,
         /// Build ID to fetch logs for
+        #[arg(
+            long,
+            required_unless_present = "commit_id",
+            conflicts_with = "commit_id"
+        )]
+        build_id: Option<String>,
+        /// Git commit ID or revision whose builds' logs to fetch
         #[arg(long)]
-        build_id: String,
+        commit_id: Option<String>,
+        /// Only fetch logs of builds of this package (with --commit-id)
+        #[arg(long, conflicts_with = "build_id")]
+        package: Option<String>,
+        /// Only fetch logs of builds for this system (with --commit-id)
+        #[arg(long, conflicts_with = "build_id")]
+        system: Option<String>,
+        /// Only fetch logs of failed builds (with --commit-id)
+        #[arg(long, conflicts_with = "build_id")]
+        failed: bool,
+        /// Keep polling and print new log lines until the build finishes
+        #[arg(long, short = 'f', conflicts_with = "commit_id")]
+        follow: bool,
+        /// Polling interval in seconds when following
+        #[arg(long, default_value = "2", requires = "follow")]
+        interval: u64,
+        /// Only show the region around the first error, with line numbers
+        #[arg(long, conflicts_with = "follow")]
+        excerpt: bool,
+        /// Lines of context around the error region (with --excerpt)
+        #[arg(long, default_value = "10", requires = "excerpt")]
+        context: usize,
+        /// Show the last error region instead of the first (with --excerpt)
+        #[arg(long, requires = "excerpt")]
+        last: bool,
+        /// Do not collapse runs of similar lines (with --excerpt)
+        #[arg(long, requires = "excerpt")]
+        no_collapse: bool,
     },
 }
 
@@ -96,7 +288,7 @@ impl Cli {
     }
 
     /// Run the CLI application
-    pub async fn run(self) -> GarnixResult<()> {
+    pub async fn run(mut self) -> GarnixResult<ExitStatus> {
         // Initialize tracing
         let level = if self.verbose {
             tracing::Level::DEBUG
@@ -104,57 +296,185 @@ impl Cli {
             tracing::Level::INFO
         };
 
-        tracing_subscriber::fmt().with_max_level(level).init();
+        // Ignore a subscriber that is already installed, e.g. by an embedding application
+        let _ = tracing_subscriber::fmt().with_max_level(level).try_init();
 
         info!("Starting Garnix Insights v{}", env!("CARGO_PKG_VERSION"));
 
-        let client = GarnixClient::new();
+        let config = Config::load(self.config.as_deref())?;
+        let profile = config.profile(self.profile.as_deref())?;
+        self.format = self.format.or(profile.format);
+        self.repo = self.repo.take().or_else(|| profile.repo.clone());
+        let token_policy = self
+            .mcp_token_policy
+            .or(profile.mcp_token_policy)
+            .unwrap_or_default();
+        let mcp_tools = self.mcp_tools.clone().or(profile.mcp_tools.clone());
+        if let (Some(tools), Some(Commands::Mcp | Commands::Server { .. })) =
+            (&mcp_tools, &self.command)
+        {
+            check_tool_names(tools)?;
+        }
+        if let Some(repo) = &self.repo {
+            repo.parse::<RepoSlug>()?;
+        }
+
+        let client = profile.client()?;
 
         match &self.command {
             Some(Commands::Fetch {
                 jwt_token,
                 commit_id,
             }) => {
-                let token = jwt_token
-                    .as_ref()
-                    .or(self.jwt_token.as_ref())
-                    .ok_or_else(|| GarnixError::ConfigError("JWT token is required".to_string()))?;
-
-                self.fetch_build_status(&client, token, commit_id).await
+                let client = self.authenticate(&client, jwt_token.as_ref());
+                self.fetch_build_status(&client, commit_id).await
+            }
+            Some(Commands::Wait {
+                jwt_token,
+                commit_id,
+                interval,
+                timeout,
+            }) => {
+                let client = self.authenticate(&client, jwt_token.as_ref());
+                let options = WaitOptions {
+                    interval: Duration::from_secs(*interval),
+                    timeout: (*timeout > 0).then(|| Duration::from_secs(*timeout)),
+                };
+                self.wait_for_completion(&client, commit_id, &options).await
+            }
+            Some(Commands::Diagnose {
+                jwt_token,
+                commit_id,
+                package,
+                system,
+            }) => {
+                let client = self.authenticate(&client, jwt_token.as_ref());
+                let filter = BuildFilter {
+                    package: package.clone(),
+                    system: system.clone(),
+                    failed_only: true,
+                };
+                self.diagnose(&client, commit_id, &filter).await?;
+                Ok(ExitStatus::Success)
+            }
+            Some(Commands::Compare {
+                jwt_token,
+                base,
+                head,
+            }) => {
+                let client = self.authenticate(&client, jwt_token.as_ref());
+                self.compare(&client, base, head).await
             }
-            Some(Commands::Server { bind_address, port }) => {
+            Some(Commands::Server {
+                bind_address,
+                port,
+                mcp_secret,
+            }) => {
                 info!("Starting HTTP server on {}:{}", bind_address, port);
-                let server = GarnixHttpServer::with_client(client)
+                // The configured token is the default for MCP sessions served under /mcp
+                let client = self.mcp_client(&client, token_policy);
+                let sessions = McpSessions::new(
+                    client.clone(),
+                    negotiate_version(self.mcp_version.as_deref()),
+                )
+                .with_token_policy(token_policy)
+                .with_enabled_tools(mcp_tools);
+                let reload = sessions.clone();
+                self.reload_mcp_tools_on_hangup(move |tools| reload.set_enabled_tools(tools));
+                let mut server = GarnixHttpServer::with_client(client)
                     .bind_address(bind_address.clone())
-                    .port(*port);
-                server.run().await
+                    .port(*port)
+                    .with_mcp_sessions(sessions);
+                let mcp_secret = match (mcp_secret, &profile.mcp_secret) {
+                    (Some(secret), _) => Some(secret.clone()),
+                    (None, Some(source)) => Some(source.provider().token().await?),
+                    (None, None) => None,
+                };
+                if let Some(secret) = mcp_secret {
+                    server = server.mcp_secret(secret);
+                }
+                server.run().await?;
+                Ok(ExitStatus::Success)
             }
             Some(Commands::Mcp) => {
                 info!("Starting MCP server");
                 let requested = self.mcp_version.as_deref();
                 let version = negotiate_version(requested);
                 info!("MCP protocol version: {}", version.as_str());
-                let server = GarnixMcpServer::with_client_and_version(client, version);
-                server.run_stdio().await
+                let client = self.mcp_client(&client, token_policy);
+                let server = Arc::new(
+                    GarnixMcpServer::with_client_and_version(client, version)
+                        .with_token_policy(token_policy)
+                        .with_enabled_tools(mcp_tools),
+                );
+                let reload = server.clone();
+                self.reload_mcp_tools_on_hangup(move |tools| reload.set_enabled_tools(tools));
+                server.run_stdio().await?;
+                Ok(ExitStatus::Success)
             }
             Some(Commands::ValidateToken { jwt_token }) => {
-                self.validate_token(&client, jwt_token).await
+                let client = self.authenticate(&client, jwt_token.as_ref());
+                self.validate_token(&client).await?;
+                Ok(ExitStatus::Success)
             }
             Some(Commands::Logs {
                 jwt_token,
                 build_id,
-            }) => self.fetch_build_logs(&client, jwt_token, build_id).await,
+                commit_id,
+                package,
+                system,
+                failed,
+                follow,
+                interval,
+                excerpt,
+                context,
+                last,
+                no_collapse,
+            }) => {
+                let client = self.authenticate(&client, jwt_token.as_ref());
+                let excerpt = excerpt.then(|| {
+                    ExcerptOptions::new()
+                        .context(*context)
+                        .region(if *last { Region::Last } else { Region::First })
+                        .collapse_repeats(!no_collapse)
+                });
+                match (build_id, commit_id) {
+                    (Some(build_id), _) if *follow => {
+                        let interval = Duration::from_secs(*interval);
+                        self.follow_build_logs(&client, build_id, interval).await?;
+                    }
+                    (Some(build_id), _) => {
+                        self.fetch_build_logs(&client, build_id, excerpt.as_ref())
+                            .await?
+                    }
+                    (None, Some(commit_id)) => {
+                        let filter = BuildFilter {
+                            package: package.clone(),
+                            system: system.clone(),
+                            failed_only: *failed,
+                        };
+                        self.fetch_commit_logs(&client, commit_id, &filter, excerpt.as_ref())
+                            .await?;
+                    }
+                    (None, None) => {
+                        return Err(GarnixError::validation(
+                            "Either --build-id or --commit-id is required",
+                        ))
+                    }
+                }
+                Ok(ExitStatus::Success)
+            }
             None => {
                 // Default behavior - try to fetch build status if we have the required args
-                match (&self.jwt_token, &self.commit_id) {
-                    (Some(token), Some(commit_id)) => {
-                        self.fetch_build_status(&client, token, commit_id).await
+                match &self.commit_id {
+                    Some(commit_id) => {
+                        let client = self.authenticate(&client, None);
+                        self.fetch_build_status(&client, commit_id).await
                     }
                     _ => {
                         error!("No command specified and missing required arguments");
                         Err(GarnixError::ConfigError(
-                            "Either specify a subcommand or provide --jwt-token and --commit-id"
-                                .to_string(),
+                            "Either specify a subcommand or provide --commit-id".to_string(),
                         ))
                     }
                 }
@@ -162,20 +482,92 @@ impl Cli {
         }
     }
 
+    /// Get the output format, defaulting to human-readable
+    pub fn output_format(&self) -> OutputFormat {
+        self.format.unwrap_or(OutputFormat::Human)
+    }
+
+    /// Authenticate with a token from the command or global flag/env var, which
+    /// take precedence over the profile's token provider
+    fn authenticate(&self, client: &GarnixClient, explicit: Option<&String>) -> GarnixClient {
+        match explicit.or(self.jwt_token.as_ref()) {
+            Some(token) => client.clone().with_token(token.clone()),
+            None => client.clone(),
+        }
+    }
+
+    /// Client holding the server-side token of MCP tool calls
+    fn mcp_client(&self, client: &GarnixClient, policy: TokenPolicy) -> GarnixClient {
+        let client = self.authenticate(client, None);
+        if policy == TokenPolicy::Disallowed && client.token_provider().is_none() {
+            warn!("MCP token policy is disallowed but no server-side token is configured");
+        }
+        client
+    }
+
+    /// Re-read the MCP tools from the configuration file whenever SIGHUP is received
+    ///
+    /// Nothing is reloaded when `--mcp-tools` is given, as the flag takes
+    /// precedence over the configuration.
+    fn reload_mcp_tools_on_hangup<F>(&self, apply: F)
+    where
+        F: Fn(Option<Vec<String>>) + Send + 'static,
+    {
+        if self.mcp_tools.is_some() {
+            return;
+        }
+        #[cfg(unix)]
+        {
+            let config = self.config.clone();
+            let profile = self.profile.clone();
+            tokio::spawn(async move {
+                use tokio::signal::unix::{signal, SignalKind};
+                let mut hangup = match signal(SignalKind::hangup()) {
+                    Ok(hangup) => hangup,
+                    Err(e) => {
+                        warn!("Cannot reload MCP tools on SIGHUP: {}", e);
+                        return;
+                    }
+                };
+                while hangup.recv().await.is_some() {
+                    let tools = Config::load(config.as_deref())
+                        .and_then(|config| config.profile(profile.as_deref()))
+                        .map(|profile| profile.mcp_tools);
+                    match tools {
+                        Ok(tools) => match tools.as_deref().map(check_tool_names) {
+                            Some(Err(e)) => warn!("Keeping the MCP tools: {}", e),
+                            _ => {
+                                info!("Reloaded MCP tools from the configuration");
+                                apply(tools);
+                            }
+                        },
+                        Err(e) => warn!("Keeping the MCP tools: {}", e),
+                    }
+                }
+            });
+        }
+        #[cfg(not(unix))]
+        let _ = apply;
+    }
+
     /// Fetch and display build status
     async fn fetch_build_status(
         &self,
         client: &GarnixClient,
-        jwt_token: &str,
         commit_id: &str,
-    ) -> GarnixResult<()> {
+    ) -> GarnixResult<ExitStatus> {
+        let commit_id = git::resolve_commit(commit_id).await?;
         info!("Fetching build status for commit: {}", commit_id);
 
-        let response = client.fetch_build_status(jwt_token, commit_id).await?;
+        let response = client.fetch_build_status(&commit_id).await?;
+        self.check_repository(&response).await;
 
-        match self.format {
+        match self.output_format() {
             OutputFormat::Json => {
-                println!("{}", serde_json::to_string_pretty(&response)?);
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&build_status_json(&response)?)?
+                );
             }
             OutputFormat::Human => {
                 self.print_human_readable(&response);
@@ -185,54 +577,216 @@ impl Cli {
             }
         }
 
-        Ok(())
+        Ok(ExitStatus::from_response(&response))
+    }
+
+    /// Wait for builds to settle, printing status transitions as they happen
+    async fn wait_for_completion(
+        &self,
+        client: &GarnixClient,
+        commit_id: &str,
+        options: &WaitOptions,
+    ) -> GarnixResult<ExitStatus> {
+        let commit_id = git::resolve_commit(commit_id).await?;
+        info!("Waiting for builds of commit: {}", commit_id);
+
+        let outcome = client
+            .wait_for_completion(&commit_id, options, |transition| {
+                self.print_transition(transition)
+            })
+            .await?;
+        let response = outcome.response();
+        self.check_repository(response).await;
+
+        match self.output_format() {
+            OutputFormat::Json => {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&build_status_json(response)?)?
+                );
+            }
+            OutputFormat::Human => {
+                self.print_human_readable(response);
+            }
+            OutputFormat::Plain => {
+                self.print_plain_text(response);
+            }
+        }
+
+        if outcome.is_timed_out() {
+            eprintln!(
+                "Timed out with {} build(s) still pending",
+                response.summary.pending
+            );
+        }
+
+        Ok(ExitStatus::from_wait_outcome(&outcome))
     }
 
-    /// Validate JWT token
-    async fn validate_token(&self, client: &GarnixClient, jwt_token: &str) -> GarnixResult<()> {
+    /// Warn if the response belongs to a different repository than expected
+    ///
+    /// The expected repository comes from `--repo` or the profile, falling back
+    /// to the `origin` remote of the local checkout.
+    async fn check_repository(&self, response: &GarnixResponse) {
+        let expected = match &self.repo {
+            Some(repo) => repo.parse::<RepoSlug>().ok(),
+            None => git::origin_repo().await,
+        };
+
+        let summary = &response.summary;
+        if let Some(expected) = expected {
+            if !expected.matches(&summary.repo_owner, &summary.repo_name) {
+                eprintln!(
+                    "Warning: commit belongs to {}/{} but the expected repository is {}",
+                    summary.repo_owner, summary.repo_name, expected
+                );
+            }
+        }
+    }
+
+    /// Print a build status transition observed while waiting
+    fn print_transition(&self, transition: &BuildTransition) {
+        let timestamp = chrono::Utc::now().format("%H:%M:%S");
+        match self.output_format() {
+            OutputFormat::Json => {
+                // Keep stdout a single JSON document; progress goes to stderr
+                eprintln!("{}", serde_json::to_string(transition).unwrap_or_default());
+            }
+            OutputFormat::Human | OutputFormat::Plain => {
+                println!("[{}] {}", timestamp, transition);
+            }
+        }
+    }
+
+    /// Validate JWT token and report its claims and the associated account
+    async fn validate_token(&self, client: &GarnixClient) -> GarnixResult<()> {
         info!("Validating JWT token");
 
-        match client.validate_token(jwt_token).await {
-            Ok(_) => {
-                match self.format {
-                    OutputFormat::Json => {
-                        println!(r#"{{"valid": true, "message": "Token is valid"}}"#);
+        let claims = match client.inspect_token().await {
+            Ok(claims) => Some(claims),
+            Err(GarnixError::ValidationError(e)) => {
+                warn!("Token claims cannot be decoded locally: {}", e);
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        let result = client.validate_token().await;
+        let expiry_warning = claims
+            .as_ref()
+            .filter(|claims| claims.expires_soon() && !claims.is_expired())
+            .and_then(TokenClaims::time_remaining)
+            .map(|remaining| format!("Token expires in {}", format_duration(remaining)));
+
+        match self.output_format() {
+            OutputFormat::Json => {
+                let mut output = json!({
+                    "valid": result.is_ok(),
+                    "claims": claims,
+                });
+                match &result {
+                    Ok(user) => output["user"] = json!(user),
+                    Err(e) => output["error"] = json!(e.to_string()),
+                }
+                if let Some(warning) = &expiry_warning {
+                    output["warning"] = json!(warning);
+                }
+                println!("{}", serde_json::to_string_pretty(&output)?);
+            }
+            OutputFormat::Human => {
+                match &result {
+                    Ok(_) => println!("[OK] JWT token is valid"),
+                    Err(e) => println!("[FAIL] JWT token is invalid: {}", e),
+                }
+                if let Ok(user) = &result {
+                    if let Some(username) = &user.username {
+                        println!("  User: {}", username);
                     }
-                    _ => {
-                        println!("[OK] JWT token is valid");
+                    if let Some(name) = &user.name {
+                        println!("  Name: {}", name);
+                    }
+                    if let Some(email) = &user.email {
+                        println!("  Email: {}", email);
                     }
                 }
-                Ok(())
+                if let Some(claims) = &claims {
+                    if let Some(subject) = &claims.subject {
+                        println!("  Subject: {}", subject);
+                    }
+                    if let Some(issued_at) = claims.issued_at {
+                        println!("  Issued: {}", issued_at.format("%Y-%m-%d %H:%M:%S UTC"));
+                    }
+                    match (claims.expires_at, claims.time_remaining()) {
+                        (Some(expires_at), Some(remaining)) => println!(
+                            "  Expires: {} ({})",
+                            expires_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                            if claims.is_expired() {
+                                "expired".to_string()
+                            } else {
+                                format!("in {}", format_duration(remaining))
+                            }
+                        ),
+                        _ => println!("  Expires: never"),
+                    }
+                }
+                if let Some(warning) = &expiry_warning {
+                    println!("[WARNING] {}", warning);
+                }
             }
-            Err(e) => {
-                match self.format {
-                    OutputFormat::Json => {
-                        println!(r#"{{"valid": false, "error": "{}"}}"#, e);
+            OutputFormat::Plain => {
+                println!("Valid: {}", result.is_ok());
+                match &result {
+                    Ok(user) => {
+                        println!("User: {}", user.username.as_deref().unwrap_or("unknown"));
+                        if let Some(email) = &user.email {
+                            println!("Email: {}", email);
+                        }
                     }
-                    _ => {
-                        println!("[FAIL] JWT token is invalid: {}", e);
+                    Err(e) => println!("Error: {}", e),
+                }
+                if let Some(claims) = &claims {
+                    println!(
+                        "Subject: {}",
+                        claims.subject.as_deref().unwrap_or("unknown")
+                    );
+                    if let Some(issued_at) = claims.issued_at {
+                        println!("Issued At: {}", issued_at.to_rfc3339());
+                    }
+                    if let Some(expires_at) = claims.expires_at {
+                        println!("Expires At: {}", expires_at.to_rfc3339());
                     }
                 }
-                Err(e)
+                if let Some(warning) = &expiry_warning {
+                    println!("Warning: {}", warning);
+                }
             }
         }
+
+        result.map(|_| ())
     }
 
-    /// Fetch and display build logs
+    /// Fetch and display build logs, or an excerpt of them
     async fn fetch_build_logs(
         &self,
         client: &GarnixClient,
-        jwt_token: &str,
         build_id: &str,
+        excerpt: Option<&ExcerptOptions>,
     ) -> GarnixResult<()> {
         info!("Fetching build logs for build: {}", build_id);
 
-        let response = client.fetch_build_logs(jwt_token, build_id).await?;
-
-        match self.format {
-            OutputFormat::Json => {
-                println!("{}", serde_json::to_string_pretty(&response)?);
-            }
+        let response = client.fetch_build_logs(build_id).await?;
+
+        match self.output_format() {
+            OutputFormat::Json => match excerpt {
+                Some(options) => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&json!({
+                        "build_id": build_id,
+                        "finished": response.finished,
+                        "excerpt": excerpt::excerpt(&response, options)
+                    }))?
+                ),
+                None => println!("{}", serde_json::to_string_pretty(&response)?),
+            },
             OutputFormat::Human | OutputFormat::Plain => {
                 if response.logs.is_empty() {
                     println!("No logs available for build {}", build_id);
@@ -242,9 +796,43 @@ impl Cli {
                         build_id, response.finished
                     );
                     println!("{}", "=".repeat(60));
+                    print_logs(&response, excerpt);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Classify and display the failures of a commit's builds
+    async fn diagnose(
+        &self,
+        client: &GarnixClient,
+        commit_id: &str,
+        filter: &BuildFilter,
+    ) -> GarnixResult<()> {
+        let commit_id = git::resolve_commit(commit_id).await?;
+        info!("Diagnosing failures of commit: {}", commit_id);
 
-                    for log_entry in &response.logs {
-                        println!("[{}] {}", log_entry.timestamp, log_entry.log_message);
+        let reports = client.diagnose_commit(&commit_id, filter).await?;
+
+        match self.output_format() {
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&reports)?);
+            }
+            OutputFormat::Human => {
+                println!("{}", format_failure_reports(&commit_id, &reports));
+            }
+            OutputFormat::Plain => {
+                if reports.is_empty() {
+                    println!("No failed builds found for commit {}", commit_id);
+                }
+                for report in &reports {
+                    let diagnosis = &report.diagnosis;
+                    println!("{}: {}", report.title(), diagnosis.kind);
+                    match diagnosis.line {
+                        Some(line) => println!("  line {}: {}", line, diagnosis.message),
+                        None => println!("  {}", diagnosis.message),
                     }
                 }
             }
@@ -253,12 +841,166 @@ impl Cli {
         Ok(())
     }
 
+    /// Compare and display the builds of two commits
+    async fn compare(
+        &self,
+        client: &GarnixClient,
+        base: &str,
+        head: &str,
+    ) -> GarnixResult<ExitStatus> {
+        let base = git::resolve_commit(base).await?;
+        let head = git::resolve_commit(head).await?;
+        info!("Comparing commit {} with {}", head, base);
+
+        let comparison = client.compare_commits(&base, &head).await?;
+
+        match self.output_format() {
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&comparison)?);
+            }
+            OutputFormat::Human => {
+                println!("{}", format_comparison(&comparison));
+            }
+            OutputFormat::Plain => {
+                let sections = [
+                    ("newly failing", &comparison.newly_failing),
+                    ("newly fixed", &comparison.newly_fixed),
+                    ("still failing", &comparison.still_failing),
+                    ("added", &comparison.added),
+                    ("removed", &comparison.removed),
+                    ("duration changed", &comparison.duration_changes),
+                ];
+                for (label, changes) in sections {
+                    for change in changes {
+                        println!("{}: {}", label, change.title());
+                    }
+                }
+                for run in &comparison.newly_failing_runs {
+                    println!("newly failing run: {}", run.label());
+                }
+            }
+        }
+
+        Ok(if comparison.has_regressions() {
+            ExitStatus::BuildsFailed
+        } else {
+            ExitStatus::Success
+        })
+    }
+
+    /// Fetch and display the logs of the builds of a commit matching a filter
+    async fn fetch_commit_logs(
+        &self,
+        client: &GarnixClient,
+        commit_id: &str,
+        filter: &BuildFilter,
+        excerpt: Option<&ExcerptOptions>,
+    ) -> GarnixResult<()> {
+        let commit_id = git::resolve_commit(commit_id).await?;
+        info!("Fetching build logs for commit: {}", commit_id);
+
+        let results = client.fetch_logs_for_commit(&commit_id, filter).await?;
+
+        if self.output_format() == OutputFormat::Json {
+            match excerpt {
+                Some(options) => {
+                    let excerpts: Vec<_> = results
+                        .iter()
+                        .map(|BuildLogs { build, logs }| {
+                            json!({
+                                "build": build,
+                                "finished": logs.finished,
+                                "excerpt": excerpt::excerpt(logs, options)
+                            })
+                        })
+                        .collect();
+                    println!("{}", serde_json::to_string_pretty(&excerpts)?);
+                }
+                None => println!("{}", serde_json::to_string_pretty(&results)?),
+            }
+            return Ok(());
+        }
+
+        if results.is_empty() {
+            println!("No builds of commit {} match the given filters", commit_id);
+            return Ok(());
+        }
+
+        for (index, BuildLogs { build, logs }) in results.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            let system = build.system.as_deref().unwrap_or("unknown");
+            match self.output_format() {
+                OutputFormat::Human => {
+                    println!(
+                        "=== {} ({}) — {} ===",
+                        build.package,
+                        system,
+                        build.status_with_emoji()
+                    );
+                }
+                _ => println!("== {} ({}) {} ==", build.package, system, build.status),
+            }
+
+            if logs.logs.is_empty() {
+                println!("No logs available for build {}", build.id);
+            } else {
+                print_logs(logs, excerpt);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Print new log lines of a build as they appear until it finishes
+    async fn follow_build_logs(
+        &self,
+        client: &GarnixClient,
+        build_id: &str,
+        interval: Duration,
+    ) -> GarnixResult<()> {
+        info!("Following build logs for build: {}", build_id);
+
+        if self.output_format() == OutputFormat::Human {
+            println!("Following logs for build {} (Ctrl-C to stop):", build_id);
+            println!("{}", "=".repeat(60));
+        }
+
+        let mut entries = std::pin::pin!(client.stream_build_logs(build_id, interval));
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            match self.output_format() {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&entry)?),
+                OutputFormat::Human | OutputFormat::Plain => {
+                    println!("[{}] {}", entry.timestamp, entry.log_message)
+                }
+            }
+        }
+
+        if self.output_format() == OutputFormat::Human {
+            println!("{}", "=".repeat(60));
+            println!("Build {} finished", build_id);
+        }
+        Ok(())
+    }
+
     /// Print build status in human-readable format
-    fn print_human_readable(&self, response: &crate::types::GarnixResponse) {
+    fn print_human_readable(&self, response: &GarnixResponse) {
         use crate::format_build_summary;
 
         println!("{}", format_build_summary(response));
 
+        // Print failed runs with their full error, since they may have no builds
+        for run in response.failed_runs() {
+            if let Some(error) = &run.error {
+                println!("\n[FAIL] {} error:", run.label());
+                for line in error.lines() {
+                    println!("    {}", line);
+                }
+            }
+        }
+
         // Print individual builds if there are any failures
         if response.summary.failed > 0 {
             println!("\n[SEARCH] Failed Builds:");
@@ -269,6 +1011,9 @@ impl Cli {
                     build.system.as_deref().unwrap_or("unknown"),
                     build.status_with_emoji()
                 );
+                if let Some(duration) = build.duration() {
+                    println!("    Duration: {}", format_duration(duration));
+                }
                 if let Some(drv_path) = &build.drv_path {
                     println!("    Derivation: {}", drv_path);
                 }
@@ -288,14 +1033,19 @@ impl Cli {
     }
 
     /// Print build status in plain text format
-    fn print_plain_text(&self, response: &crate::types::GarnixResponse) {
+    fn print_plain_text(&self, response: &GarnixResponse) {
         println!("Build Status for {}", response.summary.git_commit);
         println!(
             "Repository: {}/{}",
             response.summary.repo_owner, response.summary.repo_name
         );
         println!("Branch: {}", response.summary.branch);
-        println!("Started: {}", response.summary.start_time);
+        if let Some(start_time) = response.summary.start_time {
+            println!("Started: {}", start_time.to_rfc3339());
+        }
+        if let Some(wall_clock) = response.wall_clock() {
+            println!("Wall Clock: {}", format_duration(wall_clock));
+        }
         println!();
 
         println!("Summary:");
@@ -309,10 +1059,14 @@ impl Cli {
             println!("Individual Builds:");
             for build in &response.builds {
                 println!(
-                    "  {} - {} ({})",
+                    "  {} - {} ({}) {}",
                     build.package,
                     build.status,
-                    build.system.as_deref().unwrap_or("unknown")
+                    build.system.as_deref().unwrap_or("unknown"),
+                    build
+                        .duration()
+                        .map(format_duration)
+                        .unwrap_or_else(|| "running".to_string())
                 );
             }
         }
@@ -321,10 +1075,22 @@ impl Cli {
     }
 }
 
+/// Print every log entry with its timestamp, or just an excerpt
+fn print_logs(logs: &LogResponse, excerpt: Option<&ExcerptOptions>) {
+    match excerpt {
+        Some(options) => println!("{}", excerpt::excerpt(logs, options).render(options)),
+        None => {
+            for log_entry in &logs.logs {
+                println!("[{}] {}", log_entry.timestamp, log_entry.log_message);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{Build, GarnixResponse, Summary};
+    use crate::types::{Build, Summary};
 
     fn create_test_response() -> GarnixResponse {
         GarnixResponse {
@@ -335,7 +1101,7 @@ mod tests {
                 git_commit: "7a2f5e9c1b4d8a3e6f2a9e5c8b1d4f7a3c6e9b2d".to_string(),
                 branch: "main".to_string(),
                 req_user: "testuser".to_string(),
-                start_time: "2024-01-01T00:00:00Z".to_string(),
+                start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
                 succeeded: 2,
                 failed: 1,
                 pending: 0,
@@ -354,8 +1120,8 @@ mod tests {
                     system: Some("x86_64-linux".to_string()),
                     req_user: "testuser".to_string(),
                     status: "Success".to_string(),
-                    start_time: "2024-01-01T00:00:00Z".to_string(),
-                    end_time: "2024-01-01T00:01:00Z".to_string(),
+                    start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+                    end_time: Some("2024-01-01T00:01:00Z".parse().unwrap()),
                     drv_path: None,
                     output_paths: None,
                     github_run_id: 123,
@@ -375,8 +1141,8 @@ mod tests {
                     system: Some("x86_64-linux".to_string()),
                     req_user: "testuser".to_string(),
                     status: "Failed".to_string(),
-                    start_time: "2024-01-01T00:00:00Z".to_string(),
-                    end_time: "2024-01-01T00:01:30Z".to_string(),
+                    start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+                    end_time: Some("2024-01-01T00:01:30Z".parse().unwrap()),
                     drv_path: Some("/nix/store/test.drv".to_string()),
                     output_paths: None,
                     github_run_id: 124,
@@ -413,6 +1179,144 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_commit_defaults_to_head() {
+        let cli = Cli::try_parse_from(&["garnix-insights", "fetch"]).unwrap();
+
+        match cli.command.unwrap() {
+            Commands::Fetch { commit_id, .. } => assert_eq!(commit_id, "HEAD"),
+            _ => panic!("Wrong command parsed"),
+        }
+    }
+
+    #[test]
+    fn test_cli_wait_parsing() {
+        let cli = Cli::try_parse_from(&[
+            "garnix-insights",
+            "wait",
+            "--commit-id",
+            "5d9e2f7a1c4b8e3a6f1d9e2a7f5c8b3e6a1f4d9e",
+            "--interval",
+            "5",
+            "--timeout",
+            "0",
+        ])
+        .unwrap();
+
+        match cli.command.unwrap() {
+            Commands::Wait {
+                commit_id,
+                interval,
+                timeout,
+                ..
+            } => {
+                assert_eq!(commit_id, "5d9e2f7a1c4b8e3a6f1d9e2a7f5c8b3e6a1f4d9e");
+                assert_eq!(interval, 5);
+                assert_eq!(timeout, 0);
+            }
+            _ => panic!("Wrong command parsed"),
+        }
+    }
+
+    #[test]
+    fn test_exit_status_from_wait_outcome() {
+        let mut response = create_test_response();
+        let outcome = WaitOutcome::Settled(response.clone());
+        assert_eq!(
+            ExitStatus::from_wait_outcome(&outcome),
+            ExitStatus::BuildsFailed
+        );
+
+        response.summary.failed = 0;
+        response.summary.cancelled = 1;
+        let outcome = WaitOutcome::Settled(response.clone());
+        assert_eq!(
+            ExitStatus::from_wait_outcome(&outcome),
+            ExitStatus::BuildsCancelled
+        );
+
+        response.summary.cancelled = 0;
+        assert_eq!(
+            ExitStatus::from_wait_outcome(&WaitOutcome::Settled(response.clone())),
+            ExitStatus::Success
+        );
+
+        response.summary.pending = 1;
+        let outcome = WaitOutcome::TimedOut(response.clone());
+        assert_eq!(
+            ExitStatus::from_wait_outcome(&outcome),
+            ExitStatus::TimedOut
+        );
+
+        // Timing out before evaluation created any builds
+        response.summary.pending = 0;
+        response.builds.clear();
+        let outcome = WaitOutcome::TimedOut(response);
+        assert_eq!(
+            ExitStatus::from_wait_outcome(&outcome),
+            ExitStatus::TimedOut
+        );
+        assert_eq!(ExitStatus::TimedOut.code(), 5);
+    }
+
+    #[test]
+    fn test_exit_status_from_response() {
+        let mut response = create_test_response();
+        assert_eq!(
+            ExitStatus::from_response(&response),
+            ExitStatus::BuildsFailed
+        );
+
+        response.summary.failed = 0;
+        response.summary.pending = 1;
+        response.summary.cancelled = 1;
+        assert_eq!(
+            ExitStatus::from_response(&response),
+            ExitStatus::BuildsPending
+        );
+
+        response.summary.pending = 0;
+        assert_eq!(
+            ExitStatus::from_response(&response),
+            ExitStatus::BuildsCancelled
+        );
+
+        response.summary.cancelled = 0;
+        assert_eq!(ExitStatus::from_response(&response), ExitStatus::Success);
+        assert_eq!(ExitStatus::Success.code(), 0);
+
+        response.runs.push(crate::types::Run {
+            status: Some("Failed".to_string()),
+            error: Some("evaluation failed".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            ExitStatus::from_response(&response),
+            ExitStatus::BuildsFailed
+        );
+    }
+
+    #[test]
+    fn test_exit_status_from_error() {
+        assert_eq!(
+            ExitStatus::from_error(&GarnixError::auth("bad token")),
+            ExitStatus::AuthError
+        );
+        assert_eq!(
+            ExitStatus::from_error(&GarnixError::not_found("no commit")),
+            ExitStatus::NotFound
+        );
+        assert_eq!(
+            ExitStatus::from_error(&GarnixError::network("refused")),
+            ExitStatus::NetworkError
+        );
+        assert_eq!(
+            ExitStatus::from_error(&GarnixError::config("no token")),
+            ExitStatus::Error
+        );
+        assert_eq!(ExitStatus::NetworkError.code(), 8);
+    }
+
     #[test]
     fn test_cli_server_parsing() {
         let cli = Cli::try_parse_from(&[
@@ -426,9 +1330,14 @@ mod tests {
         .unwrap();
 
         match cli.command.unwrap() {
-            Commands::Server { bind_address, port } => {
+            Commands::Server {
+                bind_address,
+                port,
+                mcp_secret,
+            } => {
                 assert_eq!(bind_address, "0.0.0.0");
                 assert_eq!(port, 3000);
+                assert_eq!(mcp_secret, None);
             }
             _ => panic!("Wrong command parsed"),
         }
@@ -439,6 +1348,31 @@ mod tests {
         let cli = Cli::try_parse_from(&["garnix-insights", "mcp"]).unwrap();
 
         assert!(matches!(cli.command.unwrap(), Commands::Mcp));
+        assert_eq!(cli.mcp_token_policy, None);
+
+        let cli =
+            Cli::try_parse_from(&["garnix-insights", "--mcp-token-policy", "disallowed", "mcp"])
+                .unwrap();
+        assert_eq!(cli.mcp_token_policy, Some(TokenPolicy::Disallowed));
+
+        let cli = Cli::try_parse_from(&[
+            "garnix-insights",
+            "--mcp-tools",
+            "get_build_status,check_commit_ready",
+            "mcp",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli.mcp_tools,
+            Some(vec![
+                "get_build_status".to_string(),
+                "check_commit_ready".to_string()
+            ])
+        );
+        assert!(
+            Cli::try_parse_from(&["garnix-insights", "--mcp-token-policy", "never", "mcp"])
+                .is_err()
+        );
     }
 
     #[test]
@@ -453,7 +1387,7 @@ mod tests {
 
         match cli.command.unwrap() {
             Commands::ValidateToken { jwt_token } => {
-                assert_eq!(jwt_token, "test-token");
+                assert_eq!(jwt_token.as_deref(), Some("test-token"));
             }
             _ => panic!("Wrong command parsed"),
         }
@@ -475,9 +1409,12 @@ mod tests {
             Commands::Logs {
                 jwt_token,
                 build_id,
+                follow,
+                ..
             } => {
-                assert_eq!(jwt_token, "test-token");
-                assert_eq!(build_id, "build123");
+                assert_eq!(jwt_token.as_deref(), Some("test-token"));
+                assert_eq!(build_id.as_deref(), Some("build123"));
+                assert!(!follow);
             }
             _ => panic!("Wrong command parsed"),
         }
@@ -497,7 +1434,28 @@ mod tests {
         ])
         .unwrap();
 
-        assert!(matches!(cli.format, OutputFormat::Json));
+        assert_eq!(cli.format, Some(OutputFormat::Json));
+    }
+
+    #[test]
+    fn test_profile_flags_parsing() {
+        let cli = Cli::try_parse_from(&[
+            "garnix-insights",
+            "--config",
+            "/tmp/garnix.toml",
+            "--profile",
+            "work",
+            "--repo",
+            "shift/garnix-insights",
+            "fetch",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.config, Some(PathBuf::from("/tmp/garnix.toml")));
+        assert_eq!(cli.profile.as_deref(), Some("work"));
+        assert_eq!(cli.repo.as_deref(), Some("shift/garnix-insights"));
+        assert_eq!(cli.format, None);
+        assert_eq!(cli.output_format(), OutputFormat::Human);
     }
 
     #[test]
@@ -545,13 +1503,18 @@ mod tests {
             jwt_token: Some("token".to_string()),
             commit_id: Some("8b3e6f1a4c9d2e7a5f8b1e4a7c2f5e9a6b3d8f1a".to_string()),
             verbose: true,
-            format: OutputFormat::Json,
+            format: Some(OutputFormat::Json),
+            config: None,
+            profile: None,
+            repo: None,
             mcp_version: None,
+            mcp_token_policy: None,
+            mcp_tools: None,
             command: Some(Commands::Mcp),
         };
 
         assert!(cli.verbose);
-        assert!(matches!(cli.format, OutputFormat::Json));
+        assert_eq!(cli.format, Some(OutputFormat::Json));
         assert_eq!(cli.jwt_token, Some("token".to_string()));
         assert_eq!(
             cli.commit_id,
@@ -565,8 +1528,13 @@ mod tests {
             jwt_token: None,
             commit_id: Some("test123".to_string()),
             verbose: false,
-            format: OutputFormat::Human,
+            format: Some(OutputFormat::Human),
+            config: None,
+            profile: None,
+            repo: None,
             mcp_version: None,
+            mcp_token_policy: None,
+            mcp_tools: None,
             command: Some(Commands::Fetch {
                 jwt_token: None,
                 commit_id: "test123".to_string(),
@@ -578,6 +1546,47 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_cli_checks_tool_names_for_mcp_only() {
+        let mut upstream = mockito::Server::new_async().await;
+        let _status = upstream
+            .mock("GET", "/builds/7a2f5e9c1b4d8a3e6f2a9e5c8b1d4f7a3c6e9b2d")
+            .with_body(serde_json::to_string(&create_test_response()).unwrap())
+            .create_async()
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            format!("[profiles.default]\nbase_url = \"{}\"\n", upstream.url()),
+        )
+        .unwrap();
+        let cli = |command| Cli {
+            jwt_token: Some("test-token".to_string()),
+            commit_id: None,
+            verbose: false,
+            format: Some(OutputFormat::Plain),
+            config: Some(path.clone()),
+            profile: None,
+            repo: None,
+            mcp_version: None,
+            mcp_token_policy: None,
+            mcp_tools: Some(vec!["unknown_tool".to_string()]),
+            command: Some(command),
+        };
+
+        let result = cli(Commands::Fetch {
+            jwt_token: None,
+            commit_id: "7a2f5e9c1b4d8a3e6f2a9e5c8b1d4f7a3c6e9b2d".to_string(),
+        })
+        .run()
+        .await;
+        assert!(result.is_ok(), "{result:?}");
+
+        let result = cli(Commands::Mcp).run().await;
+        assert!(matches!(result, Err(GarnixError::ConfigError(e)) if e.contains("unknown_tool")));
+    }
+
     #[test]
     fn test_output_format_variants() {
         // Test that all variants can be created
@@ -590,4 +1599,150 @@ mod tests {
         assert!(!format!("{:?}", OutputFormat::Json).is_empty());
         assert!(!format!("{:?}", OutputFormat::Plain).is_empty());
     }
+
+    #[test]
+    fn test_cli_logs_follow_parsing() {
+        let cli = Cli::try_parse_from([
+            "garnix-insights",
+            "logs",
+            "--build-id",
+            "build123",
+            "--follow",
+            "--interval",
+            "5",
+        ])
+        .unwrap();
+
+        match cli.command.unwrap() {
+            Commands::Logs {
+                follow, interval, ..
+            } => {
+                assert!(follow);
+                assert_eq!(interval, 5);
+            }
+            _ => panic!("Wrong command parsed"),
+        }
+
+        // --interval only makes sense when following
+        assert!(Cli::try_parse_from([
+            "garnix-insights",
+            "logs",
+            "--build-id",
+            "build123",
+            "--interval",
+            "5",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_logs_by_commit_parsing() {
+        let cli = Cli::try_parse_from([
+            "garnix-insights",
+            "logs",
+            "--commit-id",
+            "HEAD",
+            "--package",
+            "foo",
+            "--system",
+            "x86_64-linux",
+            "--failed",
+        ])
+        .unwrap();
+
+        match cli.command.unwrap() {
+            Commands::Logs {
+                build_id,
+                commit_id,
+                package,
+                system,
+                failed,
+                ..
+            } => {
+                assert_eq!(build_id, None);
+                assert_eq!(commit_id.as_deref(), Some("HEAD"));
+                assert_eq!(package.as_deref(), Some("foo"));
+                assert_eq!(system.as_deref(), Some("x86_64-linux"));
+                assert!(failed);
+            }
+            _ => panic!("Wrong command parsed"),
+        }
+
+        let parse =
+            |args: &[&str]| Cli::try_parse_from([&["garnix-insights", "logs"], args].concat());
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--build-id", "b", "--commit-id", "HEAD"]).is_err());
+        assert!(parse(&["--build-id", "b", "--package", "foo"]).is_err());
+        assert!(parse(&["--package", "foo"]).is_err());
+        assert!(parse(&["--commit-id", "HEAD", "--follow"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_diagnose_parsing() {
+        let cli = Cli::try_parse_from(["garnix-insights", "diagnose", "--package", "foo"]).unwrap();
+
+        match cli.command.unwrap() {
+            Commands::Diagnose {
+                commit_id,
+                package,
+                system,
+                ..
+            } => {
+                assert_eq!(commit_id, "HEAD");
+                assert_eq!(package.as_deref(), Some("foo"));
+                assert_eq!(system, None);
+            }
+            _ => panic!("Wrong command parsed"),
+        }
+    }
+
+    #[test]
+    fn test_cli_compare_parsing() {
+        let cli = Cli::try_parse_from(["garnix-insights", "compare", "--base", "v1.0"]).unwrap();
+
+        match cli.command.unwrap() {
+            Commands::Compare { base, head, .. } => {
+                assert_eq!(base, "v1.0");
+                assert_eq!(head, "HEAD");
+            }
+            _ => panic!("Wrong command parsed"),
+        }
+    }
+
+    #[test]
+    fn test_cli_logs_excerpt_parsing() {
+        let cli = Cli::try_parse_from([
+            "garnix-insights",
+            "logs",
+            "--build-id",
+            "build123",
+            "--excerpt",
+            "--context",
+            "3",
+            "--last",
+        ])
+        .unwrap();
+
+        match cli.command.unwrap() {
+            Commands::Logs {
+                excerpt,
+                context,
+                last,
+                no_collapse,
+                ..
+            } => {
+                assert!(excerpt);
+                assert_eq!(context, 3);
+                assert!(last);
+                assert!(!no_collapse);
+            }
+            _ => panic!("Wrong command parsed"),
+        }
+
+        let parse =
+            |args: &[&str]| Cli::try_parse_from([&["garnix-insights", "logs"], args].concat());
+        assert!(parse(&["--build-id", "b", "--context", "3"]).is_err());
+        assert!(parse(&["--build-id", "b", "--excerpt", "--follow"]).is_err());
+        assert!(parse(&["--commit-id", "HEAD", "--excerpt", "--no-collapse"]).is_ok());
+    }
 }
@@ -1,14 +1,78 @@
 //! HTTP client for interacting with the Garnix.io API
 
-use crate::error::GarnixError;
-use crate::types::{GarnixResponse, LogResponse};
-use reqwest::{Client, StatusCode};
-use tracing::{error, info, warn};
+use crate::analysis::FailureReport;
+use crate::auth::{StaticTokenProvider, TokenClaims, TokenProvider};
+use crate::compare::{self, Comparison};
+use crate::error::{GarnixError, HttpErrorDetails};
+use crate::retry::RetryPolicy;
+use crate::types::{
+    Build, BuildFilter, BuildLogs, BuildTransition, GarnixResponse, LogEntry, LogResponse, UserInfo,
+};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use reqwest::{header, Client, Response, StatusCode};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info, info_span, warn, Instrument};
+
+/// Default base URL of the Garnix.io API
+pub const DEFAULT_BASE_URL: &str = "https://garnix.io/api";
+
+/// Maximum number of requests sent concurrently on behalf of a single call,
+/// e.g. when fetching the logs of all builds of a commit
+pub const MAX_CONCURRENT_REQUESTS: usize = 4;
 
 /// HTTP client for Garnix.io API
+///
+/// Requests are authenticated with the token supplied by the configured
+/// [`TokenProvider`]. Cloning is cheap and shares the underlying connection pool.
+#[derive(Clone)]
 pub struct GarnixClient {
     client: Client,
     base_url: String,
+    retry_policy: RetryPolicy,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+}
+
+/// Options controlling [`GarnixClient::wait_for_completion`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitOptions {
+    /// Delay between two polls of the build status
+    pub interval: Duration,
+    /// Maximum time to wait, or `None` to wait indefinitely
+    pub timeout: Option<Duration>,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            timeout: Some(Duration::from_secs(30 * 60)),
+        }
+    }
+}
+
+/// Result of waiting for a commit's builds to settle
+#[derive(Debug, Clone)]
+pub enum WaitOutcome {
+    /// No builds are pending anymore
+    Settled(GarnixResponse),
+    /// The timeout elapsed while builds were still pending
+    TimedOut(GarnixResponse),
+}
+
+impl WaitOutcome {
+    /// Get the last build status that was observed
+    pub fn response(&self) -> &GarnixResponse {
+        match self {
+            WaitOutcome::Settled(response) | WaitOutcome::TimedOut(response) => response,
+        }
+    }
+
+    /// Check if waiting stopped because of the timeout
+    pub fn is_timed_out(&self) -> bool {
+        matches!(self, WaitOutcome::TimedOut(_))
+    }
 }
 
 impl Default for GarnixClient {
@@ -22,7 +86,9 @@ impl GarnixClient {
     pub fn new() -> Self {
         Self {
             client: Client::new(),
-            base_url: "https://garnix.io/api".to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            retry_policy: RetryPolicy::default(),
+            token_provider: None,
         }
     }
 
@@ -31,6 +97,8 @@ impl GarnixClient {
         Self {
             client: Client::new(),
             base_url: base_url.into(),
+            retry_policy: RetryPolicy::default(),
+            token_provider: None,
         }
     }
 
@@ -38,7 +106,9 @@ impl GarnixClient {
     pub fn with_client(client: Client) -> Self {
         Self {
             client,
-            base_url: "https://garnix.io/api".to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            retry_policy: RetryPolicy::default(),
+            token_provider: None,
         }
     }
 
@@ -47,13 +117,31 @@ impl GarnixClient {
         Self {
             client,
             base_url: base_url.into(),
+            retry_policy: RetryPolicy::default(),
+            token_provider: None,
         }
     }
 
+    /// Set the retry policy used for all requests
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Set the provider supplying the JWT token for all requests
+    pub fn with_token_provider(mut self, provider: Arc<dyn TokenProvider>) -> Self {
+        self.token_provider = Some(provider);
+        self
+    }
+
+    /// Authenticate all requests with a fixed JWT token
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        self.with_token_provider(Arc::new(StaticTokenProvider::new(token)))
+    }
+
     /// Fetch build status for a specific commit
     ///
     /// # Arguments
-    /// * `jwt_token` - JWT authentication token for Garnix.io API
     /// * `commit_id` - Git commit hash to fetch build status for
     ///
     /// # Returns
@@ -61,71 +149,30 @@ impl GarnixClient {
     ///
     /// # Errors
     /// Returns `GarnixError` on network errors, authentication failures, or API errors
-    pub async fn fetch_build_status(
-        &self,
-        jwt_token: &str,
-        commit_id: &str,
-    ) -> Result<GarnixResponse, GarnixError> {
+    pub async fn fetch_build_status(&self, commit_id: &str) -> Result<GarnixResponse, GarnixError> {
         info!("Fetching build status for commit: {}", commit_id);
 
         let url = format!("{}/builds/{}", self.base_url, commit_id);
-
         let response = self
-            .client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", jwt_token))
-            .send()
-            .await
-            .map_err(|e| {
-                error!("Network error fetching build status: {}", e);
-                GarnixError::NetworkError(e.to_string())
-            })?;
+            .get(
+                "fetch_build_status",
+                &url,
+                &format!("Commit {} not found", commit_id),
+            )
+            .await?;
 
-        let status_code = response.status();
-        info!("API response status: {}", status_code);
+        let garnix_response = response.json::<GarnixResponse>().await.map_err(|e| {
+            error!("Failed to parse JSON response: {}", e);
+            GarnixError::ParseError(e.to_string())
+        })?;
 
-        match status_code {
-            StatusCode::OK => {
-                let garnix_response = response.json::<GarnixResponse>().await.map_err(|e| {
-                    error!("Failed to parse JSON response: {}", e);
-                    GarnixError::ParseError(e.to_string())
-                })?;
-
-                info!("Successfully fetched build status for commit {}", commit_id);
-                Ok(garnix_response)
-            }
-            StatusCode::UNAUTHORIZED => {
-                error!("Authentication failed - invalid JWT token");
-                Err(GarnixError::AuthenticationError(
-                    "Invalid JWT token".to_string(),
-                ))
-            }
-            StatusCode::NOT_FOUND => {
-                error!("Commit not found: {}", commit_id);
-                Err(GarnixError::NotFound(format!(
-                    "Commit {} not found",
-                    commit_id
-                )))
-            }
-            StatusCode::TOO_MANY_REQUESTS => {
-                warn!("Rate limited by Garnix API");
-                Err(GarnixError::RateLimit("Rate limit exceeded".to_string()))
-            }
-            _ => {
-                let error_text = response.text().await.unwrap_or_default();
-                error!("API error {}: {}", status_code, error_text);
-                Err(GarnixError::ApiError(format!(
-                    "HTTP {}: {}",
-                    status_code, error_text
-                )))
-            }
-        }
+        info!("Successfully fetched build status for commit {}", commit_id);
+        Ok(garnix_response)
     }
 
     /// Fetch build logs for a specific build
     ///
     /// # Arguments
-    /// * `jwt_token` - JWT authentication token for Garnix.io API
     /// * `build_id` - Unique build ID to fetch logs for
     ///
     /// # Returns
@@ -133,120 +180,531 @@ impl GarnixClient {
     ///
     /// # Errors
     /// Returns `GarnixError` on network errors, authentication failures, or API errors
-    pub async fn fetch_build_logs(
-        &self,
-        jwt_token: &str,
-        build_id: &str,
-    ) -> Result<LogResponse, GarnixError> {
+    pub async fn fetch_build_logs(&self, build_id: &str) -> Result<LogResponse, GarnixError> {
         info!("Fetching build logs for build: {}", build_id);
 
         let url = format!("{}/builds/{}/logs", self.base_url, build_id);
-
         let response = self
-            .client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", jwt_token))
-            .send()
+            .get(
+                "fetch_build_logs",
+                &url,
+                &format!("Build {} not found", build_id),
+            )
+            .await?;
+
+        let log_response = response.json::<LogResponse>().await.map_err(|e| {
+            error!("Failed to parse logs JSON response: {}", e);
+            GarnixError::ParseError(e.to_string())
+        })?;
+
+        info!(
+            "Successfully fetched {} log entries for build {}",
+            log_response.logs.len(),
+            build_id
+        );
+        Ok(log_response)
+    }
+
+    /// Fetch the logs of all builds of a commit matching a filter
+    ///
+    /// The build status is fetched first to resolve the matching builds, whose
+    /// logs are then fetched concurrently. Results keep the order of the builds.
+    ///
+    /// # Errors
+    /// Returns `GarnixError` if the build status or any of the logs cannot be fetched
+    pub async fn fetch_logs_for_commit(
+        &self,
+        commit_id: &str,
+        filter: &BuildFilter,
+    ) -> Result<Vec<BuildLogs>, GarnixError> {
+        let status = self.fetch_build_status(commit_id).await?;
+        let builds = status.filter_builds(filter);
+        info!(
+            "Fetching logs for {} of {} builds of commit {}",
+            builds.len(),
+            status.builds.len(),
+            commit_id
+        );
+
+        self.fetch_logs_for_builds(builds).await
+    }
+
+    /// Diagnose why the builds of a commit failed
+    ///
+    /// See [`Self::diagnose_status`].
+    ///
+    /// # Errors
+    /// Returns `GarnixError` if the build status or any of the logs cannot be fetched
+    pub async fn diagnose_commit(
+        &self,
+        commit_id: &str,
+        filter: &BuildFilter,
+    ) -> Result<Vec<FailureReport>, GarnixError> {
+        let status = self.fetch_build_status(commit_id).await?;
+        info!("Diagnosing failures of commit {}", commit_id);
+        let (reports, _) = self.diagnose_status(&status, filter).await?;
+        Ok(reports)
+    }
+
+    /// Diagnose the failures of an already fetched build status
+    ///
+    /// The logs of all failed builds matching the filter are fetched
+    /// concurrently and classified. Failed runs that report an error, such as
+    /// evaluation errors that produced no builds, are included unless the filter
+    /// selects a package or system. The logs are returned alongside the reports.
+    ///
+    /// # Errors
+    /// Returns `GarnixError` if any of the logs cannot be fetched
+    pub async fn diagnose_status(
+        &self,
+        status: &GarnixResponse,
+        filter: &BuildFilter,
+    ) -> Result<(Vec<FailureReport>, Vec<BuildLogs>), GarnixError> {
+        let filter = filter.clone().failed_only(true);
+
+        let mut reports: Vec<FailureReport> = Vec::new();
+        if filter.package.is_none() && filter.system.is_none() {
+            reports.extend(
+                status
+                    .failed_runs()
+                    .into_iter()
+                    .filter_map(FailureReport::for_run),
+            );
+        }
+
+        let logs = self
+            .fetch_logs_for_builds(status.filter_builds(&filter))
+            .await?;
+        reports.extend(
+            logs.iter()
+                .map(|build_logs| FailureReport::for_build(&build_logs.build, &build_logs.logs)),
+        );
+        Ok((reports, logs))
+    }
+
+    /// Compare the builds of a head commit with those of a base commit
+    ///
+    /// The build status of both commits is fetched concurrently.
+    ///
+    /// # Errors
+    /// Returns `GarnixError` if the build status of either commit cannot be fetched
+    pub async fn compare_commits(
+        &self,
+        base_commit: &str,
+        head_commit: &str,
+    ) -> Result<Comparison, GarnixError> {
+        info!("Comparing commit {} with {}", head_commit, base_commit);
+        let (base, head) = tokio::try_join!(
+            self.fetch_build_status(base_commit),
+            self.fetch_build_status(head_commit)
+        )?;
+        Ok(compare::compare(&base, &head))
+    }
+
+    /// Fetch the logs of several builds, at most [`MAX_CONCURRENT_REQUESTS`] at
+    /// a time, keeping their order
+    ///
+    /// A build whose logs do not exist (yet) gets empty logs instead of
+    /// failing the whole result.
+    async fn fetch_logs_for_builds(
+        &self,
+        builds: Vec<&Build>,
+    ) -> Result<Vec<BuildLogs>, GarnixError> {
+        stream::iter(builds)
+            .map(|build| async move {
+                let logs = match self.fetch_build_logs(&build.id).await {
+                    Ok(logs) => logs,
+                    Err(GarnixError::NotFound(msg)) => {
+                        warn!("No logs for build {}: {}", build.id, msg);
+                        LogResponse {
+                            finished: false,
+                            logs: Vec::new(),
+                        }
+                    }
+                    Err(e) => return Err(e),
+                };
+                Ok(BuildLogs {
+                    build: build.clone(),
+                    logs,
+                })
+            })
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .try_collect()
             .await
-            .map_err(|e| {
-                error!("Network error fetching build logs: {}", e);
-                GarnixError::NetworkError(e.to_string())
-            })?;
+    }
 
-        let status_code = response.status();
-        info!("Logs API response status: {}", status_code);
+    /// Follow the logs of a build, yielding each new entry as it appears
+    ///
+    /// The logs are polled every `interval` until Garnix reports the build as
+    /// finished. Entries already yielded are skipped by position, falling back
+    /// to their timestamp and message if the log shrinks or is rewritten
+    /// between polls. The stream ends after the last entry, or after yielding
+    /// the first error.
+    pub fn stream_build_logs<'a>(
+        &'a self,
+        build_id: &'a str,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<LogEntry, GarnixError>> + 'a {
+        struct FollowState {
+            seen: usize,
+            recent: Vec<LogEntry>,
+            pending: VecDeque<LogEntry>,
+            polls: u32,
+            done: bool,
+        }
 
-        match status_code {
-            StatusCode::OK => {
-                let log_response = response.json::<LogResponse>().await.map_err(|e| {
-                    error!("Failed to parse logs JSON response: {}", e);
-                    GarnixError::ParseError(e.to_string())
-                })?;
-
-                info!(
-                    "Successfully fetched {} log entries for build {}",
-                    log_response.logs.len(),
-                    build_id
-                );
-                Ok(log_response)
-            }
-            StatusCode::UNAUTHORIZED => {
-                error!("Authentication failed - invalid JWT token");
-                Err(GarnixError::AuthenticationError(
-                    "Invalid JWT token".to_string(),
-                ))
-            }
-            StatusCode::NOT_FOUND => {
-                error!("Build not found: {}", build_id);
-                Err(GarnixError::NotFound(format!(
-                    "Build {} not found",
-                    build_id
-                )))
-            }
-            StatusCode::TOO_MANY_REQUESTS => {
-                warn!("Rate limited by Garnix API");
-                Err(GarnixError::RateLimit("Rate limit exceeded".to_string()))
+        let state = FollowState {
+            seen: 0,
+            recent: Vec::new(),
+            pending: VecDeque::new(),
+            polls: 0,
+            done: false,
+        };
+
+        stream::unfold(state, move |mut state| async move {
+            loop {
+                if let Some(entry) = state.pending.pop_front() {
+                    return Some((Ok(entry), state));
+                }
+                if state.done {
+                    return None;
+                }
+
+                if state.polls > 0 {
+                    tokio::time::sleep(interval).await;
+                }
+                state.polls += 1;
+
+                match self.fetch_build_logs(build_id).await {
+                    Ok(response) => {
+                        let new = new_log_entries(&response.logs, state.seen, &state.recent);
+                        state.seen = response.logs.len();
+                        // Only entries sharing the latest timestamp are needed to de-duplicate
+                        state.recent.extend(new.iter().cloned());
+                        if let Some(last) = state.recent.last().cloned() {
+                            let latest = log_time(&last);
+                            state
+                                .recent
+                                .retain(|entry| match (log_time(entry), latest) {
+                                    (Some(time), Some(latest)) => time == latest,
+                                    _ => entry.timestamp == last.timestamp,
+                                });
+                        }
+                        state.pending.extend(new);
+                        state.done = response.finished;
+                    }
+                    Err(e) => {
+                        state.done = true;
+                        return Some((Err(e), state));
+                    }
+                }
             }
-            _ => {
-                let error_text = response.text().await.unwrap_or_default();
-                error!("Logs API error {}: {}", status_code, error_text);
-                Err(GarnixError::ApiError(format!(
-                    "HTTP {}: {}",
-                    status_code, error_text
-                )))
+        })
+    }
+
+    /// Poll the build status of a commit until it is settled
+    ///
+    /// A commit is settled once evaluation produced builds or finished, and
+    /// none of its builds is pending anymore. `on_transition` is called for
+    /// every build whose status changed between two polls. A commit that
+    /// Garnix does not know about yet is treated as not started, so waiting
+    /// can begin right after pushing.
+    ///
+    /// # Errors
+    /// Returns `GarnixError` if polling fails, or `NotFound` if the commit never
+    /// appeared before the timeout elapsed
+    pub async fn wait_for_completion<F>(
+        &self,
+        commit_id: &str,
+        options: &WaitOptions,
+        mut on_transition: F,
+    ) -> Result<WaitOutcome, GarnixError>
+    where
+        F: FnMut(&BuildTransition),
+    {
+        info!("Waiting for builds of commit {} to settle", commit_id);
+
+        let started = Instant::now();
+        let mut previous: Option<GarnixResponse> = None;
+
+        loop {
+            match self.fetch_build_status(commit_id).await {
+                Ok(response) => {
+                    if let Some(previous) = &previous {
+                        for transition in response.transitions_since(previous) {
+                            on_transition(&transition);
+                        }
+                    }
+                    if response.is_settled() {
+                        return Ok(WaitOutcome::Settled(response));
+                    }
+                    previous = Some(response);
+                }
+                Err(GarnixError::NotFound(msg)) => {
+                    info!("Commit not known to Garnix yet: {}", msg);
+                }
+                Err(e) => return Err(e),
             }
+
+            let remaining = match options.timeout {
+                Some(timeout) => match timeout.checked_sub(started.elapsed()) {
+                    Some(remaining) if !remaining.is_zero() => remaining,
+                    _ => {
+                        warn!("Timed out waiting for commit {}", commit_id);
+                        return match previous {
+                            Some(response) => Ok(WaitOutcome::TimedOut(response)),
+                            None => Err(GarnixError::NotFound(format!(
+                                "Commit {} not found",
+                                commit_id
+                            ))),
+                        };
+                    }
+                },
+                None => options.interval,
+            };
+
+            tokio::time::sleep(options.interval.min(remaining)).await;
         }
     }
 
-    /// Check if the API is accessible with the given token
-    ///
-    /// # Arguments
-    /// * `jwt_token` - JWT authentication token to validate
+    /// Check if the API is accessible with the configured token
     ///
     /// # Returns
-    /// Returns `Ok(())` if the token is valid, `Err(GarnixError)` otherwise
-    pub async fn validate_token(&self, jwt_token: &str) -> Result<(), GarnixError> {
+    /// Returns the account information from the `/user` endpoint if the token is
+    /// valid, `Err(GarnixError)` otherwise
+    pub async fn validate_token(&self) -> Result<UserInfo, GarnixError> {
         info!("Validating JWT token");
 
         let url = format!("{}/user", self.base_url);
+        let response = self
+            .get("validate_token", &url, "User endpoint not found")
+            .await?;
+
+        let body = response.text().await?;
+        let user_info = if body.trim().is_empty() {
+            UserInfo::default()
+        } else {
+            serde_json::from_str(&body).map_err(|e| {
+                error!("Failed to parse user JSON response: {}", e);
+                GarnixError::ParseError(e.to_string())
+            })?
+        };
+
+        info!("JWT token is valid");
+        Ok(user_info)
+    }
+
+    /// Decode the claims of the configured token locally, without contacting the API
+    ///
+    /// # Errors
+    /// Returns `GarnixError` if no token is available or it is not a well-formed JWT
+    pub async fn inspect_token(&self) -> Result<TokenClaims, GarnixError> {
+        TokenClaims::decode(&self.token().await?)
+    }
+
+    /// Get the base URL for the API
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the retry policy used for all requests
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Get the provider supplying the JWT token, if any
+    pub fn token_provider(&self) -> Option<&Arc<dyn TokenProvider>> {
+        self.token_provider.as_ref()
+    }
+
+    /// Obtain the current JWT token from the configured provider
+    async fn token(&self) -> Result<String, GarnixError> {
+        match &self.token_provider {
+            Some(provider) => provider.token().await,
+            None => Err(GarnixError::config(
+                "No JWT token configured (use --jwt-token, GARNIX_JWT_TOKEN or a profile token)",
+            )),
+        }
+    }
+
+    /// Obtain the current JWT token, refusing tokens that are known to have expired
+    ///
+    /// Tokens that cannot be decoded as a JWT are passed through unchanged and
+    /// left for the API to judge.
+    async fn usable_token(&self) -> Result<String, GarnixError> {
+        let token = self.token().await?;
+        if let Ok(claims) = TokenClaims::decode(&token) {
+            if let (true, Some(expires_at)) = (claims.is_expired(), claims.expires_at) {
+                error!("Refusing to send JWT token that expired at {}", expires_at);
+                return Err(GarnixError::AuthenticationError(format!(
+                    "JWT token expired at {}",
+                    expires_at.to_rfc3339()
+                )));
+            }
+        }
+        Ok(token)
+    }
+
+    /// Perform an authenticated GET request, retrying according to the retry policy
+    async fn get(
+        &self,
+        operation: &'static str,
+        url: &str,
+        not_found_message: &str,
+    ) -> Result<Response, GarnixError> {
+        let span = info_span!("garnix_request", operation);
+
+        async move {
+            let policy = &self.retry_policy;
+            let started = Instant::now();
+            let jwt_token = self.usable_token().await?;
+            let mut attempt = 0;
 
+            loop {
+                attempt += 1;
+
+                let error = match self
+                    .get_once(url, &jwt_token, not_found_message)
+                    .instrument(info_span!("attempt", attempt))
+                    .await
+                {
+                    Ok(response) => return Ok(response),
+                    Err(error) => error,
+                };
+
+                if !error.is_retryable() || attempt >= policy.max_attempts {
+                    return Err(error);
+                }
+
+                let delay = error
+                    .retry_after()
+                    .unwrap_or_else(|| policy.backoff(attempt));
+                if let Some(deadline) = policy.deadline {
+                    if started.elapsed() + delay > deadline {
+                        warn!("Retry deadline of {:?} exceeded, giving up", deadline);
+                        return Err(error);
+                    }
+                }
+
+                warn!(
+                    "Attempt {}/{} failed ({}), retrying in {:?}",
+                    attempt, policy.max_attempts, error, delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Perform a single authenticated GET request and map error statuses
+    async fn get_once(
+        &self,
+        url: &str,
+        jwt_token: &str,
+        not_found_message: &str,
+    ) -> Result<Response, GarnixError> {
         let response = self
             .client
-            .get(&url)
+            .get(url)
             .header("Authorization", format!("Bearer {}", jwt_token))
             .send()
             .await
             .map_err(|e| {
-                error!("Network error validating token: {}", e);
+                error!("Network error requesting {}: {}", url, e);
                 GarnixError::NetworkError(e.to_string())
             })?;
 
-        match response.status() {
-            StatusCode::OK => {
-                info!("JWT token is valid");
-                Ok(())
-            }
+        let status_code = response.status();
+        info!("API response status: {}", status_code);
+
+        match status_code {
+            status if status.is_success() => Ok(response),
             StatusCode::UNAUTHORIZED => {
-                error!("Invalid JWT token");
+                error!("Authentication failed - invalid JWT token");
                 Err(GarnixError::AuthenticationError(
                     "Invalid JWT token".to_string(),
                 ))
             }
-            status => {
+            StatusCode::NOT_FOUND => {
+                error!("{}", not_found_message);
+                Err(GarnixError::NotFound(not_found_message.to_string()))
+            }
+            _ => {
+                let retry_after = retry_after(&response);
+                let request_id = response
+                    .headers()
+                    .get(REQUEST_ID_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
                 let error_text = response.text().await.unwrap_or_default();
-                error!("Token validation error {}: {}", status, error_text);
-                Err(GarnixError::ApiError(format!(
-                    "HTTP {}: {}",
-                    status, error_text
-                )))
+
+                if status_code == StatusCode::TOO_MANY_REQUESTS {
+                    warn!("Rate limited by Garnix API");
+                } else {
+                    error!("API error {}: {}", status_code, error_text);
+                }
+
+                Err(GarnixError::http(
+                    HttpErrorDetails::new(status_code.as_u16(), url, error_text)
+                        .retry_after(retry_after)
+                        .request_id(request_id),
+                ))
             }
         }
     }
+}
 
-    /// Get the base URL for the API
-    pub fn base_url(&self) -> &str {
-        &self.base_url
+/// Select the log entries that were not yielded yet
+///
+/// `recent` holds the yielded entries sharing the latest timestamp, ending
+/// with the last yielded one. Logs normally only grow, so entries after
+/// position `seen` are new as long as the entry before them is still the last
+/// one yielded. Otherwise entries with a later timestamp are new, and entries
+/// at the same (or an unparseable) timestamp are new unless an identical
+/// entry was yielded already.
+fn new_log_entries(logs: &[LogEntry], seen: usize, recent: &[LogEntry]) -> Vec<LogEntry> {
+    let Some(last) = recent.last() else {
+        return logs[seen.min(logs.len())..].to_vec();
+    };
+
+    if seen <= logs.len() && seen > 0 && &logs[seen - 1] == last {
+        return logs[seen..].to_vec();
     }
+
+    let latest = log_time(last);
+    logs.iter()
+        .filter(|entry| match (log_time(entry), latest) {
+            (Some(time), Some(latest)) if time != latest => time > latest,
+            _ => !recent.contains(entry),
+        })
+        .cloned()
+        .collect()
+}
+
+/// Parse the timestamp of a log entry
+fn log_time(entry: &LogEntry) -> Option<chrono::DateTime<chrono::Utc>> {
+    entry.timestamp.parse().ok()
+}
+
+/// Header carrying the upstream request id
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Parse the `Retry-After` header, given either in seconds or as an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value)
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delta = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delta.to_std().unwrap_or(Duration::ZERO))
 }
 
 #[cfg(test)]
@@ -270,8 +728,8 @@ mod tests {
             system: Some("x86_64-linux".to_string()),
             req_user: "testuser".to_string(),
             status: "Success".to_string(),
-            start_time: "2024-01-01T00:00:00Z".to_string(),
-            end_time: "2024-01-01T00:01:00Z".to_string(),
+            start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+            end_time: Some("2024-01-01T00:01:00Z".parse().unwrap()),
             drv_path: Some("/nix/store/test.drv".to_string()),
             output_paths: Some(HashMap::from([(
                 "out".to_string(),
@@ -287,7 +745,7 @@ mod tests {
     #[tokio::test]
     async fn test_fetch_build_status_success() {
         let mut server = Server::new_async().await;
-        let client = GarnixClient::with_base_url(server.url());
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
 
         let mock_response = json!({
             "summary": {
@@ -317,7 +775,7 @@ mod tests {
             .await;
 
         let result = client
-            .fetch_build_status("test-token", "3b8e1f2a9c5d7e4a6b2f9e7c1a4d8f3a5c2e9b7d")
+            .fetch_build_status("3b8e1f2a9c5d7e4a6b2f9e7c1a4d8f3a5c2e9b7d")
             .await;
         assert!(result.is_ok());
 
@@ -332,7 +790,7 @@ mod tests {
     #[tokio::test]
     async fn test_fetch_build_status_unauthorized() {
         let mut server = Server::new_async().await;
-        let client = GarnixClient::with_base_url(server.url());
+        let client = GarnixClient::with_base_url(server.url()).with_token("invalid-token");
 
         let _mock = server
             .mock("GET", "/builds/4c9f2e7b1a5d8e3a6b1f4e9c2a7d5f8a3c6e1b9f")
@@ -342,7 +800,7 @@ mod tests {
             .await;
 
         let result = client
-            .fetch_build_status("invalid-token", "4c9f2e7b1a5d8e3a6b1f4e9c2a7d5f8a3c6e1b9f")
+            .fetch_build_status("4c9f2e7b1a5d8e3a6b1f4e9c2a7d5f8a3c6e1b9f")
             .await;
         assert!(matches!(result, Err(GarnixError::AuthenticationError(_))));
     }
@@ -350,7 +808,7 @@ mod tests {
     #[tokio::test]
     async fn test_fetch_build_status_not_found() {
         let mut server = Server::new_async().await;
-        let client = GarnixClient::with_base_url(server.url());
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
 
         let _mock = server
             .mock("GET", "/builds/nonexistent")
@@ -359,14 +817,14 @@ mod tests {
             .create_async()
             .await;
 
-        let result = client.fetch_build_status("test-token", "nonexistent").await;
+        let result = client.fetch_build_status("nonexistent").await;
         assert!(matches!(result, Err(GarnixError::NotFound(_))));
     }
 
     #[tokio::test]
     async fn test_fetch_build_logs_success() {
         let mut server = Server::new_async().await;
-        let client = GarnixClient::with_base_url(server.url());
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
 
         let mock_response = json!({
             "finished": true,
@@ -391,7 +849,7 @@ mod tests {
             .create_async()
             .await;
 
-        let result = client.fetch_build_logs("test-token", "test-build-1").await;
+        let result = client.fetch_build_logs("test-build-1").await;
         assert!(result.is_ok());
 
         let response = result.unwrap();
@@ -403,7 +861,7 @@ mod tests {
     #[tokio::test]
     async fn test_validate_token_success() {
         let mut server = Server::new_async().await;
-        let client = GarnixClient::with_base_url(server.url());
+        let client = GarnixClient::with_base_url(server.url()).with_token("valid-token");
 
         let _mock = server
             .mock("GET", "/user")
@@ -413,14 +871,14 @@ mod tests {
             .create_async()
             .await;
 
-        let result = client.validate_token("valid-token").await;
-        assert!(result.is_ok());
+        let user = client.validate_token().await.unwrap();
+        assert_eq!(user.username.as_deref(), Some("testuser"));
     }
 
     #[tokio::test]
     async fn test_validate_token_unauthorized() {
         let mut server = Server::new_async().await;
-        let client = GarnixClient::with_base_url(server.url());
+        let client = GarnixClient::with_base_url(server.url()).with_token("invalid-token");
 
         let _mock = server
             .mock("GET", "/user")
@@ -429,7 +887,7 @@ mod tests {
             .create_async()
             .await;
 
-        let result = client.validate_token("invalid-token").await;
+        let result = client.validate_token().await;
         assert!(matches!(result, Err(GarnixError::AuthenticationError(_))));
     }
 
@@ -448,5 +906,707 @@ mod tests {
         let custom_client = reqwest::Client::new();
         let client = GarnixClient::with_client_and_url(custom_client, "https://custom.url");
         assert_eq!(client.base_url(), "https://custom.url");
+        assert_eq!(client.retry_policy(), &RetryPolicy::default());
+        assert!(client.token_provider().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_missing_token_provider() {
+        let client = GarnixClient::with_base_url("http://127.0.0.1:9");
+        let result = client.fetch_build_status("abc1234").await;
+        assert!(matches!(result, Err(GarnixError::ConfigError(_))));
+    }
+
+    fn test_jwt(exp: i64) -> String {
+        use base64::Engine;
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let payload = json!({ "sub": "user-42", "exp": exp }).to_string();
+        format!("e30.{}.sig", engine.encode(payload))
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_not_sent() {
+        let mut server = Server::new_async().await;
+        let expired = test_jwt(chrono::Utc::now().timestamp() - 60);
+        let client = GarnixClient::with_base_url(server.url()).with_token(expired.clone());
+        let mock = server.mock("GET", "/user").expect(0).create_async().await;
+
+        let result = client.validate_token().await;
+        assert!(
+            matches!(result, Err(GarnixError::AuthenticationError(msg)) if msg.contains("expired"))
+        );
+        mock.assert_async().await;
+
+        let claims = client.inspect_token().await.unwrap();
+        assert_eq!(claims.subject.as_deref(), Some("user-42"));
+        assert!(claims.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_user_info() {
+        let mut server = Server::new_async().await;
+        let token = test_jwt(chrono::Utc::now().timestamp() + 3600);
+        let client = GarnixClient::with_base_url(server.url()).with_token(token);
+        let _mock = server
+            .mock("GET", "/user")
+            .with_status(200)
+            .with_body(r#"{"login": "octo", "email": "octo@example.com", "id": 7}"#)
+            .create_async()
+            .await;
+
+        let user = client.validate_token().await.unwrap();
+        assert_eq!(user.username.as_deref(), Some("octo"));
+        assert_eq!(user.email.as_deref(), Some("octo@example.com"));
+        assert_eq!(user.extra.get("id"), Some(&json!(7)));
+        assert!(client.inspect_token().await.unwrap().expires_soon());
+    }
+
+    #[tokio::test]
+    async fn test_token_provider_is_asked_per_request() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct RotatingToken(AtomicUsize);
+
+        #[async_trait::async_trait]
+        impl TokenProvider for RotatingToken {
+            async fn token(&self) -> crate::GarnixResult<String> {
+                Ok(format!("token-{}", self.0.fetch_add(1, Ordering::SeqCst)))
+            }
+
+            fn describe(&self) -> String {
+                "rotating test token".to_string()
+            }
+        }
+
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url())
+            .with_token_provider(Arc::new(RotatingToken(AtomicUsize::new(0))));
+        let first = server
+            .mock("GET", "/user")
+            .match_header("authorization", "Bearer token-0")
+            .with_status(200)
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/user")
+            .match_header("authorization", "Bearer token-1")
+            .with_status(200)
+            .create_async()
+            .await;
+
+        client.validate_token().await.unwrap();
+        client.validate_token().await.unwrap();
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    fn fast_retry_policy() -> RetryPolicy {
+        RetryPolicy::default()
+            .max_attempts(3)
+            .base_delay(Duration::from_millis(1))
+            .jitter(0.0)
+    }
+
+    #[tokio::test]
+    async fn test_fetch_build_logs_retries_server_errors() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url())
+            .with_token("test-token")
+            .with_retry_policy(fast_retry_policy());
+
+        let failing = server
+            .mock("GET", "/builds/test-build-1/logs")
+            .with_status(502)
+            .expect(2)
+            .create_async()
+            .await;
+        let succeeding = server
+            .mock("GET", "/builds/test-build-1/logs")
+            .with_status(200)
+            .with_body(r#"{"finished": true, "logs": []}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let result = client.fetch_build_logs("test-build-1").await;
+        assert!(result.is_ok());
+        failing.assert_async().await;
+        succeeding.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up_after_max_attempts() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url())
+            .with_token("test-token")
+            .with_retry_policy(fast_retry_policy());
+
+        let mock = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(3)
+            .create_async()
+            .await;
+
+        let error = client.fetch_build_status("abc1234").await.unwrap_err();
+        assert_eq!(error.status(), Some(429));
+        assert_eq!(error.category(), "rate_limit");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url())
+            .with_token("test-token")
+            .with_retry_policy(fast_retry_policy());
+
+        let mock = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(401)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let result = client.fetch_build_status("abc1234").await;
+        assert!(matches!(result, Err(GarnixError::AuthenticationError(_))));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_retry_respects_deadline() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url())
+            .with_token("test-token")
+            .with_retry_policy(fast_retry_policy().deadline(Some(Duration::from_secs(1))));
+
+        let mock = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(503)
+            .with_header("retry-after", "3600")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let error = client.fetch_build_status("abc1234").await.unwrap_err();
+        assert_eq!(error.status(), Some(503));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(3600)));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_http_error_metadata() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url())
+            .with_token("test-token")
+            .with_retry_policy(RetryPolicy::none());
+
+        let _mock = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(500)
+            .with_header("x-request-id", "req-42")
+            .with_body("boom")
+            .create_async()
+            .await;
+
+        let error = client.fetch_build_status("abc1234").await.unwrap_err();
+        let details = error.http_details().expect("structured HTTP error");
+        assert_eq!(details.status, 500);
+        assert_eq!(details.url, format!("{}/builds/abc1234", server.url()));
+        assert_eq!(details.request_id.as_deref(), Some("req-42"));
+        assert_eq!(details.body, "boom");
+    }
+
+    fn pending_response(pending: u32, status: &str) -> serde_json::Value {
+        let mut build = create_test_build();
+        build.status = status.to_string();
+        json!({
+            "summary": {
+                "repo_owner": "testuser",
+                "repo_name": "testrepo",
+                "repo_is_public": true,
+                "git_commit": "abc1234",
+                "branch": "main",
+                "req_user": "testuser",
+                "start_time": "2024-01-01T00:00:00Z",
+                "succeeded": 0,
+                "failed": if status == "Failed" { 1 } else { 0 },
+                "pending": pending,
+                "cancelled": 0
+            },
+            "builds": [build],
+            "runs": []
+        })
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_completion_reports_transitions() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+
+        let _pending = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(pending_response(1, "Pending").to_string())
+            .expect(2)
+            .create_async()
+            .await;
+        let _settled = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(pending_response(0, "Failed").to_string())
+            .create_async()
+            .await;
+
+        let options = WaitOptions {
+            interval: Duration::from_millis(1),
+            timeout: Some(Duration::from_secs(10)),
+        };
+        let mut transitions = Vec::new();
+        let outcome = client
+            .wait_for_completion("abc1234", &options, |t| transitions.push(t.clone()))
+            .await
+            .unwrap();
+
+        assert!(!outcome.is_timed_out());
+        assert_eq!(outcome.response().summary.failed, 1);
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].from.as_deref(), Some("Pending"));
+        assert_eq!(transitions[0].to, "Failed");
+    }
+
+    fn log_entry(timestamp: &str, message: &str) -> LogEntry {
+        LogEntry {
+            timestamp: timestamp.to_string(),
+            log_message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_new_log_entries() {
+        let a = log_entry("2024-01-01T00:00:01Z", "a");
+        let b = log_entry("2024-01-01T00:00:02Z", "b");
+        let c = log_entry("2024-01-01T00:00:03Z", "c");
+
+        assert_eq!(new_log_entries(&[a.clone(), b.clone()], 0, &[]).len(), 2);
+        assert_eq!(
+            new_log_entries(
+                &[a.clone(), b.clone(), c.clone()],
+                2,
+                std::slice::from_ref(&b)
+            ),
+            vec![c.clone()]
+        );
+        // Identical messages at a new position are still new
+        assert_eq!(
+            new_log_entries(&[a.clone(), a.clone()], 1, std::slice::from_ref(&a)),
+            vec![a.clone()]
+        );
+        // The log was rewritten: fall back to timestamps
+        assert_eq!(
+            new_log_entries(&[b.clone(), c.clone()], 2, std::slice::from_ref(&b)),
+            vec![c.clone()]
+        );
+        assert!(new_log_entries(&[a], 3, std::slice::from_ref(&c)).is_empty());
+
+        // Entries sharing the last timestamp are de-duplicated by message
+        let b2 = log_entry("2024-01-01T00:00:02Z", "b2");
+        assert_eq!(
+            new_log_entries(
+                &[b.clone(), b2.clone(), c.clone()],
+                5,
+                std::slice::from_ref(&b)
+            ),
+            vec![b2.clone(), c.clone()]
+        );
+        assert_eq!(
+            new_log_entries(&[b.clone(), b2.clone()], 5, &[b.clone(), b2]),
+            vec![]
+        );
+
+        // Timestamps are compared as times, not as strings
+        let offset = log_entry("2024-01-01T01:00:00+01:00", "offset");
+        let later = log_entry("2024-01-01T00:30:00Z", "later");
+        assert_eq!(
+            new_log_entries(std::slice::from_ref(&later), 5, &[offset]),
+            vec![later]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_build_logs_yields_new_entries() {
+        use futures::StreamExt;
+
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+
+        let logs = |finished: bool, messages: &[&str]| {
+            let logs: Vec<_> = messages
+                .iter()
+                .enumerate()
+                .map(|(i, m)| json!({"timestamp": format!("2024-01-01T00:00:0{}Z", i), "log_message": m}))
+                .collect();
+            json!({ "finished": finished, "logs": logs }).to_string()
+        };
+
+        let _first = server
+            .mock("GET", "/builds/build-1/logs")
+            .with_status(200)
+            .with_body(logs(false, &["building"]))
+            .expect(1)
+            .create_async()
+            .await;
+        let _second = server
+            .mock("GET", "/builds/build-1/logs")
+            .with_status(200)
+            .with_body(logs(false, &["building", "compiling"]))
+            .expect(1)
+            .create_async()
+            .await;
+        let _last = server
+            .mock("GET", "/builds/build-1/logs")
+            .with_status(200)
+            .with_body(logs(true, &["building", "compiling", "done"]))
+            .create_async()
+            .await;
+
+        let messages: Vec<String> = client
+            .stream_build_logs("build-1", Duration::from_millis(1))
+            .map(|entry| entry.unwrap().log_message)
+            .collect()
+            .await;
+
+        assert_eq!(messages, vec!["building", "compiling", "done"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_build_logs_mixed_timestamp_formats() {
+        use futures::StreamExt;
+
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+        let logs = |finished: bool, entries: &[(&str, &str)]| {
+            let logs: Vec<_> = entries
+                .iter()
+                .map(|(timestamp, m)| json!({"timestamp": timestamp, "log_message": m}))
+                .collect();
+            json!({ "finished": finished, "logs": logs }).to_string()
+        };
+
+        let _first = server
+            .mock("GET", "/builds/build-1/logs")
+            .with_status(200)
+            .with_body(logs(
+                false,
+                &[
+                    ("2024-01-01T00:00:00Z", "fetching"),
+                    ("2024-01-01T00:00:01Z", "building"),
+                    ("2024-01-01T00:00:01.000Z", "compiling"),
+                ],
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+        // The log is rewritten, so entries are matched by time and message
+        let _last = server
+            .mock("GET", "/builds/build-1/logs")
+            .with_status(200)
+            .with_body(logs(
+                true,
+                &[
+                    ("2024-01-01T00:00:01Z", "building"),
+                    ("2024-01-01T00:00:01.000Z", "compiling"),
+                    ("2024-01-01T00:00:02Z", "done"),
+                ],
+            ))
+            .create_async()
+            .await;
+
+        let messages: Vec<String> = client
+            .stream_build_logs("build-1", Duration::from_millis(1))
+            .map(|entry| entry.unwrap().log_message)
+            .collect()
+            .await;
+
+        assert_eq!(messages, vec!["fetching", "building", "compiling", "done"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_build_logs_ends_on_error() {
+        use futures::StreamExt;
+
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+        let _mock = server
+            .mock("GET", "/builds/missing/logs")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let items: Vec<_> = client
+            .stream_build_logs("missing", Duration::from_millis(1))
+            .collect()
+            .await;
+        assert_eq!(items.len(), 1);
+        assert!(matches!(items[0], Err(GarnixError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_completion_times_out() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+
+        let _pending = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(pending_response(1, "Pending").to_string())
+            .create_async()
+            .await;
+
+        let options = WaitOptions {
+            interval: Duration::from_millis(5),
+            timeout: Some(Duration::from_millis(20)),
+        };
+        let outcome = client
+            .wait_for_completion("abc1234", &options, |_| {})
+            .await
+            .unwrap();
+
+        assert!(outcome.is_timed_out());
+        assert_eq!(outcome.response().summary.pending, 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_completion_waits_for_evaluation() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+
+        let mut unevaluated = pending_response(0, "Pending");
+        unevaluated["builds"] = json!([]);
+        let _unevaluated = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(unevaluated.to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let _settled = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(pending_response(0, "Failed").to_string())
+            .create_async()
+            .await;
+
+        let options = WaitOptions {
+            interval: Duration::from_millis(1),
+            timeout: Some(Duration::from_secs(10)),
+        };
+        let outcome = client
+            .wait_for_completion("abc1234", &options, |_| {})
+            .await
+            .unwrap();
+
+        assert!(!outcome.is_timed_out());
+        assert_eq!(outcome.response().builds.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_logs_for_commit() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+
+        let mut status = pending_response(0, "Failed");
+        let mut other = create_test_build();
+        other.id = "test-build-2".to_string();
+        other.package = "other-package".to_string();
+        other.status = "Failed".to_string();
+        status["builds"].as_array_mut().unwrap().push(json!(other));
+
+        let _status = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(status.to_string())
+            .create_async()
+            .await;
+        let logs = server
+            .mock("GET", "/builds/test-build-1/logs")
+            .with_status(200)
+            .with_body(r#"{"finished": true, "logs": [{"timestamp": "t", "log_message": "boom"}]}"#)
+            .create_async()
+            .await;
+        let other_logs = server
+            .mock("GET", "/builds/test-build-2/logs")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let filter = BuildFilter::new().package("test-package").failed_only(true);
+        let result = client
+            .fetch_logs_for_commit("abc1234", &filter)
+            .await
+            .unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].build.id, "test-build-1");
+        assert_eq!(result[0].logs.logs[0].log_message, "boom");
+        logs.assert_async().await;
+        other_logs.assert_async().await;
+
+        let all = client
+            .fetch_logs_for_commit("abc1234", &BuildFilter::new().system("aarch64-darwin"))
+            .await
+            .unwrap();
+        assert!(all.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_logs_for_many_builds() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+
+        let mut status = pending_response(0, "Failed");
+        let mut mocks = Vec::new();
+        let builds: Vec<_> = (0..10)
+            .map(|i| {
+                let mut build = create_test_build();
+                build.id = format!("build-{}", i);
+                build.status = "Failed".to_string();
+                json!(build)
+            })
+            .collect();
+        status["builds"] = json!(builds);
+        mocks.push(
+            server
+                .mock("GET", "/builds/abc1234")
+                .with_status(200)
+                .with_body(status.to_string())
+                .create_async()
+                .await,
+        );
+        for i in 0..10 {
+            let mock = server.mock("GET", format!("/builds/build-{}/logs", i).as_str());
+            mocks.push(
+                if i == 3 {
+                    mock.with_status(404)
+                } else {
+                    mock.with_status(200).with_body(
+                        json!({"finished": true, "logs": [{"timestamp": "t", "log_message": format!("log {}", i)}]})
+                            .to_string(),
+                    )
+                }
+                .create_async()
+                .await,
+            );
+        }
+
+        let result = client
+            .fetch_logs_for_commit("abc1234", &BuildFilter::new())
+            .await
+            .unwrap();
+
+        let ids: Vec<_> = result.iter().map(|logs| logs.build.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            (0..10).map(|i| format!("build-{}", i)).collect::<Vec<_>>()
+        );
+        assert_eq!(result[2].logs.logs[0].log_message, "log 2");
+        // Missing logs do not fail the other builds
+        assert!(result[3].logs.logs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_diagnose_commit() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+
+        let mut status = pending_response(0, "Failed");
+        status["runs"] = json!([{
+            "id": "run-1",
+            "status": "Failed",
+            "eval_error": "error: undefined variable 'foo'"
+        }]);
+        let _status = server
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(status.to_string())
+            .create_async()
+            .await;
+        let _logs = server
+            .mock("GET", "/builds/test-build-1/logs")
+            .with_status(200)
+            .with_body(
+                json!({
+                    "finished": true,
+                    "logs": [{"timestamp": "t", "log_message": "test result: FAILED. 1 passed; 1 failed"}]
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let reports = client
+            .diagnose_commit("abc1234", &BuildFilter::new())
+            .await
+            .unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].run.as_deref(), Some("run run-1"));
+        assert_eq!(
+            reports[0].diagnosis.kind,
+            crate::analysis::FailureKind::EvaluationError
+        );
+        assert_eq!(reports[1].build_id.as_deref(), Some("test-build-1"));
+        assert_eq!(
+            reports[1].diagnosis.kind,
+            crate::analysis::FailureKind::TestFailure
+        );
+
+        let filtered = client
+            .diagnose_commit("abc1234", &BuildFilter::new().package("test-package"))
+            .await
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_compare_commits() {
+        let mut server = Server::new_async().await;
+        let client = GarnixClient::with_base_url(server.url()).with_token("test-token");
+
+        let _base = server
+            .mock("GET", "/builds/base")
+            .with_status(200)
+            .with_body(pending_response(0, "Success").to_string())
+            .create_async()
+            .await;
+        let _head = server
+            .mock("GET", "/builds/head")
+            .with_status(200)
+            .with_body(pending_response(0, "Failed").to_string())
+            .create_async()
+            .await;
+
+        let comparison = client.compare_commits("base", "head").await.unwrap();
+        assert_eq!(comparison.newly_failing.len(), 1);
+        assert_eq!(comparison.newly_failing[0].build_id, "test-build-1");
+        assert!(comparison.has_regressions());
+
+        let _missing = server
+            .mock("GET", "/builds/unknown")
+            .with_status(404)
+            .create_async()
+            .await;
+        let missing = client.compare_commits("base", "unknown").await;
+        assert!(matches!(missing, Err(GarnixError::NotFound(_))));
     }
 }
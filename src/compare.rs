@@ -0,0 +1,447 @@
+//! Comparison of the builds of two commits
+//!
+//! Builds are matched by package and system. Each pair is classified by how
+//! its status changed between the base and the head commit, which answers the
+//! question "did this change break anything that was green before?".
+
+use crate::format_duration;
+use crate::types::{Build, GarnixResponse, Run};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Minimum relative change for a duration change to be reported, in percent
+const DURATION_CHANGE_PERCENT: i64 = 50;
+/// Minimum absolute change for a duration change to be reported, in seconds
+const DURATION_CHANGE_SECS: i64 = 60;
+
+/// A build present in the base commit, the head commit, or both
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+pub struct BuildChange {
+    /// Package/derivation name
+    pub package: String,
+    /// Target system (e.g., "x86_64-linux")
+    pub system: Option<String>,
+    /// Status in the base commit, or `None` if the build was added
+    pub base_status: Option<String>,
+    /// Status in the head commit, or `None` if the build was removed
+    pub head_status: Option<String>,
+    /// Duration in the base commit in seconds, if finished
+    pub base_duration_secs: Option<i64>,
+    /// Duration in the head commit in seconds, if finished
+    pub head_duration_secs: Option<i64>,
+    /// ID of the build in the head commit, or in the base commit if removed
+    pub build_id: String,
+}
+
+impl BuildChange {
+    fn new(base: Option<&Build>, head: Option<&Build>) -> Self {
+        let build = head.or(base).expect("a change has at least one build");
+        let duration = |build: &Build| build.duration().map(|duration| duration.num_seconds());
+        Self {
+            package: build.package.clone(),
+            system: build.system.clone(),
+            base_status: base.map(|build| build.status.clone()),
+            head_status: head.map(|build| build.status.clone()),
+            base_duration_secs: base.and_then(duration),
+            head_duration_secs: head.and_then(duration),
+            build_id: build.id.clone(),
+        }
+    }
+
+    /// Short name identifying the build in summaries
+    pub fn title(&self) -> String {
+        format!(
+            "{} ({})",
+            self.package,
+            self.system.as_deref().unwrap_or("unknown")
+        )
+    }
+
+    /// Whether the duration changed by at least 50% and at least a minute
+    pub fn is_large_duration_change(&self) -> bool {
+        match (self.base_duration_secs, self.head_duration_secs) {
+            (Some(base), Some(head)) => {
+                let change = (head - base).abs();
+                change >= DURATION_CHANGE_SECS && change * 100 >= base * DURATION_CHANGE_PERCENT
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Per-build differences between the builds of two commits
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+pub struct Comparison {
+    /// The commit compared against
+    pub base_commit: String,
+    /// The commit being checked
+    pub head_commit: String,
+    /// Builds that did not fail in the base commit but fail in the head commit
+    pub newly_failing: Vec<BuildChange>,
+    /// Builds that failed in the base commit and succeed in the head commit
+    pub newly_fixed: Vec<BuildChange>,
+    /// Builds that fail in both commits
+    pub still_failing: Vec<BuildChange>,
+    /// Builds only present in the head commit
+    pub added: Vec<BuildChange>,
+    /// Builds only present in the base commit
+    pub removed: Vec<BuildChange>,
+    /// Builds that succeed in both commits but took much more or less time
+    pub duration_changes: Vec<BuildChange>,
+    /// Runs of the head commit that fail, matched by name with the runs of the
+    /// base commit that did not fail
+    pub newly_failing_runs: Vec<Run>,
+    /// Whether the base commit had builds but the settled head commit has none of them
+    pub all_removed: bool,
+}
+
+impl Comparison {
+    /// Whether the head commit broke builds, including added builds that fail,
+    /// failed runs and losing all builds of the base commit
+    pub fn has_regressions(&self) -> bool {
+        !self.newly_failing.is_empty()
+            || !self.newly_failing_runs.is_empty()
+            || self.all_removed
+            || self
+                .added
+                .iter()
+                .any(|change| change.head_status.as_deref() == Some("Failed"))
+    }
+}
+
+/// Compare the builds of a base commit with those of a head commit
+///
+/// When a commit has several builds of the same package and system, for
+/// example after a rebuild, the one started last is used. A head commit whose
+/// evaluation failed usually has no builds at all, so its failed runs and the
+/// loss of all base builds count as regressions too.
+pub fn compare(base: &GarnixResponse, head: &GarnixResponse) -> Comparison {
+    let base_builds = latest_builds(base);
+    let mut head_builds = latest_builds(head);
+    let base_failed_runs: Vec<String> = base.failed_runs().iter().map(|run| run.label()).collect();
+    let mut comparison = Comparison {
+        base_commit: base.summary.git_commit.clone(),
+        head_commit: head.summary.git_commit.clone(),
+        newly_failing_runs: head
+            .failed_runs()
+            .into_iter()
+            .filter(|run| !base_failed_runs.contains(&run.label()))
+            .cloned()
+            .collect(),
+        ..Comparison::default()
+    };
+    let base_count = base_builds.len();
+
+    for (key, base_build) in base_builds {
+        let Some(head_build) = head_builds.remove(&key) else {
+            comparison
+                .removed
+                .push(BuildChange::new(Some(base_build), None));
+            continue;
+        };
+        let change = BuildChange::new(Some(base_build), Some(head_build));
+        match (base_build.is_failed(), head_build.is_failed()) {
+            (false, true) => comparison.newly_failing.push(change),
+            (true, true) => comparison.still_failing.push(change),
+            (true, false) if head_build.is_successful() => comparison.newly_fixed.push(change),
+            _ if base_build.is_successful()
+                && head_build.is_successful()
+                && change.is_large_duration_change() =>
+            {
+                comparison.duration_changes.push(change)
+            }
+            _ => {}
+        }
+    }
+    comparison.added = head_builds
+        .into_values()
+        .map(|head_build| BuildChange::new(None, Some(head_build)))
+        .collect();
+    comparison.all_removed =
+        head.is_settled() && base_count > 0 && comparison.removed.len() == base_count;
+    comparison
+}
+
+/// Builds keyed by package and system, keeping the latest of duplicates
+fn latest_builds(response: &GarnixResponse) -> BTreeMap<(&str, Option<&str>), &Build> {
+    let mut builds: BTreeMap<(&str, Option<&str>), &Build> = BTreeMap::new();
+    for build in &response.builds {
+        let key = (build.package.as_str(), build.system.as_deref());
+        match builds.get(&key) {
+            Some(existing) if existing.start_time > build.start_time => {}
+            _ => {
+                builds.insert(key, build);
+            }
+        }
+    }
+    builds
+}
+
+/// Format a comparison as markdown
+pub fn format_comparison(comparison: &Comparison) -> String {
+    let mut output = format!(
+        "# Comparison of {} with {}\n\n{} newly failing, {} newly fixed, {} still failing, {} added, {} removed, {} duration changes\n",
+        short(&comparison.head_commit),
+        short(&comparison.base_commit),
+        comparison.newly_failing.len(),
+        comparison.newly_fixed.len(),
+        comparison.still_failing.len(),
+        comparison.added.len(),
+        comparison.removed.len(),
+        comparison.duration_changes.len()
+    );
+
+    let status = |status: &Option<String>| status.clone().unwrap_or_else(|| "-".to_string());
+    let sections = [
+        ("Newly failing", &comparison.newly_failing),
+        ("Newly fixed", &comparison.newly_fixed),
+        ("Still failing", &comparison.still_failing),
+        ("Added", &comparison.added),
+        ("Removed", &comparison.removed),
+    ];
+    for (heading, changes) in sections {
+        if changes.is_empty() {
+            continue;
+        }
+        output.push_str(&format!("\n## {}\n", heading));
+        for change in changes {
+            output.push_str(&format!(
+                "- **{}**: {} → {}\n",
+                change.title(),
+                status(&change.base_status),
+                status(&change.head_status)
+            ));
+        }
+    }
+
+    if !comparison.newly_failing_runs.is_empty() {
+        output.push_str("\n## Newly failing runs\n");
+        for run in &comparison.newly_failing_runs {
+            match run.error.as_deref().and_then(|error| error.lines().next()) {
+                Some(error) => output.push_str(&format!("- **{}**: {}\n", run.label(), error)),
+                None => output.push_str(&format!("- **{}**\n", run.label())),
+            }
+        }
+    }
+    if comparison.all_removed {
+        output
+            .push_str("\nNone of the builds of the base commit were built for the head commit.\n");
+    }
+
+    if !comparison.duration_changes.is_empty() {
+        output.push_str("\n## Duration changes\n");
+        for change in &comparison.duration_changes {
+            let (base, head) = (
+                change.base_duration_secs.unwrap_or_default(),
+                change.head_duration_secs.unwrap_or_default(),
+            );
+            let percent = match base {
+                0 => String::new(),
+                _ => format!(" ({:+}%)", (head - base) * 100 / base),
+            };
+            output.push_str(&format!(
+                "- **{}**: {} → {}{}\n",
+                change.title(),
+                format_duration(chrono::Duration::seconds(base)),
+                format_duration(chrono::Duration::seconds(head)),
+                percent
+            ));
+        }
+    }
+    output
+}
+
+/// First 8 characters of a commit SHA
+fn short(commit: &str) -> &str {
+    commit.get(..8).unwrap_or(commit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Summary;
+    use chrono::{Duration, TimeZone, Utc};
+
+    fn build(package: &str, system: &str, status: &str, seconds: i64) -> Build {
+        let start_time = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        Build {
+            id: format!("{}-{}", package, status),
+            repo_user: "testowner".to_string(),
+            repo_name: "testrepo".to_string(),
+            branch: "main".to_string(),
+            repo_is_public: true,
+            git_commit: "abc123".to_string(),
+            package: package.to_string(),
+            package_type: "package".to_string(),
+            system: Some(system.to_string()),
+            req_user: "testuser".to_string(),
+            status: status.to_string(),
+            start_time: Some(start_time),
+            end_time: Some(start_time + Duration::seconds(seconds)),
+            drv_path: None,
+            output_paths: None,
+            github_run_id: 1,
+            wants_incrementalism: false,
+            eval_host: "eval".to_string(),
+            uploaded_to_cache: false,
+        }
+    }
+
+    fn response(commit: &str, builds: Vec<Build>) -> GarnixResponse {
+        GarnixResponse {
+            summary: Summary {
+                repo_owner: "testowner".to_string(),
+                repo_name: "testrepo".to_string(),
+                repo_is_public: true,
+                git_commit: commit.to_string(),
+                branch: "main".to_string(),
+                req_user: "testuser".to_string(),
+                start_time: Some(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()),
+                succeeded: 0,
+                failed: 0,
+                pending: 0,
+                cancelled: 0,
+            },
+            builds,
+            runs: vec![],
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let base = response(
+            "base000000",
+            vec![
+                build("app", "x86_64-linux", "Success", 100),
+                build("lib", "x86_64-linux", "Failed", 100),
+                build("docs", "x86_64-linux", "Failed", 100),
+                build("tests", "x86_64-linux", "Success", 100),
+                build("old", "x86_64-linux", "Success", 100),
+                build("app", "aarch64-linux", "Success", 100),
+            ],
+        );
+        let head = response(
+            "head000000",
+            vec![
+                build("app", "x86_64-linux", "Failed", 100),
+                build("lib", "x86_64-linux", "Success", 100),
+                build("docs", "x86_64-linux", "Failed", 100),
+                build("tests", "x86_64-linux", "Success", 400),
+                build("new", "x86_64-linux", "Success", 100),
+                build("app", "aarch64-linux", "Success", 130),
+            ],
+        );
+
+        let comparison = compare(&base, &head);
+        let titles = |changes: &[BuildChange]| -> Vec<String> {
+            changes.iter().map(BuildChange::title).collect()
+        };
+        assert_eq!(comparison.base_commit, "base000000");
+        assert_eq!(titles(&comparison.newly_failing), ["app (x86_64-linux)"]);
+        assert_eq!(titles(&comparison.newly_fixed), ["lib (x86_64-linux)"]);
+        assert_eq!(titles(&comparison.still_failing), ["docs (x86_64-linux)"]);
+        assert_eq!(titles(&comparison.added), ["new (x86_64-linux)"]);
+        assert_eq!(titles(&comparison.removed), ["old (x86_64-linux)"]);
+        // +30s is below the threshold, +300s is reported
+        assert_eq!(
+            titles(&comparison.duration_changes),
+            ["tests (x86_64-linux)"]
+        );
+        assert!(comparison.has_regressions());
+
+        let removed = &comparison.removed[0];
+        assert_eq!(removed.base_status.as_deref(), Some("Success"));
+        assert_eq!(removed.head_status, None);
+
+        let markdown = format_comparison(&comparison);
+        assert!(markdown.starts_with("# Comparison of head0000 with base0000"));
+        assert!(markdown.contains("1 newly failing, 1 newly fixed, 1 still failing"));
+        assert!(markdown.contains("## Newly failing\n- **app (x86_64-linux)**: Success → Failed"));
+        assert!(markdown.contains("## Added\n- **new (x86_64-linux)**: - → Success"));
+        assert!(markdown.contains("- **tests (x86_64-linux)**: 1m40s → 6m40s (+300%)"));
+    }
+
+    #[test]
+    fn test_failed_head_run() {
+        let base = response(
+            "base",
+            vec![
+                build("app", "x86_64-linux", "Success", 100),
+                build("docs", "x86_64-linux", "Success", 100),
+            ],
+        );
+        let mut head = response("head", vec![]);
+        head.runs = vec![Run {
+            name: Some("flake check".to_string()),
+            status: Some("Failed".to_string()),
+            error: Some("error: undefined variable 'pkgs'\n at flake.nix:3:5".to_string()),
+            ..Run::default()
+        }];
+
+        let comparison = compare(&base, &head);
+        assert_eq!(comparison.removed.len(), 2);
+        assert!(comparison.all_removed);
+        assert_eq!(comparison.newly_failing_runs.len(), 1);
+        assert!(comparison.has_regressions());
+
+        let markdown = format_comparison(&comparison);
+        assert!(markdown.contains(
+            "## Newly failing runs\n- **flake check**: error: undefined variable 'pkgs'"
+        ));
+        assert!(markdown.contains("None of the builds of the base commit were built"));
+
+        // A run that already failed in the base commit is not a regression
+        let mut failing_base = base.clone();
+        failing_base.runs = head.runs.clone();
+        let comparison = compare(&failing_base, &head);
+        assert!(comparison.newly_failing_runs.is_empty());
+        assert!(comparison.has_regressions());
+
+        // Neither is a head commit that has not been built yet
+        let mut pending = response("head", vec![]);
+        pending.runs = vec![Run {
+            status: Some("Pending".to_string()),
+            ..Run::default()
+        }];
+        assert!(!compare(&base, &pending).has_regressions());
+    }
+
+    #[test]
+    fn test_unevaluated_head() {
+        let base = response("base", vec![build("app", "x86_64-linux", "Success", 100)]);
+        let head = response("head", vec![]);
+
+        let comparison = compare(&base, &head);
+        assert_eq!(comparison.removed.len(), 1);
+        assert!(!comparison.all_removed);
+        assert!(!comparison.has_regressions());
+    }
+
+    #[test]
+    fn test_latest_duplicate_wins() {
+        let mut rebuilt = build("app", "x86_64-linux", "Success", 100);
+        rebuilt.start_time = rebuilt.start_time.map(|start| start + Duration::hours(1));
+        let base = response("base", vec![build("app", "x86_64-linux", "Success", 100)]);
+        let head = response(
+            "head",
+            vec![
+                rebuilt,
+                build("app", "x86_64-linux", "Failed", 100),
+                build("extra", "x86_64-linux", "Pending", 0),
+            ],
+        );
+
+        let comparison = compare(&base, &head);
+        assert!(comparison.newly_failing.is_empty());
+        assert_eq!(comparison.added.len(), 1);
+        assert!(!comparison.has_regressions());
+        assert_eq!(
+            compare(&head, &head),
+            Comparison {
+                base_commit: "head".to_string(),
+                head_commit: "head".to_string(),
+                ..Comparison::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,372 @@
+//! Configuration file with named profiles
+//!
+//! The configuration is read from `--config`, `$GARNIX_CONFIG`, or
+//! `$XDG_CONFIG_HOME/garnix-insights/config.toml` (falling back to
+//! `~/.config/garnix-insights/config.toml`):
+//!
+//! ```toml
+//! default_profile = "work"
+//!
+//! [profiles.work]
+//! base_url = "https://garnix.io/api"
+//! format = "json"
+//! timeout_secs = 30
+//! repo = "shift/garnix-insights"
+//! token = { command = "pass show garnix" }
+//! mcp_token_policy = "disallowed"
+//! mcp_tools = ["get_build_status", "check_commit_ready"]
+//! mcp_secret = { file = "/run/secrets/garnix-mcp" }
+//!
+//! [profiles.work.retry]
+//! max_attempts = 5
+//! base_delay_ms = 250
+//! ```
+
+use crate::auth::{
+    CommandTokenProvider, EnvTokenProvider, FileTokenProvider, StaticTokenProvider, TokenProvider,
+};
+use crate::client::{GarnixClient, DEFAULT_BASE_URL};
+use crate::error::{GarnixError, GarnixResult};
+use crate::retry::RetryPolicy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+/// Name of the profile used when none is selected or configured as default
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Top-level configuration file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Profile used when `--profile` is not given
+    pub default_profile: Option<String>,
+    /// Named profiles
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// A named set of connection and output settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Where to read the JWT token from
+    pub token: Option<TokenSource>,
+    /// Base URL of the Garnix API
+    pub base_url: Option<String>,
+    /// Default output format for the CLI
+    pub format: Option<OutputFormat>,
+    /// HTTP request timeout in seconds
+    pub timeout_secs: Option<u64>,
+    /// Retry policy overrides
+    pub retry: Option<RetryConfig>,
+    /// Default repository (`owner/name`) to cross-check results against
+    pub repo: Option<String>,
+    /// Whether MCP tool calls may pass their own `token` argument
+    pub mcp_token_policy: Option<TokenPolicy>,
+    /// MCP tools to offer (all but the opt-in tools when unset)
+    pub mcp_tools: Option<Vec<String>>,
+    /// Where to read the bearer secret required on the server's `/mcp` endpoint from
+    pub mcp_secret: Option<TokenSource>,
+}
+
+/// Available output formats
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human-readable output with colors and emojis
+    Human,
+    /// JSON output
+    Json,
+    /// Plain text output
+    Plain,
+}
+
+/// Whether MCP tool calls may, must or must not carry their own `token` argument
+///
+/// Keeping the token on the server avoids handing the Garnix JWT to agents,
+/// where it would end up in chat transcripts.
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenPolicy {
+    /// Every tool call must pass a `token` argument; resources and prompts,
+    /// which would use the server-side token, are refused
+    Required,
+    /// A `token` argument overrides the server-side token
+    #[default]
+    Optional,
+    /// Only the server-side token is used; a `token` argument is rejected
+    Disallowed,
+}
+
+/// Source of the JWT token for a profile
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenSource {
+    /// Read the token from an environment variable
+    Env(String),
+    /// Read the token from a file
+    File(PathBuf),
+    /// Use the standard output of a shell command
+    Command(String),
+    /// Use a literal token value
+    Value(String),
+}
+
+/// Retry policy overrides; unset fields keep the [`RetryPolicy`] defaults
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: Option<u32>,
+    /// Delay before the first retry in milliseconds
+    pub base_delay_ms: Option<u64>,
+    /// Upper bound for the backoff delay in milliseconds
+    pub max_delay_ms: Option<u64>,
+    /// Fraction (0.0 - 1.0) of the backoff delay that is randomised
+    pub jitter: Option<f64>,
+    /// Overall time budget in seconds (0 disables the deadline)
+    pub deadline_secs: Option<u64>,
+}
+
+impl Config {
+    /// Parse a configuration from TOML
+    pub fn from_toml(content: &str) -> GarnixResult<Self> {
+        toml::from_str(content).map_err(|e| GarnixError::config(format!("Invalid config: {}", e)))
+    }
+
+    /// Load the configuration from an explicit path or the default location
+    ///
+    /// A missing file at the default location yields an empty configuration,
+    /// while a missing explicit path is an error.
+    pub fn load(path: Option<&Path>) -> GarnixResult<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                debug!("Loading configuration from {}", path.display());
+                Self::from_toml(&content)
+                    .map_err(|e| GarnixError::config(format!("{}: {}", path.display(), e)))
+            }
+            Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(GarnixError::config(format!(
+                "Failed to read {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+
+    /// Default configuration path following the XDG base directory specification
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("garnix-insights").join("config.toml"))
+    }
+
+    /// Select a profile by name, falling back to `default_profile` and then `default`
+    ///
+    /// # Errors
+    /// Returns a configuration error if an explicitly selected profile does not exist
+    pub fn profile(&self, name: Option<&str>) -> GarnixResult<Profile> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self.profiles.get(name).cloned().ok_or_else(|| {
+                GarnixError::config(format!("Profile '{}' not found in configuration", name))
+            }),
+            None => Ok(self
+                .profiles
+                .get(DEFAULT_PROFILE)
+                .cloned()
+                .unwrap_or_default()),
+        }
+    }
+}
+
+impl Profile {
+    /// Build a Garnix client honouring the base URL, timeout, retry and token settings
+    pub fn client(&self) -> GarnixResult<GarnixClient> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout_secs {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+        let http = builder
+            .build()
+            .map_err(|e| GarnixError::config(format!("Failed to build HTTP client: {}", e)))?;
+
+        let base_url = self.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL);
+        let retry_policy = self
+            .retry
+            .as_ref()
+            .map(RetryConfig::policy)
+            .unwrap_or_default();
+
+        let client =
+            GarnixClient::with_client_and_url(http, base_url).with_retry_policy(retry_policy);
+        Ok(match self.token_provider() {
+            Some(provider) => client.with_token_provider(provider),
+            None => client,
+        })
+    }
+
+    /// Token provider for the configured source, if any
+    pub fn token_provider(&self) -> Option<Arc<dyn TokenProvider>> {
+        self.token.as_ref().map(TokenSource::provider)
+    }
+}
+
+impl TokenSource {
+    /// Create a provider reading the token from this source
+    pub fn provider(&self) -> Arc<dyn TokenProvider> {
+        match self {
+            TokenSource::Env(name) => Arc::new(EnvTokenProvider::new(name)),
+            TokenSource::File(path) => Arc::new(FileTokenProvider::new(path)),
+            TokenSource::Command(command) => Arc::new(CommandTokenProvider::new(command)),
+            TokenSource::Value(value) => Arc::new(StaticTokenProvider::new(value)),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Apply these overrides on top of the default retry policy
+    pub fn policy(&self) -> RetryPolicy {
+        let mut policy = RetryPolicy::default();
+        if let Some(max_attempts) = self.max_attempts {
+            policy = policy.max_attempts(max_attempts);
+        }
+        if let Some(base_delay) = self.base_delay_ms {
+            policy = policy.base_delay(Duration::from_millis(base_delay));
+        }
+        if let Some(max_delay) = self.max_delay_ms {
+            policy = policy.max_delay(Duration::from_millis(max_delay));
+        }
+        if let Some(jitter) = self.jitter {
+            policy = policy.jitter(jitter);
+        }
+        if let Some(deadline) = self.deadline_secs {
+            policy = policy.deadline((deadline > 0).then(|| Duration::from_secs(deadline)));
+        }
+        policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"
+        default_profile = "work"
+
+        [profiles.work]
+        base_url = "https://garnix.example/api"
+        format = "json"
+        timeout_secs = 30
+        repo = "shift/garnix-insights"
+        token = { value = " secret-token \n" }
+        mcp_token_policy = "disallowed"
+        mcp_tools = ["get_build_status"]
+        mcp_secret = { value = "mcp-secret" }
+
+        [profiles.work.retry]
+        max_attempts = 5
+        base_delay_ms = 250
+        deadline_secs = 0
+
+        [profiles.default]
+        token = { env = "GARNIX_TEST_UNSET_VARIABLE" }
+    "#;
+
+    #[tokio::test]
+    async fn test_parse_profiles() {
+        let config = Config::from_toml(EXAMPLE).unwrap();
+        assert_eq!(config.default_profile.as_deref(), Some("work"));
+        assert_eq!(config.profiles.len(), 2);
+
+        let work = config.profile(None).unwrap();
+        assert_eq!(work.base_url.as_deref(), Some("https://garnix.example/api"));
+        assert_eq!(work.format, Some(OutputFormat::Json));
+        assert_eq!(work.repo.as_deref(), Some("shift/garnix-insights"));
+        assert_eq!(work.mcp_token_policy, Some(TokenPolicy::Disallowed));
+        assert_eq!(work.mcp_tools, Some(vec!["get_build_status".to_string()]));
+        assert_eq!(
+            work.mcp_secret,
+            Some(TokenSource::Value("mcp-secret".to_string()))
+        );
+        let provider = work.token_provider().unwrap();
+        assert_eq!(provider.token().await.unwrap(), "secret-token");
+
+        let policy = work.retry.as_ref().unwrap().policy();
+        assert_eq!(policy.max_attempts, 5);
+        assert_eq!(policy.base_delay, Duration::from_millis(250));
+        assert_eq!(policy.deadline, None);
+
+        let client = work.client().unwrap();
+        assert_eq!(client.base_url(), "https://garnix.example/api");
+        assert_eq!(client.retry_policy().max_attempts, 5);
+        assert!(client.token_provider().is_some());
+    }
+
+    #[test]
+    fn test_profile_selection() {
+        let config = Config::from_toml(EXAMPLE).unwrap();
+        assert!(config.profile(Some("default")).unwrap().token.is_some());
+        assert!(matches!(
+            config.profile(Some("missing")),
+            Err(GarnixError::ConfigError(_))
+        ));
+
+        let empty = Config::default();
+        let profile = empty.profile(None).unwrap();
+        assert!(profile.token.is_none());
+        let client = profile.client().unwrap();
+        assert_eq!(client.base_url(), DEFAULT_BASE_URL);
+        assert!(client.token_provider().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_token_sources() {
+        let unset = TokenSource::Env("GARNIX_TEST_UNSET_VARIABLE".to_string()).provider();
+        assert!(matches!(
+            unset.token().await,
+            Err(GarnixError::ConfigError(_))
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        std::fs::write(&path, "file-token\n").unwrap();
+        let file = TokenSource::File(path).provider();
+        assert_eq!(file.token().await.unwrap(), "file-token");
+
+        let command = TokenSource::Command("echo command-token".to_string()).provider();
+        assert_eq!(command.token().await.unwrap(), "command-token");
+
+        let empty = TokenSource::Value("  ".to_string()).provider();
+        assert!(empty.token().await.is_err());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(Config::from_toml("[profiles.work]\nunknown = 1").is_err());
+        assert!(Config::load(Some(Path::new("/nonexistent/config.toml"))).is_err());
+    }
+
+    #[test]
+    fn test_load_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, EXAMPLE).unwrap();
+
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.profiles.len(), 2);
+    }
+}
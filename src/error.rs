@@ -1,6 +1,10 @@
 //! Error types for the Garnix Fetcher library
 
 use std::fmt;
+use std::time::Duration;
+
+/// Maximum number of characters of a response body kept in [`HttpErrorDetails`]
+pub const MAX_ERROR_BODY_LEN: usize = 1024;
 
 /// Main error type for Garnix operations
 #[derive(Debug, Clone)]
@@ -15,6 +19,8 @@ pub enum GarnixError {
     RateLimit(String),
     /// General API errors from Garnix
     ApiError(String),
+    /// Unsuccessful HTTP response from the Garnix API
+    HttpError(HttpErrorDetails),
     /// JSON parsing errors
     ParseError(String),
     /// Configuration errors
@@ -33,6 +39,7 @@ impl fmt::Display for GarnixError {
             GarnixError::NotFound(msg) => write!(f, "Not found: {}", msg),
             GarnixError::RateLimit(msg) => write!(f, "Rate limit exceeded: {}", msg),
             GarnixError::ApiError(msg) => write!(f, "API error: {}", msg),
+            GarnixError::HttpError(details) => write!(f, "API error: {}", details),
             GarnixError::ParseError(msg) => write!(f, "Parse error: {}", msg),
             GarnixError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
             GarnixError::IoError(msg) => write!(f, "I/O error: {}", msg),
@@ -43,6 +50,72 @@ impl fmt::Display for GarnixError {
 
 impl std::error::Error for GarnixError {}
 
+/// Metadata about an unsuccessful HTTP response from the Garnix API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpErrorDetails {
+    /// Numeric HTTP status code
+    pub status: u16,
+    /// URL of the upstream request
+    pub url: String,
+    /// Delay requested by the `Retry-After` header
+    pub retry_after: Option<Duration>,
+    /// Upstream request id (`X-Request-Id` header)
+    pub request_id: Option<String>,
+    /// Response body, truncated to [`MAX_ERROR_BODY_LEN`] characters
+    pub body: String,
+}
+
+impl HttpErrorDetails {
+    /// Create new details for a response, truncating the body if needed
+    pub fn new(status: u16, url: impl Into<String>, body: impl AsRef<str>) -> Self {
+        let body = body.as_ref();
+        let body = match body.char_indices().nth(MAX_ERROR_BODY_LEN) {
+            Some((index, _)) => format!("{}…", &body[..index]),
+            None => body.to_string(),
+        };
+
+        Self {
+            status,
+            url: url.into(),
+            retry_after: None,
+            request_id: None,
+            body,
+        }
+    }
+
+    /// Set the delay requested by the `Retry-After` header
+    pub fn retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Set the upstream request id
+    pub fn request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    /// Check if the status is a server error (5xx)
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.status)
+    }
+
+    /// Check if the status signals rate limiting (429)
+    pub fn is_rate_limited(&self) -> bool {
+        self.status == 429
+    }
+}
+
+impl fmt::Display for HttpErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP {} from {}", self.status, self.url)?;
+        if !self.body.is_empty() {
+            write!(f, ": {}", self.body)?;
+        }
+        Ok(())
+    }
+}
+
 impl From<reqwest::Error> for GarnixError {
     fn from(error: reqwest::Error) -> Self {
         GarnixError::NetworkError(error.to_string())
@@ -93,6 +166,11 @@ impl GarnixError {
         GarnixError::ApiError(message.into())
     }
 
+    /// Create a new HTTP error from response details
+    pub fn http(details: HttpErrorDetails) -> Self {
+        GarnixError::HttpError(details)
+    }
+
     /// Create a new parse error
     pub fn parse<T: Into<String>>(message: T) -> Self {
         GarnixError::ParseError(message.into())
@@ -122,6 +200,9 @@ impl GarnixError {
                 // Some API errors might be retryable (5xx status codes)
                 msg.contains("HTTP 5")
             }
+            GarnixError::HttpError(details) => {
+                details.is_server_error() || details.is_rate_limited() || details.status == 408
+            }
             _ => false,
         }
     }
@@ -137,6 +218,11 @@ impl GarnixError {
                 // 4xx client errors shouldn't be retried
                 msg.contains("HTTP 4")
             }
+            GarnixError::HttpError(details) => {
+                (400..500).contains(&details.status)
+                    && !details.is_rate_limited()
+                    && details.status != 408
+            }
             _ => false,
         }
     }
@@ -149,12 +235,32 @@ impl GarnixError {
             GarnixError::NotFound(_) => "not_found",
             GarnixError::RateLimit(_) => "rate_limit",
             GarnixError::ApiError(_) => "api",
+            GarnixError::HttpError(details) if details.is_rate_limited() => "rate_limit",
+            GarnixError::HttpError(_) => "api",
             GarnixError::ParseError(_) => "parse",
             GarnixError::ConfigError(_) => "config",
             GarnixError::IoError(_) => "io",
             GarnixError::ValidationError(_) => "validation",
         }
     }
+
+    /// Get the HTTP response details, if this error came from an HTTP response
+    pub fn http_details(&self) -> Option<&HttpErrorDetails> {
+        match self {
+            GarnixError::HttpError(details) => Some(details),
+            _ => None,
+        }
+    }
+
+    /// Get the HTTP status code, if this error came from an HTTP response
+    pub fn status(&self) -> Option<u16> {
+        self.http_details().map(|details| details.status)
+    }
+
+    /// Get the delay requested by the API before retrying, if any
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.http_details().and_then(|details| details.retry_after)
+    }
 }
 
 /// Result type alias for Garnix operations
@@ -252,4 +358,48 @@ mod tests {
         let error = GarnixError::NetworkError("Test error".to_string());
         let _: &dyn std::error::Error = &error;
     }
+
+    #[test]
+    fn test_http_error_categorization() {
+        let server_error = GarnixError::http(HttpErrorDetails::new(502, "https://x/y", "bad"));
+        assert!(server_error.is_retryable());
+        assert!(!server_error.is_client_error());
+        assert_eq!(server_error.category(), "api");
+        assert_eq!(server_error.status(), Some(502));
+        assert_eq!(
+            server_error.to_string(),
+            "API error: HTTP 502 from https://x/y: bad"
+        );
+
+        let rate_limited = GarnixError::http(
+            HttpErrorDetails::new(429, "https://x/y", "")
+                .retry_after(Some(Duration::from_secs(5)))
+                .request_id(Some("req-1".to_string())),
+        );
+        assert!(rate_limited.is_retryable());
+        assert!(!rate_limited.is_client_error());
+        assert_eq!(rate_limited.category(), "rate_limit");
+        assert_eq!(rate_limited.retry_after(), Some(Duration::from_secs(5)));
+        assert_eq!(
+            rate_limited.http_details().unwrap().request_id.as_deref(),
+            Some("req-1")
+        );
+
+        let bad_request = GarnixError::http(HttpErrorDetails::new(400, "https://x/y", ""));
+        assert!(!bad_request.is_retryable());
+        assert!(bad_request.is_client_error());
+
+        assert_eq!(GarnixError::network("down").status(), None);
+    }
+
+    #[test]
+    fn test_http_error_body_truncation() {
+        let body = "é".repeat(MAX_ERROR_BODY_LEN + 10);
+        let details = HttpErrorDetails::new(500, "https://x/y", &body);
+        assert_eq!(details.body.chars().count(), MAX_ERROR_BODY_LEN + 1);
+        assert!(details.body.ends_with('…'));
+
+        let details = HttpErrorDetails::new(500, "https://x/y", "short");
+        assert_eq!(details.body, "short");
+    }
 }
@@ -0,0 +1,411 @@
+//! Extraction of the interesting part of long build logs
+//!
+//! Instead of dumping a 50k-line log, an excerpt shows the first (or last)
+//! region of error-looking lines with some context around it. Runs of similar
+//! lines (progress counters, repeated warnings) are collapsed.
+
+use crate::analysis;
+use crate::types::LogResponse;
+use serde::Serialize;
+
+/// Which error region of the log to show
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Region {
+    /// The first error region, usually the root cause
+    #[default]
+    First,
+    /// The last error region, usually the final summary
+    Last,
+}
+
+/// Options controlling how an excerpt is extracted and rendered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcerptOptions {
+    /// Lines of context shown before the error region
+    pub context_before: usize,
+    /// Lines of context shown after the error region
+    pub context_after: usize,
+    /// Which error region to show
+    pub region: Region,
+    /// Collapse runs of similar lines
+    pub collapse_repeats: bool,
+    /// Prefix lines with their number in the log
+    pub line_numbers: bool,
+}
+
+impl Default for ExcerptOptions {
+    fn default() -> Self {
+        Self {
+            context_before: 10,
+            context_after: 10,
+            region: Region::First,
+            collapse_repeats: true,
+            line_numbers: true,
+        }
+    }
+}
+
+impl ExcerptOptions {
+    /// Create the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the context shown both before and after the error region
+    pub fn context(mut self, lines: usize) -> Self {
+        self.context_before = lines;
+        self.context_after = lines;
+        self
+    }
+
+    /// Set the context shown before the error region
+    pub fn context_before(mut self, lines: usize) -> Self {
+        self.context_before = lines;
+        self
+    }
+
+    /// Set the context shown after the error region
+    pub fn context_after(mut self, lines: usize) -> Self {
+        self.context_after = lines;
+        self
+    }
+
+    /// Choose which error region to show
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = region;
+        self
+    }
+
+    /// Enable or disable collapsing of similar lines
+    pub fn collapse_repeats(mut self, collapse: bool) -> Self {
+        self.collapse_repeats = collapse;
+        self
+    }
+
+    /// Enable or disable line numbers when rendering
+    pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+}
+
+/// A line of an excerpt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExcerptLine {
+    /// A line of the log
+    Line {
+        /// 1-based line number in the log
+        number: usize,
+        /// Content of the line
+        text: String,
+        /// Whether the line looks like an error
+        error: bool,
+    },
+    /// Similar lines that were left out
+    Collapsed {
+        /// Number of lines left out
+        count: usize,
+    },
+}
+
+/// Excerpt of a log around an error region
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Excerpt {
+    /// Number of lines in the whole log
+    pub total_lines: usize,
+    /// 1-based number of the first error line shown, if any error was found
+    pub error_line: Option<usize>,
+    /// The lines of the excerpt
+    pub lines: Vec<ExcerptLine>,
+}
+
+impl Excerpt {
+    /// Render the excerpt as text
+    pub fn render(&self, options: &ExcerptOptions) -> String {
+        let width = self.total_lines.max(1).to_string().len();
+        let first_shown = self.lines.iter().find_map(|line| match line {
+            ExcerptLine::Line { number, .. } => Some(*number),
+            ExcerptLine::Collapsed { .. } => None,
+        });
+
+        let mut output = Vec::new();
+        if self.error_line.is_none() && self.total_lines > 0 {
+            output.push("(no error found, showing the end of the log)".to_string());
+        }
+        if let Some(first) = first_shown.filter(|first| *first > 1) {
+            output.push(format!("... {} earlier lines", first - 1));
+        }
+        for line in &self.lines {
+            output.push(match line {
+                ExcerptLine::Line {
+                    number,
+                    text,
+                    error,
+                } if options.line_numbers => format!(
+                    "{}{:>width$} | {}",
+                    if *error { ">" } else { " " },
+                    number,
+                    text
+                ),
+                ExcerptLine::Line { text, .. } => text.clone(),
+                ExcerptLine::Collapsed { count } => {
+                    format!("... {} similar lines", count)
+                }
+            });
+        }
+        let last_shown = self.lines.iter().rev().find_map(|line| match line {
+            ExcerptLine::Line { number, .. } => Some(*number),
+            ExcerptLine::Collapsed { .. } => None,
+        });
+        if let Some(last) = last_shown.filter(|last| *last < self.total_lines) {
+            output.push(format!("... {} later lines", self.total_lines - last));
+        }
+        output.join("\n")
+    }
+}
+
+/// Check if a log line looks like an error
+///
+/// Lines matching a failure signature of [`analysis::signature`] count as
+/// errors, as do lines carrying a generic error marker.
+///
+/// # Example
+/// ```rust
+/// # use garnix_insights::excerpt::is_error_line;
+/// assert!(is_error_line("error[E0425]: cannot find value `x` in this scope"));
+/// assert!(is_error_line("test result: FAILED. 1 passed; 1 failed"));
+/// assert!(is_error_line("curl: (6) Could not resolve host: example.org"));
+/// assert!(!is_error_line("   Compiling foo v0.1.0"));
+/// ```
+pub fn is_error_line(line: &str) -> bool {
+    if analysis::signature(line).is_some() {
+        return true;
+    }
+    let lowercase = line.trim_start().to_lowercase();
+    lowercase.starts_with("error")
+        || lowercase.contains("panicked at")
+        || (lowercase.contains("builder for") && lowercase.contains("failed"))
+        || line.contains("FAILED")
+}
+
+/// Extract an excerpt from the logs of a build
+pub fn excerpt(logs: &LogResponse, options: &ExcerptOptions) -> Excerpt {
+    let lines: Vec<&str> = logs
+        .logs
+        .iter()
+        .flat_map(|entry| entry.log_message.lines())
+        .collect();
+    extract(&lines, options)
+}
+
+/// Extract an excerpt from raw log lines
+pub fn extract<S: AsRef<str>>(lines: &[S], options: &ExcerptOptions) -> Excerpt {
+    let errors: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| is_error_line(line.as_ref()))
+        .map(|(index, _)| index)
+        .collect();
+
+    let (start, end, error_line) = match error_region(&errors, options) {
+        Some((first, last)) => (
+            first.saturating_sub(options.context_before),
+            lines.len().min(last + options.context_after + 1),
+            Some(first + 1),
+        ),
+        None => {
+            let shown = options.context_before + options.context_after + 1;
+            (lines.len().saturating_sub(shown), lines.len(), None)
+        }
+    };
+
+    let mut excerpt_lines: Vec<ExcerptLine> = Vec::new();
+    let mut index = start;
+    while index < end {
+        let text = lines[index].as_ref();
+        let error = errors.binary_search(&index).is_ok();
+        excerpt_lines.push(ExcerptLine::Line {
+            number: index + 1,
+            text: text.to_string(),
+            error,
+        });
+        index += 1;
+
+        if options.collapse_repeats && !error {
+            let shape = shape_of(text);
+            let repeats = lines[index..end]
+                .iter()
+                .take_while(|line| {
+                    let line = line.as_ref();
+                    !is_error_line(line) && shape_of(line) == shape
+                })
+                .count();
+            // Only collapse when it saves lines
+            if repeats > 1 {
+                excerpt_lines.push(ExcerptLine::Collapsed { count: repeats });
+                index += repeats;
+            }
+        }
+    }
+
+    Excerpt {
+        total_lines: lines.len(),
+        error_line,
+        lines: excerpt_lines,
+    }
+}
+
+/// Find the first or last run of error lines, merging errors within the context
+fn error_region(errors: &[usize], options: &ExcerptOptions) -> Option<(usize, usize)> {
+    match options.region {
+        Region::First => {
+            let first = *errors.first()?;
+            let mut last = first;
+            for &index in &errors[1..] {
+                if index > last + options.context_after {
+                    break;
+                }
+                last = index;
+            }
+            Some((first, last))
+        }
+        Region::Last => {
+            let last = *errors.last()?;
+            let mut first = last;
+            for &index in errors[..errors.len() - 1].iter().rev() {
+                if index + options.context_before < first {
+                    break;
+                }
+                first = index;
+            }
+            Some((first, last))
+        }
+    }
+}
+
+/// Line with numbers masked, so that progress counters compare equal
+fn shape_of(line: &str) -> String {
+    let mut shape = String::with_capacity(line.len());
+    for c in line.trim().chars() {
+        if c.is_ascii_digit() {
+            if !shape.ends_with('#') {
+                shape.push('#');
+            }
+        } else {
+            shape.push(c);
+        }
+    }
+    shape
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(excerpt: &Excerpt) -> Vec<usize> {
+        excerpt
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                ExcerptLine::Line { number, .. } => Some(*number),
+                ExcerptLine::Collapsed { .. } => None,
+            })
+            .collect()
+    }
+
+    fn build_log() -> Vec<String> {
+        let mut lines: Vec<String> = (0..100).map(|i| format!("step {}", i)).collect();
+        lines[20] = "   Compiling foo v0.1.0".to_string();
+        lines[30] = "error[E0425]: cannot find value `x` in this scope".to_string();
+        lines[32] = "error: could not compile `foo`".to_string();
+        lines[90] =
+            "error: builder for '/nix/store/abc-foo.drv' failed with exit code 101".to_string();
+        lines
+    }
+
+    #[test]
+    fn test_first_region() {
+        let lines = build_log();
+        let options = ExcerptOptions::new().context(2).collapse_repeats(false);
+        let excerpt = extract(&lines, &options);
+
+        assert_eq!(excerpt.total_lines, 100);
+        assert_eq!(excerpt.error_line, Some(31));
+        assert_eq!(numbers(&excerpt), (29..=35).collect::<Vec<_>>());
+
+        let rendered = excerpt.render(&options);
+        assert!(rendered.starts_with("... 28 earlier lines"));
+        assert!(rendered.contains("\n> 31 | error[E0425]"));
+        assert!(rendered.contains("\n  30 | step 29"));
+        assert!(rendered.ends_with("... 65 later lines"));
+    }
+
+    #[test]
+    fn test_last_region() {
+        let lines = build_log();
+        let options = ExcerptOptions::new()
+            .context(2)
+            .region(Region::Last)
+            .collapse_repeats(false)
+            .line_numbers(false);
+        let excerpt = extract(&lines, &options);
+
+        assert_eq!(excerpt.error_line, Some(91));
+        assert_eq!(numbers(&excerpt), (89..=93).collect::<Vec<_>>());
+        assert!(excerpt
+            .render(&options)
+            .contains("\nerror: builder for '/nix/store/abc-foo.drv'"));
+    }
+
+    #[test]
+    fn test_collapse_repeats() {
+        let lines = build_log();
+        let options = ExcerptOptions::new().context(10);
+        let excerpt = extract(&lines, &options);
+
+        // "step 21" .. "step 29" share a shape and collapse after the first line
+        assert_eq!(
+            &excerpt.lines[..3],
+            &[
+                ExcerptLine::Line {
+                    number: 21,
+                    text: "   Compiling foo v0.1.0".to_string(),
+                    error: false
+                },
+                ExcerptLine::Line {
+                    number: 22,
+                    text: "step 21".to_string(),
+                    error: false
+                },
+                ExcerptLine::Collapsed { count: 8 },
+            ]
+        );
+        assert!(excerpt.render(&options).contains("... 8 similar lines"));
+
+        let json = serde_json::to_value(&excerpt).unwrap();
+        assert_eq!(json["lines"][2]["type"], "collapsed");
+    }
+
+    #[test]
+    fn test_no_errors_shows_tail() {
+        let lines: Vec<String> = (0..50).map(|i| format!("line {}", i)).collect();
+        let options = ExcerptOptions::new().context(1).collapse_repeats(false);
+        let excerpt = extract(&lines, &options);
+
+        assert_eq!(excerpt.error_line, None);
+        assert_eq!(numbers(&excerpt), vec![48, 49, 50]);
+        assert!(excerpt.render(&options).starts_with("(no error found"));
+
+        let empty = super::excerpt(
+            &LogResponse {
+                finished: true,
+                logs: vec![],
+            },
+            &ExcerptOptions::new(),
+        );
+        assert!(empty.lines.is_empty());
+        assert_eq!(empty.render(&ExcerptOptions::new()), "");
+    }
+}
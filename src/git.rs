@@ -0,0 +1,297 @@
+//! Helpers for resolving commits and repositories from a local git checkout
+
+use crate::error::{GarnixError, GarnixResult};
+use std::fmt;
+use std::path::Path;
+use tokio::process::Command;
+use tracing::debug;
+
+/// Repository owner and name, as reported by Garnix in `Summary`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoSlug {
+    /// Repository owner/organization
+    pub owner: String,
+    /// Repository name
+    pub name: String,
+}
+
+impl RepoSlug {
+    /// Check if this slug refers to the given owner and name (case-insensitive)
+    pub fn matches(&self, owner: &str, name: &str) -> bool {
+        self.owner.eq_ignore_ascii_case(owner) && self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+impl std::str::FromStr for RepoSlug {
+    type Err = GarnixError;
+
+    /// Parse an `owner/name` string
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once('/') {
+            Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+                Ok(RepoSlug {
+                    owner: owner.to_string(),
+                    name: name.to_string(),
+                })
+            }
+            _ => Err(GarnixError::validation(format!(
+                "Invalid repository '{}', expected owner/name",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for RepoSlug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
+
+/// Check if a string is a full 40-character hexadecimal commit SHA
+pub fn is_full_commit_sha(revision: &str) -> bool {
+    revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Resolve a git revision (`HEAD`, branch, tag, short SHA, `HEAD~3`, ...) to a full commit SHA
+///
+/// Full SHAs are returned as-is without touching git, so this also works outside
+/// a checkout. Other revisions are resolved against the repository in the
+/// current directory.
+///
+/// # Errors
+/// Returns a validation error if git is unavailable or the revision is unknown
+pub async fn resolve_commit(revision: &str) -> GarnixResult<String> {
+    resolve_commit_in(Path::new("."), revision).await
+}
+
+/// Resolve a git revision against the repository in `dir`
+///
+/// # Errors
+/// Returns a validation error if git is unavailable or the revision is unknown
+pub async fn resolve_commit_in(dir: &Path, revision: &str) -> GarnixResult<String> {
+    if is_full_commit_sha(revision) {
+        return Ok(revision.to_ascii_lowercase());
+    }
+    check_revision(revision)?;
+
+    let spec = format!("{}^{{commit}}", revision);
+    let sha = run_git(dir, &["rev-parse", "--verify", "--quiet", &spec])
+        .await
+        .map_err(|e| {
+            GarnixError::validation(format!("Cannot resolve git revision '{}': {}", revision, e))
+        })?;
+
+    debug!("Resolved git revision {} to {}", revision, sha);
+    Ok(sha)
+}
+
+/// List the most recent commits reachable from a git revision, newest first
+///
+/// # Errors
+/// Returns a validation error if git is unavailable or the revision is unknown
+pub async fn recent_commits(revision: &str, count: usize) -> GarnixResult<Vec<String>> {
+    recent_commits_in(Path::new("."), revision, count).await
+}
+
+/// List the most recent commits reachable from a revision in the repository in `dir`
+///
+/// # Errors
+/// Returns a validation error if git is unavailable or the revision is unknown
+pub async fn recent_commits_in(
+    dir: &Path,
+    revision: &str,
+    count: usize,
+) -> GarnixResult<Vec<String>> {
+    check_revision(revision)?;
+    let max_count = format!("--max-count={}", count);
+    let commits = run_git(dir, &["rev-list", &max_count, revision, "--"])
+        .await
+        .map_err(|e| {
+            GarnixError::validation(format!("Cannot list commits of '{}': {}", revision, e))
+        })?;
+    Ok(commits.lines().map(String::from).collect())
+}
+
+/// Reject revisions that git would parse as an option
+fn check_revision(revision: &str) -> GarnixResult<()> {
+    if revision.starts_with('-') {
+        return Err(GarnixError::validation(format!(
+            "Invalid git revision '{}'",
+            revision
+        )));
+    }
+    Ok(())
+}
+
+/// Infer the repository owner and name from the `origin` remote of the current directory
+///
+/// Returns `None` when not inside a git checkout, when there is no `origin`
+/// remote, or when its URL cannot be parsed.
+pub async fn origin_repo() -> Option<RepoSlug> {
+    origin_repo_in(Path::new(".")).await
+}
+
+/// Infer the repository owner and name from the `origin` remote of `dir`
+pub async fn origin_repo_in(dir: &Path) -> Option<RepoSlug> {
+    match run_git(dir, &["remote", "get-url", "origin"]).await {
+        Ok(url) => parse_remote_url(&url),
+        Err(e) => {
+            debug!("No usable origin remote: {}", e);
+            None
+        }
+    }
+}
+
+/// Parse a git remote URL into owner and name
+///
+/// Supports scp-like (`git@github.com:owner/repo.git`) and URL
+/// (`https://github.com/owner/repo`, `ssh://git@host/owner/repo.git`) forms.
+pub fn parse_remote_url(url: &str) -> Option<RepoSlug> {
+    let url = url.trim();
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => url.split_once(':')?.1,
+    };
+
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let mut segments = path.rsplit('/');
+    let name = segments.next().filter(|s| !s.is_empty())?;
+    let owner = segments.next().filter(|s| !s.is_empty())?;
+
+    Some(RepoSlug {
+        owner: owner.to_string(),
+        name: name.to_string(),
+    })
+}
+
+/// Run a git command in `dir` and return its trimmed stdout
+async fn run_git(dir: &Path, args: &[&str]) -> GarnixResult<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await
+        .map_err(|e| GarnixError::io(format!("Failed to run git: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = match stderr.trim() {
+            "" => "unknown revision".to_string(),
+            stderr => stderr.to_string(),
+        };
+        return Err(GarnixError::validation(message));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_url() {
+        let expected = Some(RepoSlug {
+            owner: "shift".to_string(),
+            name: "garnix-insights".to_string(),
+        });
+
+        assert_eq!(
+            parse_remote_url("git@github.com:shift/garnix-insights.git"),
+            expected
+        );
+        assert_eq!(
+            parse_remote_url("https://github.com/shift/garnix-insights"),
+            expected
+        );
+        assert_eq!(
+            parse_remote_url("https://github.com/shift/garnix-insights.git/"),
+            expected
+        );
+        assert_eq!(
+            parse_remote_url("ssh://git@github.com/shift/garnix-insights.git\n"),
+            expected
+        );
+        assert_eq!(parse_remote_url("https://github.com/"), None);
+        assert_eq!(parse_remote_url("not a url"), None);
+    }
+
+    #[test]
+    fn test_repo_slug_matches() {
+        let slug = RepoSlug {
+            owner: "Shift".to_string(),
+            name: "garnix-insights".to_string(),
+        };
+        assert!(slug.matches("shift", "Garnix-Insights"));
+        assert!(!slug.matches("shift", "other"));
+        assert_eq!(slug.to_string(), "Shift/garnix-insights");
+
+        let parsed: RepoSlug = "shift/garnix-insights".parse().unwrap();
+        assert!(parsed.matches("shift", "garnix-insights"));
+        assert!("shift".parse::<RepoSlug>().is_err());
+        assert!("a/b/c".parse::<RepoSlug>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_full_sha_is_returned_without_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let sha = "3B8E1F2A9C5D7E4A6B2F9E7C1A4D8F3A5C2E9B7D";
+        let resolved = resolve_commit_in(dir.path(), sha).await.unwrap();
+        assert_eq!(resolved, sha.to_ascii_lowercase());
+    }
+
+    #[tokio::test]
+    async fn test_option_like_revisions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        for revision in ["--output=/tmp/x", "-h"] {
+            assert!(matches!(
+                resolve_commit_in(dir.path(), revision).await,
+                Err(GarnixError::ValidationError(msg)) if msg.contains("Invalid git revision")
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_revisions_in_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+        };
+        // Skip when git is not installed (e.g. inside the Nix build sandbox)
+        if git(&["init", "-q"]).is_err() {
+            return;
+        }
+        let commit = ["-c", "user.name=t", "-c", "user.email=t@t", "commit", "-q"];
+        git(&[&commit[..], &["--allow-empty", "-m", "one"]].concat()).unwrap();
+        git(&[&commit[..], &["--allow-empty", "-m", "two"]].concat()).unwrap();
+        git(&["remote", "add", "origin", "git@github.com:owner/repo.git"]).unwrap();
+
+        let head = resolve_commit_in(dir.path(), "HEAD").await.unwrap();
+        let parent = resolve_commit_in(dir.path(), "HEAD~1").await.unwrap();
+        assert!(is_full_commit_sha(&head));
+        assert!(is_full_commit_sha(&parent));
+        assert_ne!(head, parent);
+
+        let short = resolve_commit_in(dir.path(), &head[..8]).await.unwrap();
+        assert_eq!(short, head);
+
+        let missing = resolve_commit_in(dir.path(), "no-such-branch").await;
+        assert!(matches!(missing, Err(GarnixError::ValidationError(_))));
+
+        let origin = origin_repo_in(dir.path()).await.unwrap();
+        assert_eq!(origin.to_string(), "owner/repo");
+
+        let commits = recent_commits_in(dir.path(), "HEAD", 5).await.unwrap();
+        assert_eq!(commits, vec![head.clone(), parent]);
+        let commits = recent_commits_in(dir.path(), "HEAD", 1).await.unwrap();
+        assert_eq!(commits, vec![head]);
+        assert!(recent_commits_in(dir.path(), "no-such-branch", 5)
+            .await
+            .is_err());
+    }
+}
@@ -8,6 +8,7 @@
 //! ## Features
 //!
 //! - **HTTP Client**: Fetch build status from Garnix.io API
+//! - **Automatic Retries**: Exponential backoff honouring `Retry-After`
 //! - **Multiple Output Formats**: JSON and human-readable formatting
 //! - **CLI Interface**: Command-line tool with multiple commands
 //! - **HTTP Server**: REST API server for build status queries
@@ -29,16 +30,25 @@
 //! }
 //! ```
 
+pub mod analysis;
+pub mod auth;
 pub mod cli;
 pub mod client;
+pub mod compare;
+pub mod config;
 pub mod error;
+pub mod excerpt;
+pub mod git;
 pub mod mcp;
+pub mod retry;
 pub mod server;
 pub mod types;
 
+pub use auth::TokenProvider;
 pub use client::GarnixClient;
-pub use error::{GarnixError, GarnixResult};
-pub use types::{Build, GarnixRequest, GarnixResponse, Summary};
+pub use error::{GarnixError, GarnixResult, HttpErrorDetails};
+pub use retry::RetryPolicy;
+pub use types::{Build, BuildStatusReport, GarnixRequest, GarnixResponse, Run, Summary};
 
 /// The main function to fetch Garnix data for a given commit
 ///
@@ -70,13 +80,18 @@ pub use types::{Build, GarnixRequest, GarnixResponse, Summary};
 /// ```
 pub async fn get_garnix_data(jwt_token: &str, commit_id: &str) -> GarnixResult<GarnixResponse> {
     let client = GarnixClient::new();
-    client.fetch_build_status(jwt_token, commit_id).await
+    client
+        .with_token(jwt_token)
+        .fetch_build_status(commit_id)
+        .await
 }
 
 /// Format a build summary as a human-readable string
 ///
 /// Creates a markdown-formatted summary of the build status including
 /// repository information, branch, timing, and success/failure counts.
+/// Failed runs (e.g. evaluation errors that produced no builds) are listed
+/// in a separate section.
 ///
 /// # Arguments
 /// * `response` - The Garnix response to format
@@ -96,7 +111,7 @@ pub async fn get_garnix_data(jwt_token: &str, commit_id: &str) -> GarnixResult<G
 ///         git_commit: "abc123".to_string(),
 ///         branch: "main".to_string(),
 ///         req_user: "user".to_string(),
-///         start_time: "2024-01-01T00:00:00Z".to_string(),
+///         start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
 ///         succeeded: 5,
 ///         failed: 1,
 ///         pending: 0,
@@ -109,11 +124,12 @@ pub async fn get_garnix_data(jwt_token: &str, commit_id: &str) -> GarnixResult<G
 /// assert!(summary.contains("# Build Summary"));
 /// ```
 pub fn format_build_summary(response: &GarnixResponse) -> String {
-    format!(
+    let mut summary = format!(
         "# Build Summary for {}\n\n\
          **Repository:** {}/{}\n\
          **Branch:** {}\n\
-         **Started:** {}\n\n\
+         **Started:** {}\n\
+         **Wall clock:** {}\n\n\
          ## Summary\n\
          - [OK] Succeeded: {}\n\
          - [FAIL] Failed: {}\n\
@@ -123,12 +139,40 @@ pub fn format_build_summary(response: &GarnixResponse) -> String {
         response.summary.repo_owner,
         response.summary.repo_name,
         response.summary.branch,
-        response.summary.start_time,
+        match response.summary.start_time {
+            Some(start_time) => start_time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            None => "n/a".to_string(),
+        },
+        match response.wall_clock() {
+            Some(wall_clock) => format_duration(wall_clock),
+            None => "n/a".to_string(),
+        },
         response.summary.succeeded,
         response.summary.failed,
         response.summary.pending,
         response.summary.cancelled
-    )
+    );
+
+    let failed_runs = response.failed_runs();
+    if !failed_runs.is_empty() {
+        summary.push_str("\n\n## Failed Runs");
+        for run in failed_runs {
+            summary.push_str(&format!(
+                "\n- [FAIL] {} ({})",
+                run.label(),
+                run.status.as_deref().unwrap_or("unknown status")
+            ));
+            if let Some(error) = &run.error {
+                summary.push_str(&format!(": {}", error.lines().next().unwrap_or_default()));
+            }
+            // Without a GitHub run ID, the builds of a run are unknown
+            if run.github_run_id.is_some() && response.builds_for_run(run).is_empty() {
+                summary.push_str(" — no builds were produced");
+            }
+        }
+    }
+
+    summary
 }
 
 /// Format build details as a human-readable string
@@ -159,8 +203,8 @@ pub fn format_build_summary(response: &GarnixResponse) -> String {
 ///         system: Some("x86_64-linux".to_string()),
 ///         req_user: "user".to_string(),
 ///         status: "Success".to_string(),
-///         start_time: "2024-01-01T00:00:00Z".to_string(),
-///         end_time: "2024-01-01T00:01:00Z".to_string(),
+///         start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+///         end_time: Some("2024-01-01T00:01:00Z".parse().unwrap()),
 ///         drv_path: None,
 ///         output_paths: None,
 ///         github_run_id: 123,
@@ -191,10 +235,14 @@ pub fn format_build_details(builds: &[Build]) -> String {
             build_details.push_str(&format!("- **System:** {}\n", system));
         }
 
+        let duration = match build.duration() {
+            Some(duration) => format_duration(duration),
+            None => "running".to_string(),
+        };
         build_details.push_str(&format!(
-            "- **Duration:** {} → {}\n\
+            "- **Duration:** {}\n\
              - **Build ID:** {}\n\n",
-            build.start_time, build.end_time, build.id
+            duration, build.id
         ));
 
         if build.is_failed() {
@@ -207,6 +255,40 @@ pub fn format_build_details(builds: &[Build]) -> String {
     build_details
 }
 
+/// Serialize a build status to JSON, annotated with human-readable durations
+///
+/// Each build gains `duration` and `duration_secs` fields (`null` while running)
+/// and the top level gains `wall_clock` and `wall_clock_secs`, as described by
+/// [`BuildStatusReport`].
+pub fn build_status_json(response: &GarnixResponse) -> GarnixResult<serde_json::Value> {
+    Ok(serde_json::to_value(BuildStatusReport::from(response))?)
+}
+
+/// Format a duration compactly, e.g. `45s`, `3m12s`, `1h02m05s` or `2d03h00m`
+///
+/// Durations of a day or more drop the seconds.
+///
+/// # Example
+/// ```rust
+/// # use garnix_insights::format_duration;
+/// assert_eq!(format_duration(chrono::Duration::seconds(192)), "3m12s");
+/// ```
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (days, hours, minutes, seconds) = (
+        seconds / 86400,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+    );
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, _) => format!("{}m{:02}s", minutes, seconds),
+        (0, _, _) => format!("{}h{:02}m{:02}s", hours, minutes, seconds),
+        _ => format!("{}d{:02}h{:02}m", days, hours, minutes),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,7 +303,7 @@ mod tests {
                 git_commit: "abc123def456".to_string(),
                 branch: "main".to_string(),
                 req_user: "testuser".to_string(),
-                start_time: "2024-01-01T00:00:00Z".to_string(),
+                start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
                 succeeded: 5,
                 failed: 1,
                 pending: 0,
@@ -240,8 +322,8 @@ mod tests {
                     system: Some("x86_64-linux".to_string()),
                     req_user: "testuser".to_string(),
                     status: "Success".to_string(),
-                    start_time: "2024-01-01T00:00:00Z".to_string(),
-                    end_time: "2024-01-01T00:01:00Z".to_string(),
+                    start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+                    end_time: Some("2024-01-01T00:01:00Z".parse().unwrap()),
                     drv_path: Some("/nix/store/test.drv".to_string()),
                     output_paths: Some(HashMap::new()),
                     github_run_id: 123456,
@@ -261,8 +343,8 @@ mod tests {
                     system: Some("x86_64-linux".to_string()),
                     req_user: "testuser".to_string(),
                     status: "Failed".to_string(),
-                    start_time: "2024-01-01T00:00:00Z".to_string(),
-                    end_time: "2024-01-01T00:01:00Z".to_string(),
+                    start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+                    end_time: Some("2024-01-01T00:01:00Z".parse().unwrap()),
                     drv_path: Some("/nix/store/test2.drv".to_string()),
                     output_paths: Some(HashMap::new()),
                     github_run_id: 123457,
@@ -295,6 +377,33 @@ mod tests {
         assert!(formatted.contains("**Branch:** main"));
         assert!(formatted.contains("[OK] Succeeded: 5"));
         assert!(formatted.contains("[FAIL] Failed: 1"));
+        assert!(formatted.contains("**Started:** 2024-01-01 00:00:00 UTC"));
+        assert!(formatted.contains("**Wall clock:** 1m00s"));
+        assert!(!formatted.contains("## Failed Runs"));
+    }
+
+    #[test]
+    fn test_format_build_summary_failed_runs() {
+        let mut response = create_test_response();
+        response.runs.push(types::Run {
+            id: Some("run-1".to_string()),
+            github_run_id: Some(999),
+            status: Some("Failed".to_string()),
+            error: Some("error: undefined variable 'foo'\n  at flake.nix:3".to_string()),
+            ..Default::default()
+        });
+
+        let formatted = format_build_summary(&response);
+        assert!(formatted.contains("## Failed Runs"));
+        assert!(formatted.contains(
+            "[FAIL] run run-1 (Failed): error: undefined variable 'foo' — no builds were produced"
+        ));
+        assert!(!formatted.contains("flake.nix:3"));
+
+        response.runs[0].github_run_id = None;
+        let formatted = format_build_summary(&response);
+        assert!(formatted.contains("[FAIL] run run-1 (Failed): error: undefined variable 'foo'"));
+        assert!(!formatted.contains("no builds were produced"));
     }
 
     #[test]
@@ -308,6 +417,7 @@ mod tests {
         assert!(formatted.contains("### package2"));
         assert!(formatted.contains("[FAIL] Failed"));
         assert!(formatted.contains("**System:** x86_64-linux"));
+        assert!(formatted.contains("**Duration:** 1m00s"));
     }
 
     #[test]
@@ -315,4 +425,29 @@ mod tests {
         let details = format_build_details(&[]);
         assert!(details.contains("## No builds found"));
     }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(chrono::Duration::zero()), "0s");
+        assert_eq!(format_duration(chrono::Duration::seconds(45)), "45s");
+        assert_eq!(format_duration(chrono::Duration::seconds(192)), "3m12s");
+        assert_eq!(format_duration(chrono::Duration::seconds(305)), "5m05s");
+        assert_eq!(format_duration(chrono::Duration::seconds(3725)), "1h02m05s");
+        assert_eq!(format_duration(chrono::Duration::seconds(-3)), "0s");
+        assert_eq!(format_duration(chrono::Duration::hours(51)), "2d03h00m");
+    }
+
+    #[test]
+    fn test_build_status_json_durations() {
+        let mut response = create_test_response();
+        response.builds[1].end_time = None;
+
+        let value = build_status_json(&response).unwrap();
+        assert_eq!(value["builds"][0]["duration"], "1m00s");
+        assert_eq!(value["builds"][0]["duration_secs"], 60);
+        assert!(value["builds"][1]["duration"].is_null());
+        assert_eq!(value["builds"][1]["end_time"], serde_json::Value::Null);
+        assert_eq!(value["wall_clock"], "1m00s");
+        assert_eq!(value["summary"]["start_time"], "2024-01-01T00:00:00Z");
+    }
 }
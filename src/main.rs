@@ -3,15 +3,18 @@
 //! A command-line tool for fetching build status information from Garnix.io.
 
 use clap::Parser;
-use garnix_insights::cli::Cli;
+use garnix_insights::cli::{Cli, ExitStatus};
 use std::process;
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
 
-    if let Err(e) = cli.run().await {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    match cli.run().await {
+        Ok(status) => process::exit(status.code()),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(ExitStatus::from_error(&e).code());
+        }
     }
 }
@@ -3,11 +3,34 @@
 //! This module provides MCP server functionality for AI agents to query Garnix build status.
 //! Uses a simple JSON-RPC 2.0 implementation over stdio.
 
-use crate::client::GarnixClient;
+mod sampling;
+mod tools;
+
+use crate::analysis::{self, format_failure_reports, Diagnosis, FailureReport};
+use crate::client::{GarnixClient, WaitOptions, MAX_CONCURRENT_REQUESTS};
+use crate::compare::format_comparison;
+pub use crate::config::TokenPolicy;
 use crate::error::{GarnixError, GarnixResult};
+use crate::excerpt::{self, ExcerptOptions};
+use crate::git::{self, RepoSlug};
+use crate::types::{Build, BuildFilter, BuildStatus, GarnixResponse, LogResponse};
+use crate::{build_status_json, format_build_details, format_build_summary, format_duration};
+use base64::Engine;
+use futures::future::{AbortHandle, Abortable};
+use futures::stream::{FuturesUnordered, StreamExt};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tools::{
+    BuildLogsArguments, BuildStatusArguments, CompareArguments, DiagnoseArguments,
+    ReadinessArguments, SummarizeArguments, ToolDescriptor, WaitArguments, TOOLS,
+};
 
 /// Negotiate a supported MCP protocol version from an optional selector
 pub fn negotiate_version(requested: Option<&str>) -> McpVersion {
@@ -20,13 +43,213 @@ pub fn negotiate_version(requested: Option<&str>) -> McpVersion {
     McpVersion::V2025_03_26
 }
 
+tokio::task_local! {
+    /// Stream answering the request being handled, which takes the messages
+    /// related to it instead of the subscribers
+    static RELATED_MESSAGES: mpsc::UnboundedSender<String>;
+}
+
 /// MCP server for Garnix Insights
 pub struct GarnixMcpServer {
     client: GarnixClient,
     version: McpVersion,
+    token_policy: TokenPolicy,
+    enabled_tools: Mutex<Option<Vec<String>>>,
+    state: Mutex<SessionState>,
+    messages: broadcast::Sender<String>,
+    recent: Mutex<VecDeque<GarnixResponse>>,
+    requests: Mutex<HashMap<String, AbortHandle>>,
+    client_capabilities: Mutex<Value>,
+    outgoing: Mutex<HashMap<String, ResponseSender>>,
+    request_subscribers: Mutex<Vec<mpsc::UnboundedSender<String>>>,
+    next_request_id: AtomicU64,
+}
+
+/// Check that every name refers to a tool of the MCP server
+///
+/// # Errors
+/// Returns a configuration error listing the available tools for unknown names
+pub fn check_tool_names(names: &[String]) -> GarnixResult<()> {
+    match names
+        .iter()
+        .find(|name| ToolDescriptor::find(name).is_none())
+    {
+        Some(unknown) => Err(GarnixError::config(format!(
+            "Unknown MCP tool '{}', expected one of: {}",
+            unknown,
+            TOOLS
+                .iter()
+                .map(|tool| tool.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Number of recently fetched commits offered by `resources/list`
+const RECENT_COMMITS: usize = 20;
+
+/// Default maximum number of open MCP sessions
+pub const DEFAULT_MAX_SESSIONS: usize = 64;
+/// Default time after which an MCP session without requests is ended
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Registry of MCP sessions served over the streamable HTTP transport
+///
+/// Every session gets its own [`GarnixMcpServer`], and with it its own
+/// lifecycle state, sharing the configured client. Sessions that saw no
+/// request for the idle timeout are ended, and no more than the maximum
+/// number of sessions are open at once.
+#[derive(Clone)]
+pub struct McpSessions {
+    client: GarnixClient,
+    version: McpVersion,
+    token_policy: TokenPolicy,
+    max_sessions: usize,
+    idle_timeout: Duration,
+    enabled_tools: Arc<Mutex<Option<Vec<String>>>>,
+    sessions: Arc<Mutex<HashMap<String, SessionEntry>>>,
+}
+
+/// An open session and when it was last used
+struct SessionEntry {
+    server: Arc<GarnixMcpServer>,
+    last_used: Instant,
+}
+
+impl McpSessions {
+    /// Create an empty registry for sessions using the given client and version
+    pub fn new(client: GarnixClient, version: McpVersion) -> Self {
+        Self {
+            client,
+            version,
+            token_policy: TokenPolicy::default(),
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            idle_timeout: DEFAULT_SESSION_IDLE_TIMEOUT,
+            enabled_tools: Arc::new(Mutex::new(None)),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Set the token policy of new sessions
+    pub fn with_token_policy(mut self, policy: TokenPolicy) -> Self {
+        self.token_policy = policy;
+        self
+    }
+
+    /// Set the maximum number of open sessions
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = max_sessions;
+        self
+    }
+
+    /// Set the time after which a session without requests is ended
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Only offer the named tools in new sessions, or all but the opt-in tools for `None`
+    pub fn with_enabled_tools(self, tools: Option<Vec<String>>) -> Self {
+        *self.enabled_tools.lock().unwrap() = tools;
+        self
+    }
+
+    /// Change the offered tools of new and open sessions
+    pub fn set_enabled_tools(&self, tools: Option<Vec<String>>) {
+        *self.enabled_tools.lock().unwrap() = tools.clone();
+        for entry in self.sessions.lock().unwrap().values() {
+            entry.server.set_enabled_tools(tools.clone());
+        }
+    }
+
+    /// The client shared by all sessions
+    pub fn client(&self) -> &GarnixClient {
+        &self.client
+    }
+
+    /// Start a new session, returning its ID
+    ///
+    /// # Errors
+    /// Returns `GarnixError::RateLimit` if the maximum number of sessions is open
+    pub fn create(&self) -> GarnixResult<(String, Arc<GarnixMcpServer>)> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        if sessions.len() >= self.max_sessions {
+            return Err(GarnixError::RateLimit(format!(
+                "Too many MCP sessions (at most {})",
+                self.max_sessions
+            )));
+        }
+
+        let id = format!("{:032x}", rand::rng().random::<u128>());
+        let server = Arc::new(
+            GarnixMcpServer::with_client_and_version(self.client.clone(), self.version)
+                .with_token_policy(self.token_policy)
+                .with_enabled_tools(self.enabled_tools.lock().unwrap().clone()),
+        );
+        sessions.insert(
+            id.clone(),
+            SessionEntry {
+                server: server.clone(),
+                last_used: Instant::now(),
+            },
+        );
+        Ok((id, server))
+    }
+
+    /// Look up a session by ID, marking it as used
+    pub fn get(&self, id: &str) -> Option<Arc<GarnixMcpServer>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        sessions.get_mut(id).map(|entry| {
+            entry.last_used = Instant::now();
+            entry.server.clone()
+        })
+    }
+
+    /// End a session, returning whether it existed
+    pub fn remove(&self, id: &str) -> bool {
+        self.sessions.lock().unwrap().remove(id).is_some()
+    }
+
+    /// Number of open sessions
+    pub fn len(&self) -> usize {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.expire(&mut sessions);
+        sessions.len()
+    }
+
+    /// Check if there are no open sessions
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// End the sessions that were idle for longer than the timeout
+    fn expire(&self, sessions: &mut HashMap<String, SessionEntry>) {
+        sessions.retain(|id, entry| {
+            let alive = entry.last_used.elapsed() < self.idle_timeout;
+            if !alive {
+                tracing::info!("Ending idle MCP session {}", id);
+            }
+            alive
+        });
+    }
 }
 
+/// Lifecycle state of the MCP session
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionState {
+    /// Waiting for the `initialize` request
+    AwaitingInitialize,
+    /// `initialize` was answered, waiting for `notifications/initialized`
+    Initializing(McpVersion),
+    /// Normal operation
+    Ready(McpVersion),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 /// Supported MCP protocol versions (aligned with OpenCode)
 pub enum McpVersion {
     /// Legacy protocol version
@@ -55,6 +278,11 @@ impl McpVersion {
         }
     }
 
+    /// Whether tools can declare an `outputSchema` and return `structuredContent`
+    pub fn supports_structured_output(self) -> bool {
+        self >= Self::V2025_06_18
+    }
+
     /// Return canonical date string for the version
     pub fn as_str(self) -> &'static str {
         match self {
@@ -74,102 +302,334 @@ impl Default for GarnixMcpServer {
 impl GarnixMcpServer {
     /// Create a new MCP server instance
     pub fn new() -> Self {
-        Self {
-            client: GarnixClient::new(),
-            version: McpVersion::V2025_03_26,
-        }
+        Self::with_client(GarnixClient::new())
     }
 
     /// Create a new MCP server with custom Garnix client
     pub fn with_client(client: GarnixClient) -> Self {
+        Self::with_client_and_version(client, McpVersion::V2025_03_26)
+    }
+
+    /// Create a new MCP server with custom client and version
+    pub fn with_client_and_version(client: GarnixClient, version: McpVersion) -> Self {
         Self {
             client,
-            version: McpVersion::V2025_03_26,
+            version,
+            token_policy: TokenPolicy::default(),
+            enabled_tools: Mutex::new(None),
+            state: Mutex::new(SessionState::AwaitingInitialize),
+            messages: broadcast::channel(64).0,
+            recent: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(HashMap::new()),
+            client_capabilities: Mutex::new(json!({})),
+            outgoing: Mutex::new(HashMap::new()),
+            request_subscribers: Mutex::new(Vec::new()),
+            next_request_id: AtomicU64::new(1),
         }
     }
 
-    /// Create a new MCP server with custom client and version
-    pub fn with_client_and_version(client: GarnixClient, version: McpVersion) -> Self {
-        Self { client, version }
+    /// Set whether tool calls may pass their own `token` argument
+    pub fn with_token_policy(mut self, policy: TokenPolicy) -> Self {
+        self.token_policy = policy;
+        self
+    }
+
+    /// Only offer the named tools, or all but the opt-in tools for `None`
+    pub fn with_enabled_tools(self, tools: Option<Vec<String>>) -> Self {
+        *self.enabled_tools.lock().unwrap() = tools;
+        self
+    }
+
+    /// Change the offered tools, notifying the client if the set changed
+    pub fn set_enabled_tools(&self, tools: Option<Vec<String>>) {
+        let before = self.enabled_tool_names();
+        *self.enabled_tools.lock().unwrap() = tools;
+        if self.enabled_tool_names() == before {
+            return;
+        }
+        tracing::info!(
+            "Enabled MCP tools changed to {:?}",
+            self.enabled_tool_names()
+        );
+        if matches!(*self.state.lock().unwrap(), SessionState::Ready(_)) {
+            self.notify("notifications/tools/list_changed", json!({}));
+        }
+    }
+
+    /// Names of the offered tools
+    pub fn enabled_tool_names(&self) -> Vec<&'static str> {
+        self.enabled_tools().iter().map(|tool| tool.name).collect()
+    }
+
+    fn enabled_tools(&self) -> Vec<&'static ToolDescriptor> {
+        let enabled = self.enabled_tools.lock().unwrap();
+        TOOLS
+            .iter()
+            .filter(|tool| match enabled.as_ref() {
+                Some(names) => names.iter().any(|name| name == tool.name),
+                None => !tool.opt_in,
+            })
+            .collect()
+    }
+
+    /// Subscribe to messages the server sends on its own, outside of responses
+    ///
+    /// Requests to the client, such as sampling requests, are not included;
+    /// see [`Self::subscribe_requests`].
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.messages.subscribe()
+    }
+
+    /// Subscribe to requests the server sends to the client on its own
+    ///
+    /// Unlike notifications, every request goes to a single subscriber, the
+    /// most recent one still listening, so that the client answers it once.
+    pub fn subscribe_requests(&self) -> mpsc::UnboundedReceiver<String> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.request_subscribers.lock().unwrap().push(sender);
+        receiver
     }
 
     /// Run the MCP server on stdio transport
-    pub async fn run_stdio(self) -> GarnixResult<()> {
+    ///
+    /// Requests are processed concurrently, so that a long-running tool call can
+    /// be cancelled and does not hold up other requests. Every message is
+    /// processed up to its first await before the next line is read, which keeps
+    /// notifications in order with the requests they refer to.
+    pub async fn run_stdio(&self) -> GarnixResult<()> {
         tracing::info!("Starting Garnix Insights MCP server on stdio transport");
 
-        let stdin = tokio::io::stdin();
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
         let mut stdout = tokio::io::stdout();
-        let mut reader = BufReader::new(stdin);
-        let mut line = String::new();
+        let mut messages = self.subscribe();
+        let mut requests = self.subscribe_requests();
+        let mut in_flight = FuturesUnordered::new();
+        let mut reading = true;
 
-        // Send initial server info (announce negotiated/default version and supported list)
-        let server_info = json!({
-            "jsonrpc": "2.0",
-            "id": 0,
-            "result": {
-                "protocolVersion": self.version.as_str(),
-                "serverInfo": {
-                    "name": "garnix-insights",
-                    "version": "0.2.0"
+        while reading || !in_flight.is_empty() {
+            tokio::select! {
+                biased;
+                Some(response) = in_flight.next(), if !in_flight.is_empty() => {
+                    let response: Option<String> = response;
+                    // Progress sent while handling the request goes out before its response
+                    while let Ok(message) = messages.try_recv() {
+                        write_line(&mut stdout, &message).await?;
+                    }
+                    if let Some(response) = response {
+                        write_line(&mut stdout, &response).await?;
+                    }
+                }
+                message = messages.recv() => match message {
+                    Ok(message) => write_line(&mut stdout, &message).await?,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Dropped {} MCP notifications", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {}
                 },
-                "capabilities": {
-                    "tools": {
-                        "listChanged": false
+                Some(request) = requests.recv() => write_line(&mut stdout, &request).await?,
+                line = lines.next_line(), if reading => match line {
+                    Ok(Some(line)) if line.trim().is_empty() => {}
+                    Ok(Some(line)) => {
+                        let server = self;
+                        in_flight.push(async move { server.handle_message(line.trim()).await });
+                    }
+                    Ok(None) => {
+                        tracing::info!("MCP server received EOF, shutting down");
+                        reading = false;
+                    }
+                    Err(e) => {
+                        tracing::error!("Error reading from stdin: {}", e);
+                        return Err(GarnixError::NetworkError(format!("stdin error: {}", e)));
                     }
                 },
-                "supportedVersions": McpVersion::SUPPORTED.iter().map(|v| v.as_str()).collect::<Vec<_>>()
             }
-        });
+        }
 
-        stdout
-            .write_all(format!("{}\n", server_info).as_bytes())
-            .await
-            .map_err(|e| GarnixError::NetworkError(format!("Failed to write to stdout: {}", e)))?;
-        stdout
-            .flush()
+        Ok(())
+    }
+
+    /// Handle a single JSON-RPC message, returning the serialized response if one is due
+    ///
+    /// Notifications and responses from the client are never answered.
+    pub async fn handle_message(&self, message: &str) -> Option<String> {
+        let response = match serde_json::from_str::<Value>(message) {
+            Err(e) => {
+                tracing::warn!("Invalid JSON received: {}", e);
+                Some(McpResponse::failure(
+                    Value::Null,
+                    McpError::new(PARSE_ERROR, format!("Parse error: {}", e)),
+                ))
+            }
+            Ok(value) => self.handle_value(value).await,
+        };
+        response.map(|response| serde_json::to_string(&response).unwrap_or_default())
+    }
+
+    /// Handle a single JSON-RPC message like [`Self::handle_message`], sending
+    /// the messages related to it to `related` instead of the subscribers
+    ///
+    /// Progress notifications and requests to the client made while handling
+    /// the message go to `related`, so that the streamable HTTP transport can
+    /// answer the request with an event stream carrying them before the response.
+    pub async fn handle_message_streaming(
+        &self,
+        message: &str,
+        related: mpsc::UnboundedSender<String>,
+    ) -> Option<String> {
+        RELATED_MESSAGES
+            .scope(related, self.handle_message(message))
             .await
-            .map_err(|e| GarnixError::NetworkError(format!("Failed to flush stdout: {}", e)))?;
-
-        loop {
-            line.clear();
-            match reader.read_line(&mut line).await {
-                Ok(0) => {
-                    tracing::info!("MCP server received EOF, shutting down");
-                    break;
+    }
+
+    async fn handle_value(&self, value: Value) -> Option<McpResponse> {
+        let id = value.get("id").cloned();
+        if !value.is_object() {
+            return Some(McpResponse::failure(
+                Value::Null,
+                McpError::new(INVALID_REQUEST, "Invalid Request: expected a JSON object"),
+            ));
+        }
+        if value.get("method").is_none()
+            && (value.get("result").is_some() || value.get("error").is_some())
+        {
+            self.handle_client_response(&value);
+            return None;
+        }
+
+        let request = match serde_json::from_value::<McpRequest>(value) {
+            Ok(request) if request.jsonrpc == "2.0" => request,
+            Ok(_) => {
+                return Some(McpResponse::failure(
+                    id.unwrap_or(Value::Null),
+                    McpError::new(INVALID_REQUEST, "Invalid Request: jsonrpc must be \"2.0\""),
+                ))
+            }
+            Err(e) => {
+                return Some(McpResponse::failure(
+                    id.unwrap_or(Value::Null),
+                    McpError::new(INVALID_REQUEST, format!("Invalid Request: {}", e)),
+                ))
+            }
+        };
+
+        match request.id.clone() {
+            None => {
+                self.handle_notification(&request);
+                None
+            }
+            Some(id) if !(id.is_string() || id.is_number()) => Some(McpResponse::failure(
+                Value::Null,
+                McpError::new(
+                    INVALID_REQUEST,
+                    "Invalid Request: id must be a string or number",
+                ),
+            )),
+            Some(id) => self.handle_cancellable(request, id).await,
+        }
+    }
+
+    /// Handle a request that `notifications/cancelled` can abort, in which case
+    /// no response is sent
+    async fn handle_cancellable(&self, request: McpRequest, id: Value) -> Option<McpResponse> {
+        if request.method == "initialize" {
+            return Some(self.handle_request(request).await);
+        }
+
+        let key = id.to_string();
+        let (handle, registration) = AbortHandle::new_pair();
+        // Unregistered when the request finishes or its future is dropped,
+        // e.g. because the HTTP client went away
+        let Some(_pending) = PendingRequest::new(&self.requests, key.clone(), handle) else {
+            return Some(McpResponse::failure(
+                id,
+                McpError::new(
+                    INVALID_REQUEST,
+                    format!("Invalid Request: id {} is already in use", key),
+                ),
+            ));
+        };
+        let response = Abortable::new(self.handle_request(request), registration).await;
+
+        match response {
+            Ok(response) => Some(response),
+            Err(_) => {
+                tracing::info!("Request {} was cancelled", key);
+                None
+            }
+        }
+    }
+
+    /// Pass a response from the client to the request waiting for it
+    fn handle_client_response(&self, response: &Value) {
+        let key = response.get("id").unwrap_or(&Value::Null).to_string();
+        let Some(sender) = self.outgoing.lock().unwrap().remove(&key) else {
+            tracing::debug!("Ignoring response to unknown request {}", key);
+            return;
+        };
+        let result = match response.get("error") {
+            Some(error) => Err(McpError::new(
+                error.get("code").and_then(Value::as_i64).unwrap_or(-32000) as i32,
+                error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("Unknown error"),
+            )),
+            None => Ok(response.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        // The request may have timed out or been cancelled in the meantime
+        let _ = sender.send(result);
+    }
+
+    fn handle_notification(&self, notification: &McpRequest) {
+        match notification.method.as_str() {
+            "notifications/initialized" => {
+                let mut state = self.state.lock().unwrap();
+                if let SessionState::Initializing(version) = *state {
+                    tracing::info!("MCP session ready (protocol {})", version.as_str());
+                    *state = SessionState::Ready(version);
                 }
-                Ok(_) => {
-                    if let Ok(request) = serde_json::from_str::<McpRequest>(line.trim()) {
-                        let response = self.handle_request(request).await;
-                        let response_json = serde_json::to_string(&response).unwrap_or_default();
-
-                        stdout
-                            .write_all(format!("{}\n", response_json).as_bytes())
-                            .await
-                            .map_err(|e| {
-                                GarnixError::NetworkError(format!(
-                                    "Failed to write response: {}",
-                                    e
-                                ))
-                            })?;
-                        stdout.flush().await.map_err(|e| {
-                            GarnixError::NetworkError(format!("Failed to flush stdout: {}", e))
-                        })?;
-                    } else {
-                        tracing::warn!("Invalid JSON-RPC request: {}", line.trim());
+            }
+            "notifications/cancelled" => {
+                let Some(id) = notification
+                    .params
+                    .as_ref()
+                    .and_then(|p| p.get("requestId"))
+                else {
+                    tracing::debug!("Ignoring cancellation without requestId");
+                    return;
+                };
+                // Requests that already finished or are unknown are ignored
+                match self.requests.lock().unwrap().get(&id.to_string()) {
+                    Some(handle) => {
+                        tracing::info!("Client cancelled request {}", id);
+                        handle.abort();
                     }
-                }
-                Err(e) => {
-                    tracing::error!("Error reading from stdin: {}", e);
-                    return Err(GarnixError::NetworkError(format!("stdin error: {}", e)));
+                    None => tracing::debug!("Cancelled request {} is not in flight", id),
                 }
             }
+            method => tracing::debug!("Ignoring notification: {}", method),
         }
-
-        Ok(())
     }
 
     async fn handle_request(&self, request: McpRequest) -> McpResponse {
+        let id = request.id.clone().unwrap_or(Value::Null);
+        let state = *self.state.lock().unwrap();
+        if state == SessionState::AwaitingInitialize
+            && !matches!(request.method.as_str(), "initialize" | "ping")
+        {
+            tracing::warn!("Rejecting {} before initialize", request.method);
+            return McpResponse::failure(
+                id,
+                McpError::new(SERVER_NOT_INITIALIZED, "Server not initialized"),
+            );
+        }
+
         match request.method.as_str() {
+            "ping" => McpResponse::success(id, json!({})),
+            "initialize" if state != SessionState::AwaitingInitialize => McpResponse::failure(
+                id,
+                McpError::new(INVALID_REQUEST, "Server already initialized"),
+            ),
             "initialize" => {
                 tracing::info!("Handling initialize request");
                 // Allow client to request a version in params.protocolVersion
@@ -178,12 +638,22 @@ impl GarnixMcpServer {
                     .as_ref()
                     .and_then(|p| p.get("protocolVersion"))
                     .and_then(|v| v.as_str());
-                let chosen = negotiate_version(requested);
+                // Fall back to the configured version when the requested one is unsupported
+                let chosen = requested
+                    .and_then(McpVersion::parse_selector)
+                    .filter(|v| McpVersion::SUPPORTED.contains(v))
+                    .unwrap_or(self.version);
+                *self.state.lock().unwrap() = SessionState::Initializing(chosen);
+                *self.client_capabilities.lock().unwrap() = request
+                    .params
+                    .as_ref()
+                    .and_then(|p| p.get("capabilities"))
+                    .cloned()
+                    .unwrap_or_else(|| json!({}));
 
-                McpResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id: request.id,
-                    result: Some(json!({
+                McpResponse::success(
+                    id,
+                    json!({
                         "protocolVersion": chosen.as_str(),
                         "serverInfo": {
                             "name": "garnix-insights",
@@ -191,303 +661,1546 @@ impl GarnixMcpServer {
                         },
                         "capabilities": {
                             "tools": {
+                                "listChanged": true
+                            },
+                            "resources": {
+                                "subscribe": false,
+                                "listChanged": false
+                            },
+                            "prompts": {
                                 "listChanged": false
                             }
                         },
                         "supportedVersions": McpVersion::SUPPORTED.iter().map(|v| v.as_str()).collect::<Vec<_>>()
-                    })),
-                    error: None,
-                }
+                    }),
+                )
             }
             "tools/list" => {
                 tracing::info!("Handling tools/list request");
-                McpResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id: request.id,
-                    result: Some(json!({
-                        "tools": [
-                            {
-                                "name": "get_build_status",
-                                "description": "Get the build status for a specific commit from Garnix",
-                                "inputSchema": {
-                                    "type": "object",
-                                    "properties": {
-                                        "commit_id": {
-                                            "type": "string",
-                                            "description": "The commit SHA to check build status for"
-                                        },
-                                        "token": {
-                                            "type": "string",
-                                            "description": "JWT token for Garnix API authentication"
-                                        }
-                                    },
-                                    "required": ["commit_id", "token"]
-                                }
-                            },
-                            {
-                                "name": "get_build_logs",
-                                "description": "Get detailed build logs for a specific commit from Garnix",
-                                "inputSchema": {
-                                    "type": "object",
-                                    "properties": {
-                                        "commit_id": {
-                                            "type": "string",
-                                            "description": "The commit SHA to get logs for"
-                                        },
-                                        "token": {
-                                            "type": "string",
-                                            "description": "JWT token for Garnix API authentication"
-                                        }
-                                    },
-                                    "required": ["commit_id", "token"]
-                                }
-                            },
-                            {
-                                "name": "check_commit_ready",
-                                "description": "Check if a commit is ready for deployment (all builds passed)",
-                                "inputSchema": {
-                                    "type": "object",
-                                    "properties": {
-                                        "commit_id": {
-                                            "type": "string",
-                                            "description": "The commit SHA to check readiness for"
-                                        },
-                                        "token": {
-                                            "type": "string",
-                                            "description": "JWT token for Garnix API authentication"
-                                        }
-                                    },
-                                    "required": ["commit_id", "token"]
-                                }
-                            }
-                        ]
-                    })),
-                    error: None,
+                let structured = self.structured_output();
+                let tools: Vec<Value> = self
+                    .enabled_tools()
+                    .iter()
+                    .map(|tool| tool.to_json(structured))
+                    .collect();
+                match paginate(tools, request.params.as_ref()) {
+                    Ok((tools, next_cursor)) => {
+                        let mut result = json!({ "tools": tools });
+                        apply_token_policy(&mut result, self.token_policy);
+                        McpResponse::success(id, with_next_cursor(result, next_cursor))
+                    }
+                    Err(error) => McpResponse::failure(id, error),
                 }
             }
             "tools/call" => {
                 tracing::info!("Handling tools/call request");
                 match self.handle_tool_call(request.params).await {
-                    Ok(result) => McpResponse {
-                        jsonrpc: "2.0".to_string(),
-                        id: request.id,
-                        result: Some(result),
-                        error: None,
-                    },
-                    Err(error_msg) => McpResponse {
-                        jsonrpc: "2.0".to_string(),
-                        id: request.id,
-                        result: None,
-                        error: Some(McpError {
-                            code: -32000,
-                            message: error_msg,
-                            data: None,
-                        }),
-                    },
+                    Ok(result) => McpResponse::success(id, result),
+                    Err(error) => McpResponse::failure(id, error),
+                }
+            }
+            "resources/list" => match paginate(self.list_resources(), request.params.as_ref()) {
+                Ok((resources, next_cursor)) => McpResponse::success(
+                    id,
+                    with_next_cursor(json!({ "resources": resources }), next_cursor),
+                ),
+                Err(error) => McpResponse::failure(id, error),
+            },
+            "resources/templates/list" => McpResponse::success(
+                id,
+                json!({
+                    "resourceTemplates": [
+                        {
+                            "uriTemplate": "garnix://commit/{sha}",
+                            "name": "commit",
+                            "title": "Commit CI status",
+                            "description": "Build summary and individual builds of a commit",
+                            "mimeType": "text/markdown"
+                        },
+                        {
+                            "uriTemplate": "garnix://build/{id}",
+                            "name": "build",
+                            "title": "Build details",
+                            "description": "Status, system, duration and derivation of a build. Only builds of commits read earlier in this session through garnix://commit/{sha} resolve",
+                            "mimeType": "text/markdown"
+                        },
+                        {
+                            "uriTemplate": "garnix://build/{id}/logs",
+                            "name": "build-logs",
+                            "title": "Build logs",
+                            "description": "Excerpt around the first error (markdown) and the full log (JSON) of a build",
+                            "mimeType": "text/markdown"
+                        }
+                    ]
+                }),
+            ),
+            "resources/read" => {
+                tracing::info!("Handling resources/read request");
+                match self.read_resource(request.params).await {
+                    Ok(result) => McpResponse::success(id, result),
+                    Err(error) => McpResponse::failure(id, error),
+                }
+            }
+            "prompts/list" => match paginate(prompt_list(), request.params.as_ref()) {
+                Ok((prompts, next_cursor)) => McpResponse::success(
+                    id,
+                    with_next_cursor(json!({ "prompts": prompts }), next_cursor),
+                ),
+                Err(error) => McpResponse::failure(id, error),
+            },
+            "prompts/get" => {
+                tracing::info!("Handling prompts/get request");
+                match self.get_prompt(request.params).await {
+                    Ok(result) => McpResponse::success(id, result),
+                    Err(error) => McpResponse::failure(id, error),
                 }
             }
             _ => {
                 tracing::warn!("Unknown method: {}", request.method);
-                McpResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id: request.id,
-                    result: None,
-                    error: Some(McpError {
-                        code: -32601,
-                        message: "Method not found".to_string(),
-                        data: None,
-                    }),
-                }
+                McpResponse::failure(
+                    id,
+                    McpError::new(
+                        METHOD_NOT_FOUND,
+                        format!("Method not found: {}", request.method),
+                    ),
+                )
             }
         }
     }
 
-    async fn handle_tool_call(&self, params: Option<Value>) -> Result<Value, String> {
+    async fn handle_tool_call(&self, params: Option<Value>) -> Result<Value, McpError> {
         let params = params.ok_or("Missing parameters for tool call")?;
         let tool_name = params
             .get("name")
             .and_then(|v| v.as_str())
             .ok_or("Missing tool name")?;
         let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+        let progress_token = params
+            .get("_meta")
+            .and_then(|meta| meta.get("progressToken"))
+            .cloned();
+
+        let tool = self
+            .enabled_tools()
+            .into_iter()
+            .find(|tool| tool.name == tool_name)
+            .ok_or_else(|| McpError::new(INVALID_PARAMS, format!("Unknown tool: {}", tool_name)))?;
 
-        match tool_name {
-            "get_build_status" => self.handle_get_build_status(arguments).await,
-            "get_build_logs" => self.handle_get_build_logs(arguments).await,
-            "check_commit_ready" => self.handle_check_commit_ready(arguments).await,
-            _ => Err(format!("Unknown tool: {}", tool_name)),
+        match tool.name {
+            "get_build_status" => {
+                self.handle_get_build_status(tool.arguments(arguments)?)
+                    .await
+            }
+            "get_build_logs" => self.handle_get_build_logs(tool.arguments(arguments)?).await,
+            "check_commit_ready" => {
+                self.handle_check_commit_ready(tool.arguments(arguments)?)
+                    .await
+            }
+            "diagnose_build_failures" => {
+                self.handle_diagnose_build_failures(tool.arguments(arguments)?)
+                    .await
+            }
+            "wait_for_builds" => {
+                self.handle_wait_for_builds(tool.arguments(arguments)?, progress_token)
+                    .await
+            }
+            "compare_commits" => {
+                self.handle_compare_commits(tool.arguments(arguments)?)
+                    .await
+            }
+            "summarize_failure" => {
+                self.handle_summarize_failure(tool.arguments(arguments)?, progress_token)
+                    .await
+            }
+            name => Err(format!("Tool {} has no handler", name).into()),
         }
     }
 
-    async fn handle_get_build_status(&self, arguments: Value) -> Result<Value, String> {
-        let commit_id = arguments
-            .get("commit_id")
-            .and_then(|v| v.as_str())
-            .ok_or("Missing required argument: commit_id")?;
+    /// Keep a fetched commit status so its builds can be listed and read as resources
+    fn remember(&self, status: &GarnixResponse) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|known| known.summary.git_commit != status.summary.git_commit);
+        recent.push_back(status.clone());
+        while recent.len() > RECENT_COMMITS {
+            recent.pop_front();
+        }
+    }
 
-        let token = arguments
-            .get("token")
-            .and_then(|v| v.as_str())
-            .ok_or("Missing required argument: token")?;
+    /// Find a build of a recently fetched commit
+    fn recent_build(&self, build_id: &str) -> Option<Build> {
+        self.recent
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .flat_map(|status| &status.builds)
+            .find(|build| build.id == build_id)
+            .cloned()
+    }
 
-        match self.client.fetch_build_status(token, commit_id).await {
-            Ok(status) => {
-                let status_json = serde_json::to_value(&status)
-                    .map_err(|e| format!("Failed to serialize status: {}", e))?;
-                Ok(json!({
-                    "content": [{
-                        "type": "text",
-                        "text": format!("Build Status for commit {}:\n\n{}", commit_id,
-                            serde_json::to_string_pretty(&status_json).unwrap_or_default())
-                    }]
-                }))
+    /// Resources for the recently fetched commits and their builds
+    fn list_resources(&self) -> Vec<Value> {
+        let mut resources = Vec::new();
+        for status in self.recent.lock().unwrap().iter().rev() {
+            let summary = &status.summary;
+            resources.push(json!({
+                "uri": ResourceUri::Commit(summary.git_commit.clone()).to_string(),
+                "name": format!(
+                    "{}/{}@{}",
+                    summary.repo_owner,
+                    summary.repo_name,
+                    &summary.git_commit[..summary.git_commit.len().min(8)]
+                ),
+                "description": format!("CI status of commit {} on {}", summary.git_commit, summary.branch),
+                "mimeType": "text/markdown"
+            }));
+            for build in &status.builds {
+                let name = format!(
+                    "{} ({})",
+                    build.package,
+                    build.system.as_deref().unwrap_or("unknown")
+                );
+                resources.push(json!({
+                    "uri": ResourceUri::Build(build.id.clone()).to_string(),
+                    "name": name,
+                    "description": format!("{} build of {}", build.status, summary.git_commit),
+                    "mimeType": "text/markdown"
+                }));
+                if build.is_failed() {
+                    resources.push(json!({
+                        "uri": ResourceUri::BuildLogs(build.id.clone()).to_string(),
+                        "name": format!("{} logs", name),
+                        "description": format!("Logs of the failed build {}", build.id),
+                        "mimeType": "text/markdown"
+                    }));
+                }
             }
-            Err(e) => Err(format!("Error getting build status: {}", e)),
         }
+        resources
     }
 
-    async fn handle_get_build_logs(&self, arguments: Value) -> Result<Value, String> {
-        let commit_id = arguments
-            .get("commit_id")
-            .and_then(|v| v.as_str())
-            .ok_or("Missing required argument: commit_id")?;
-
-        let token = arguments
-            .get("token")
+    /// Read a `garnix://` resource as markdown and JSON
+    async fn read_resource(&self, params: Option<Value>) -> Result<Value, McpError> {
+        let uri = params
+            .as_ref()
+            .and_then(|p| p.get("uri"))
             .and_then(|v| v.as_str())
-            .ok_or("Missing required argument: token")?;
-
-        match self.client.fetch_build_status(token, commit_id).await {
-            Ok(status) => {
-                let logs_text = status.builds
-                    .iter()
-                    .map(|build| {
-                        format!(
-                            "**Build {} ({})**:\nStatus: {}\nSystem: {}\nPackage: {}\nDuration: {} to {}",
-                            build.id,
-                            build.system.as_ref().unwrap_or(&"unknown".to_string()),
-                            build.status_with_emoji(),
-                            build.system.as_ref().unwrap_or(&"unknown".to_string()),
-                            build.package,
-                            build.start_time,
-                            build.end_time
-                        )
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n\n");
+            .ok_or_else(|| McpError::new(INVALID_PARAMS, "Missing required parameter: uri"))?;
+        let resource = ResourceUri::parse(uri).ok_or_else(|| {
+            McpError::new(INVALID_PARAMS, format!("Unsupported resource URI: {}", uri))
+        })?;
 
-                Ok(json!({
-                    "content": [{
-                        "type": "text",
-                        "text": format!("Build Information for commit {}:\n\n{}", commit_id, logs_text)
-                    }]
-                }))
+        let (markdown, json) = match &resource {
+            ResourceUri::Commit(sha) => {
+                let status = self
+                    .server_client()?
+                    .fetch_build_status(sha)
+                    .await
+                    .map_err(|e| McpError::garnix("Error reading commit", &e))?;
+                self.remember(&status);
+                let json = build_status_json(&status)
+                    .map_err(|e| McpError::from(format!("Failed to serialize status: {}", e)))?;
+                (
+                    format!(
+                        "{}\n{}",
+                        format_build_summary(&status),
+                        format_build_details(&status.builds)
+                    ),
+                    json,
+                )
             }
-            Err(e) => Err(format!("Error getting build logs: {}", e)),
-        }
+            ResourceUri::Build(build_id) => {
+                let build = self.recent_build(build_id).ok_or_else(|| {
+                    McpError::resource_not_found(
+                        uri,
+                        "Unknown build; read the garnix://commit/{sha} resource of its commit first",
+                    )
+                })?;
+                let mut json = serde_json::to_value(&build)
+                    .map_err(|e| McpError::from(format!("Failed to serialize build: {}", e)))?;
+                json["duration"] = json!(build.duration().map(format_duration));
+                (format_build_details(std::slice::from_ref(&build)), json)
+            }
+            ResourceUri::BuildLogs(build_id) => {
+                let logs = self
+                    .server_client()?
+                    .fetch_build_logs(build_id)
+                    .await
+                    .map_err(|e| McpError::garnix("Error reading build logs", &e))?;
+                let options = ExcerptOptions::new();
+                let json = serde_json::to_value(&logs)
+                    .map_err(|e| McpError::from(format!("Failed to serialize logs: {}", e)))?;
+                (
+                    format!(
+                        "# Logs for build {} (finished: {})\n\n```\n{}\n```",
+                        build_id,
+                        logs.finished,
+                        excerpt::excerpt(&logs, &options).render(&options)
+                    ),
+                    json,
+                )
+            }
+        };
+
+        Ok(json!({
+            "contents": [
+                { "uri": uri, "mimeType": "text/markdown", "text": markdown },
+                {
+                    "uri": uri,
+                    "mimeType": "application/json",
+                    "text": serde_json::to_string_pretty(&json).unwrap_or_default()
+                }
+            ]
+        }))
     }
 
-    async fn handle_check_commit_ready(&self, arguments: Value) -> Result<Value, String> {
-        let commit_id = arguments
-            .get("commit_id")
+    /// Fill in a triage prompt with the current CI data
+    async fn get_prompt(&self, params: Option<Value>) -> Result<Value, McpError> {
+        let params = params.unwrap_or(json!({}));
+        let name = params
+            .get("name")
             .and_then(|v| v.as_str())
-            .ok_or("Missing required argument: commit_id")?;
+            .ok_or_else(|| McpError::new(INVALID_PARAMS, "Missing required parameter: name"))?;
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+        let argument = |name: &str| arguments.get(name).and_then(|v| v.as_str());
+        let required = |name: &str| {
+            argument(name).ok_or_else(|| {
+                McpError::new(
+                    INVALID_PARAMS,
+                    format!("Missing required argument: {}", name),
+                )
+            })
+        };
 
-        let token = arguments
-            .get("token")
-            .and_then(|v| v.as_str())
-            .ok_or("Missing required argument: token")?;
+        let (description, messages) = match name {
+            "triage_failed_builds" => {
+                let commit_id = required("commit_id")?;
+                let filter = BuildFilter {
+                    package: argument("package").map(String::from),
+                    system: argument("system").map(String::from),
+                    failed_only: true,
+                };
+                (
+                    format!("Triage the failed builds of commit {}", commit_id),
+                    self.triage_messages(commit_id, &filter).await?,
+                )
+            }
+            "deploy_readiness" => {
+                let commit_id = required("commit_id")?;
+                (
+                    format!("Decide whether commit {} is safe to deploy", commit_id),
+                    self.deploy_messages(commit_id).await?,
+                )
+            }
+            "branch_health" => {
+                let branch = required("branch")?;
+                let count = match argument("count") {
+                    Some(count) => count
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|count| (1..=MAX_HEALTH_COMMITS).contains(count))
+                        .ok_or_else(|| {
+                            McpError::new(
+                                INVALID_PARAMS,
+                                format!(
+                                    "Invalid count '{}', expected 1 to {}",
+                                    count, MAX_HEALTH_COMMITS
+                                ),
+                            )
+                        })?,
+                    None => DEFAULT_HEALTH_COMMITS,
+                };
+                // The commits come from the server's working directory, which
+                // has to be a checkout of the repository built by Garnix
+                let origin = git::origin_repo().await.ok_or_else(|| {
+                    McpError::new(
+                        INVALID_PARAMS,
+                        "branch_health needs the server to run inside a git checkout \
+                         with an origin remote",
+                    )
+                })?;
+                let commits = git::recent_commits(branch, count)
+                    .await
+                    .map_err(|e| McpError::new(INVALID_PARAMS, e.to_string()))?;
+                (
+                    format!("Summarise the CI health of branch {}", branch),
+                    self.health_messages(branch, &commits, &origin).await?,
+                )
+            }
+            _ => {
+                return Err(McpError::new(
+                    INVALID_PARAMS,
+                    format!("Unknown prompt: {}", name),
+                ))
+            }
+        };
 
-        match self.client.fetch_build_status(token, commit_id).await {
-            Ok(status) => {
-                let total_builds = status.builds.len() as u32;
-                let success_rate = status.success_rate();
-                let is_ready = success_rate == 100.0 && total_builds > 0;
+        Ok(json!({ "description": description, "messages": messages }))
+    }
 
-                let status_text = if is_ready {
-                    format!(
-                        "[OK] Commit {} is ready for deployment! All {} builds passed.",
-                        commit_id, total_builds
-                    )
-                } else if total_builds == 0 {
-                    format!("[PENDING] Commit {} has no builds yet.", commit_id)
-                } else {
-                    format!(
-                        "[FAIL] Commit {} is NOT ready. {}/{} builds passed ({:.1}% success rate).",
-                        commit_id, status.summary.succeeded, total_builds, success_rate
-                    )
-                };
+    /// Status of a commit, remembered for resource reads
+    async fn prompt_status(&self, commit_id: &str) -> Result<GarnixResponse, McpError> {
+        let status = self
+            .server_client()?
+            .fetch_build_status(commit_id)
+            .await
+            .map_err(|e| McpError::garnix("Error getting build status", &e))?;
+        self.remember(&status);
+        Ok(status)
+    }
 
-                Ok(json!({
-                    "content": [{
-                        "type": "text",
-                        "text": status_text
-                    }]
-                }))
-            }
-            Err(e) => Err(format!("Error checking commit readiness: {}", e)),
+    async fn triage_messages(
+        &self,
+        commit_id: &str,
+        filter: &BuildFilter,
+    ) -> Result<Vec<Value>, McpError> {
+        let status = self.prompt_status(commit_id).await?;
+        let (reports, logs) = self
+            .server_client()?
+            .diagnose_status(&status, filter)
+            .await
+            .map_err(|e| McpError::garnix("Error getting build logs", &e))?;
+
+        let mut messages = vec![
+            prompt_text(format!(
+                "Triage the failed CI builds of commit {}. The build status, an excerpt of the \
+                 log of every failed build and an automatic diagnosis follow. For each failure:\n\
+                 1. State the root cause, quoting the offending log lines.\n\
+                 2. Tell apart failures caused by the change from infrastructure problems \
+                 (timeouts, out of memory, network) that a rerun may fix.\n\
+                 3. Note failures that only follow from another failed build.\n\
+                 4. Propose a concrete fix or next step.\n\
+                 Finish with a prioritised list of actions.",
+                commit_id
+            )),
+            commit_resource(&status),
+        ];
+        let options = ExcerptOptions::new();
+        for build_logs in &logs {
+            let (build, logs) = (&build_logs.build, &build_logs.logs);
+            messages.push(prompt_resource(
+                ResourceUri::BuildLogs(build.id.clone()),
+                format!(
+                    "# Logs of {} ({})\n\n```\n{}\n```",
+                    build.package,
+                    build.system.as_deref().unwrap_or("unknown"),
+                    excerpt::excerpt(logs, &options).render(&options)
+                ),
+            ));
         }
+        messages.push(prompt_text(format_failure_reports(commit_id, &reports)));
+        Ok(messages)
     }
-}
 
-/// JSON-RPC 2.0 request structure for MCP
-#[derive(Debug, Deserialize)]
-struct McpRequest {
-    jsonrpc: String,
-    id: Option<Value>,
-    method: String,
-    params: Option<Value>,
-}
+    async fn deploy_messages(&self, commit_id: &str) -> Result<Vec<Value>, McpError> {
+        let status = self.prompt_status(commit_id).await?;
+        let (verdict, _) = readiness(commit_id, &status);
+        Ok(vec![
+            prompt_text(format!(
+                "Decide whether commit {} is safe to deploy, based on its CI status below. \
+                 Answer yes or no first. Only answer yes if every build and run succeeded; \
+                 pending builds mean not yet. For a no, list the blocking builds and runs \
+                 and what has to happen before deploying.",
+                commit_id
+            )),
+            commit_resource(&status),
+            prompt_text(format!("Automatic readiness check: {}", verdict)),
+        ])
+    }
 
-/// JSON-RPC 2.0 response structure for MCP
-#[derive(Debug, Serialize)]
-struct McpResponse {
-    jsonrpc: String,
-    id: Option<Value>,
-    result: Option<Value>,
-    error: Option<McpError>,
-}
+    async fn health_messages(
+        &self,
+        branch: &str,
+        commits: &[String],
+        origin: &RepoSlug,
+    ) -> Result<Vec<Value>, McpError> {
+        let client = self.server_client()?;
+        let statuses: Vec<_> = futures::stream::iter(commits)
+            .map(|commit| client.fetch_build_status(commit))
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .collect()
+            .await;
+        if let Some(foreign) = statuses
+            .iter()
+            .flatten()
+            .map(|status| &status.summary)
+            .find(|summary| !origin.matches(&summary.repo_owner, &summary.repo_name))
+        {
+            return Err(McpError::new(
+                INVALID_PARAMS,
+                format!(
+                    "The server runs in a checkout of {} but commit {} belongs to {}/{}; \
+                     branch_health only works when the server runs inside the repository",
+                    origin, foreign.git_commit, foreign.repo_owner, foreign.repo_name
+                ),
+            ));
+        }
 
-/// JSON-RPC 2.0 error structure
-#[derive(Debug, Serialize)]
-struct McpError {
-    code: i32,
-    message: String,
-    data: Option<Value>,
-}
+        let mut table = format!(
+            "# CI health of {} (last {} commits, newest first)\n\n\
+             | Commit | Result | Succeeded | Failed | Pending | Failed builds |\n\
+             |--------|--------|-----------|--------|---------|---------------|\n",
+            branch,
+            commits.len()
+        );
+        for (commit, status) in commits.iter().zip(statuses) {
+            let short = &commit[..commit.len().min(8)];
+            match status {
+                Ok(status) => {
+                    let summary = &status.summary;
+                    let failed: Vec<String> = status
+                        .failed_builds()
+                        .iter()
+                        .map(|build| build.package.clone())
+                        .chain(status.failed_runs().iter().map(|run| run.label()))
+                        .collect();
+                    table.push_str(&format!(
+                        "| {} | {} | {} | {} | {} | {} |\n",
+                        short,
+                        commit_result(&status),
+                        summary.succeeded,
+                        summary.failed,
+                        summary.pending,
+                        failed.join(", ")
+                    ));
+                    self.remember(&status);
+                }
+                Err(GarnixError::NotFound(_)) => {
+                    table.push_str(&format!("| {} | no CI run | - | - | - | |\n", short));
+                }
+                Err(e) => return Err(McpError::garnix("Error getting build status", &e)),
+            }
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        Ok(vec![
+            prompt_text(format!(
+                "Summarise the CI health of branch {}. The results of its most recent commits \
+                 follow. Say whether the branch is currently green, when it last was, which \
+                 packages or systems fail repeatedly, and whether failures look flaky (passing \
+                 and failing across commits) or persistent. Recommend what to look at first.",
+                branch
+            )),
+            prompt_text(table),
+        ])
+    }
 
-    #[test]
-    fn test_server_creation() {
-        let server = GarnixMcpServer::new();
-        // Just test that it can be created
-        drop(server);
+    /// Whether the negotiated protocol version supports structured tool output
+    fn structured_output(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            SessionState::Initializing(version) | SessionState::Ready(version) => {
+                version.supports_structured_output()
+            }
+            SessionState::AwaitingInitialize => false,
+        }
     }
 
-    #[test]
-    fn test_server_with_client() {
-        let client = GarnixClient::new();
-        let server = GarnixMcpServer::with_client(client);
-        drop(server);
+    /// Build a tool result, adding `structuredContent` when the session supports it
+    fn tool_result(&self, text: String, structured: impl Serialize) -> Result<Value, McpError> {
+        let mut result = json!({
+            "content": [{
+                "type": "text",
+                "text": text
+            }]
+        });
+        if self.structured_output() {
+            result["structuredContent"] = serde_json::to_value(structured)
+                .map_err(|e| McpError::from(format!("Failed to serialize result: {}", e)))?;
+        }
+        Ok(result)
     }
 
-    #[test]
-    fn test_default_implementation() {
-        let server1 = GarnixMcpServer::new();
-        let server2 = GarnixMcpServer::default();
+    /// Client authenticated with the `token` argument, or the configured provider,
+    /// as allowed by the token policy
+    fn client_for(&self, token: Option<&str>) -> Result<GarnixClient, McpError> {
+        match (self.token_policy, token) {
+            (TokenPolicy::Disallowed, Some(_)) => Err(McpError::new(
+                INVALID_PARAMS,
+                "The token argument is not allowed; this server uses its own Garnix token",
+            )),
+            (TokenPolicy::Required, None) => Err(McpError::new(
+                INVALID_PARAMS,
+                "Missing required argument: token",
+            )),
+            (_, Some(token)) => Ok(self.client.clone().with_token(token)),
+            (_, None) => Ok(self.client.clone()),
+        }
+    }
 
-        // Both should be created successfully
-        drop(server1);
-        drop(server2);
+    /// Client with the server-side token, for resources and prompts, which
+    /// cannot pass a `token` argument
+    ///
+    /// Unavailable under [`TokenPolicy::Required`], which only allows tokens
+    /// passed by the caller.
+    fn server_client(&self) -> Result<GarnixClient, McpError> {
+        match self.token_policy {
+            TokenPolicy::Required => Err(McpError::new(
+                INVALID_REQUEST,
+                "Resources and prompts use the server's Garnix token, which the required \
+                 token policy does not allow; call the tools with a token instead",
+            )),
+            TokenPolicy::Optional | TokenPolicy::Disallowed => Ok(self.client.clone()),
+        }
     }
 
-    #[test]
-    fn test_request_deserialization() {
-        let json = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
-        let request: Result<McpRequest, _> = serde_json::from_str(json);
-        assert!(request.is_ok());
+    async fn handle_get_build_status(
+        &self,
+        arguments: BuildStatusArguments,
+    ) -> Result<Value, McpError> {
+        let commit_id = arguments.commit_id.as_str();
+
+        match self
+            .client_for(arguments.token.as_deref())?
+            .fetch_build_status(commit_id)
+            .await
+        {
+            Ok(status) => {
+                self.remember(&status);
+                let status_json = build_status_json(&status)
+                    .map_err(|e| McpError::from(format!("Failed to serialize status: {}", e)))?;
+                let text = format!(
+                    "Build Status for commit {}:\n\n{}",
+                    commit_id,
+                    serde_json::to_string_pretty(&status_json).unwrap_or_default()
+                );
+                self.tool_result(text, status_json)
+            }
+            Err(e) => Err(McpError::garnix("Error getting build status", &e)),
+        }
+    }
+
+    async fn handle_get_build_logs(
+        &self,
+        arguments: BuildLogsArguments,
+    ) -> Result<Value, McpError> {
+        let commit_id = arguments.commit_id.as_str();
+        let failed_only = arguments
+            .failed_only
+            .unwrap_or(arguments.package.is_none() && arguments.system.is_none());
+        let filter = BuildFilter {
+            package: arguments.package.clone(),
+            system: arguments.system.clone(),
+            failed_only,
+        };
+        let mode = arguments.mode.unwrap_or(LogMode::Excerpt);
+        let max_lines = arguments.max_lines.unwrap_or(200).max(1) as usize;
+        let options = ExcerptOptions::new().context(arguments.context.unwrap_or(10) as usize);
+
+        match self
+            .client_for(arguments.token.as_deref())?
+            .fetch_logs_for_commit(commit_id, &filter)
+            .await
+        {
+            Ok(results) => {
+                let output = BuildLogsOutput {
+                    commit_id: commit_id.to_string(),
+                    builds: results
+                        .into_iter()
+                        .map(|build_logs| BuildLogOutput {
+                            log: log_text(&build_logs.logs, mode, max_lines, &options),
+                            finished: build_logs.logs.finished,
+                            build: build_logs.build,
+                        })
+                        .collect(),
+                };
+
+                let text = if output.builds.is_empty() && failed_only {
+                    format!("No failed builds found for commit {}", commit_id)
+                } else if output.builds.is_empty() {
+                    format!("No builds of commit {} match the given filters", commit_id)
+                } else {
+                    let logs_text = output
+                        .builds
+                        .iter()
+                        .map(|BuildLogOutput { build, log, .. }| {
+                            format!(
+                                "## {} ({}): {}\nBuild ID: {}\nDuration: {}\n\n```\n{}\n```",
+                                build.package,
+                                build.system.as_deref().unwrap_or("unknown"),
+                                build.status_with_emoji(),
+                                build.id,
+                                build
+                                    .duration()
+                                    .map(format_duration)
+                                    .unwrap_or_else(|| "running".to_string()),
+                                log
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n\n");
+                    format!("Build logs for commit {}:\n\n{}", commit_id, logs_text)
+                };
+                self.tool_result(text, output)
+            }
+            Err(e) => Err(McpError::garnix("Error getting build logs", &e)),
+        }
+    }
+
+    async fn handle_check_commit_ready(
+        &self,
+        arguments: ReadinessArguments,
+    ) -> Result<Value, McpError> {
+        let commit_id = arguments.commit_id.as_str();
+
+        match self
+            .client_for(arguments.token.as_deref())?
+            .fetch_build_status(commit_id)
+            .await
+        {
+            Ok(status) => {
+                self.remember(&status);
+                let (status_text, output) = readiness(commit_id, &status);
+                self.tool_result(status_text, output)
+            }
+            Err(e) => Err(McpError::garnix("Error checking commit readiness", &e)),
+        }
+    }
+
+    async fn handle_diagnose_build_failures(
+        &self,
+        arguments: DiagnoseArguments,
+    ) -> Result<Value, McpError> {
+        let commit_id = arguments.commit_id.as_str();
+        let filter = BuildFilter {
+            package: arguments.package.clone(),
+            system: arguments.system.clone(),
+            failed_only: true,
+        };
+
+        match self
+            .client_for(arguments.token.as_deref())?
+            .diagnose_commit(commit_id, &filter)
+            .await
+        {
+            Ok(failures) => self.tool_result(
+                format_failure_reports(commit_id, &failures),
+                DiagnosisOutput {
+                    commit_id: commit_id.to_string(),
+                    failures,
+                },
+            ),
+            Err(e) => Err(McpError::garnix("Error diagnosing build failures", &e)),
+        }
+    }
+
+    async fn handle_compare_commits(&self, arguments: CompareArguments) -> Result<Value, McpError> {
+        match self
+            .client_for(arguments.token.as_deref())?
+            .compare_commits(&arguments.base_commit, &arguments.head_commit)
+            .await
+        {
+            Ok(comparison) => self.tool_result(format_comparison(&comparison), comparison),
+            Err(e) => Err(McpError::garnix("Error comparing commits", &e)),
+        }
+    }
+
+    async fn handle_wait_for_builds(
+        &self,
+        arguments: WaitArguments,
+        progress_token: Option<Value>,
+    ) -> Result<Value, McpError> {
+        let commit_id = arguments.commit_id.as_str();
+        let defaults = WaitOptions::default();
+        let interval = match arguments.interval_secs {
+            Some(secs) => Duration::from_secs(secs.max(1)),
+            None => defaults.interval,
+        };
+        let timeout = match arguments.timeout_secs {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => defaults.timeout,
+        };
+        let options = WaitOptions { interval, timeout };
+
+        // Progress counts the builds that finished while waiting
+        let mut finished = 0;
+        let outcome = self
+            .client_for(arguments.token.as_deref())?
+            .wait_for_completion(commit_id, &options, |transition| {
+                let Some(token) = &progress_token else {
+                    return;
+                };
+                if BuildStatus::from(transition.to.as_str()) == BuildStatus::Pending {
+                    return;
+                }
+                finished += 1;
+                self.notify(
+                    "notifications/progress",
+                    json!({
+                        "progressToken": token,
+                        "progress": finished,
+                        "message": transition.to_string()
+                    }),
+                );
+            })
+            .await
+            .map_err(|e| McpError::garnix("Error waiting for builds", &e))?;
+
+        let status = outcome.response();
+        self.remember(status);
+        let status_json = build_status_json(status)
+            .map_err(|e| McpError::from(format!("Failed to serialize status: {}", e)))?;
+        let headline = if outcome.is_timed_out() && status.builds.is_empty() {
+            format!(
+                "Timed out waiting for commit {}; it has no builds yet.",
+                commit_id
+            )
+        } else if outcome.is_timed_out() {
+            format!(
+                "Timed out waiting for commit {}; {} build(s) still pending.",
+                commit_id, status.summary.pending
+            )
+        } else {
+            format!("All builds of commit {} finished.", commit_id)
+        };
+        let text = format!("{}\n\n{}", headline, format_build_summary(status));
+        self.tool_result(text, status_json)
+    }
+
+    async fn handle_summarize_failure(
+        &self,
+        arguments: SummarizeArguments,
+        progress_token: Option<Value>,
+    ) -> Result<Value, McpError> {
+        let build_id = arguments.build_id.as_str();
+        let logs = self
+            .client_for(arguments.token.as_deref())?
+            .fetch_build_logs(build_id)
+            .await
+            .map_err(|e| McpError::garnix("Error fetching build logs", &e))?;
+        let lines: Vec<&str> = logs
+            .logs
+            .iter()
+            .flat_map(|entry| entry.log_message.lines())
+            .collect();
+        let diagnosis = analysis::diagnose_lines(&lines);
+
+        let sampled = if self.client_supports_sampling() {
+            let summary = self.summarize_with_sampling(build_id, &lines, progress_token.as_ref());
+            match tokio::time::timeout(sampling::SAMPLING_DEADLINE, summary).await {
+                Ok(Ok(sampled)) => Some(sampled),
+                Ok(Err(e)) => {
+                    tracing::warn!("Sampling failed, falling back to an excerpt: {}", e.message);
+                    None
+                }
+                Err(_) => {
+                    tracing::warn!(
+                        "Sampling took longer than {}s, falling back to an excerpt",
+                        sampling::SAMPLING_DEADLINE.as_secs()
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut text = format!(
+            "# Failure summary for build {}\n\n- **Cause:** {}\n",
+            build_id, diagnosis.kind
+        );
+        let output = match sampled {
+            Some((summary, chunks)) => {
+                text.push_str(&format!("\n{}\n", summary));
+                SummaryOutput {
+                    build_id: build_id.to_string(),
+                    method: SummaryMethod::Sampling,
+                    chunks,
+                    summary,
+                    diagnosis,
+                }
+            }
+            None => {
+                let options = ExcerptOptions::default();
+                let summary = excerpt::extract(&lines, &options).render(&options);
+                if let Some(line) = diagnosis.line {
+                    text.push_str(&format!("- **Line {}:** {}\n", line, diagnosis.message));
+                }
+                text.push_str(&format!(
+                    "\nNo summary from the client's model is available; excerpt of the log:\n\n```\n{}\n```\n",
+                    summary
+                ));
+                SummaryOutput {
+                    build_id: build_id.to_string(),
+                    method: SummaryMethod::Excerpt,
+                    chunks: 0,
+                    summary,
+                    diagnosis,
+                }
+            }
+        };
+        self.tool_result(text, output)
+    }
+
+    /// Summarize a log through the client's model, returning the summary and
+    /// the number of chunks it was made of
+    async fn summarize_with_sampling(
+        &self,
+        build_id: &str,
+        lines: &[&str],
+        progress_token: Option<&Value>,
+    ) -> Result<(String, usize), McpError> {
+        let chunks = sampling::log_chunks(lines);
+        if chunks.is_empty() {
+            return Err("The log is empty".into());
+        }
+
+        let mut summaries = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.iter().enumerate() {
+            let params = sampling::chunk_request(build_id, chunk, index, chunks.len());
+            summaries.push(self.sample(params).await?);
+            if let Some(token) = progress_token {
+                self.notify(
+                    "notifications/progress",
+                    json!({
+                        "progressToken": token,
+                        "progress": index + 1,
+                        "total": chunks.len(),
+                        "message": format!("Summarized part {} of {} of the log", index + 1, chunks.len())
+                    }),
+                );
+            }
+        }
+
+        let summary = match summaries.len() {
+            1 => summaries.remove(0),
+            _ => {
+                self.sample(sampling::combine_request(build_id, &summaries))
+                    .await?
+            }
+        };
+        Ok((summary, chunks.len()))
+    }
+
+    /// Whether the client advertised the `sampling` capability in `initialize`
+    fn client_supports_sampling(&self) -> bool {
+        self.client_capabilities
+            .lock()
+            .unwrap()
+            .get("sampling")
+            .is_some()
+    }
+
+    /// Run a `sampling/createMessage` request, returning the generated text
+    async fn sample(&self, params: Value) -> Result<String, McpError> {
+        let result = self
+            .request_client("sampling/createMessage", params, sampling::SAMPLING_TIMEOUT)
+            .await?;
+        sampling::sampled_text(&result).ok_or_else(|| "Sampling result has no text content".into())
+    }
+
+    /// Send a request to the client and wait for its response
+    ///
+    /// Fails when nobody receives the request, e.g. over HTTP without an open
+    /// event stream, or when the client does not answer within the timeout.
+    async fn request_client(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, McpError> {
+        let id = format!(
+            "garnix-{}",
+            self.next_request_id.fetch_add(1, Ordering::Relaxed)
+        );
+        let (sender, receiver) = oneshot::channel();
+        // Ids come from a counter, so they are never in flight twice
+        let pending = PendingRequest::new(&self.outgoing, json!(id).to_string(), sender)
+            .ok_or_else(|| McpError::from(format!("Request id {} is already in flight", id)))?;
+
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if !self.send_request(request.to_string()) {
+            return Err(format!("No client is listening for {} requests", method).into());
+        }
+        let response = tokio::time::timeout(timeout, receiver).await;
+        drop(pending);
+        match response {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(format!("The {} request was dropped", method).into()),
+            Err(_) => Err(format!(
+                "The client did not answer {} within {}s",
+                method,
+                timeout.as_secs()
+            )
+            .into()),
+        }
+    }
+
+    /// Send a notification to the client outside of any response
+    fn notify(&self, method: &str, params: Value) {
+        let notification = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        // Nobody listening is not an error; the notification is simply dropped
+        self.send(notification.to_string());
+    }
+
+    /// Send a message to the client, on the stream answering the request being
+    /// handled if there is one, returning whether anybody received it
+    fn send(&self, message: String) -> bool {
+        match RELATED_MESSAGES.try_with(Clone::clone) {
+            Ok(related) => related.send(message).is_ok(),
+            Err(_) => self.messages.send(message).is_ok(),
+        }
+    }
+
+    /// Send a request to the client, on the stream answering the request being
+    /// handled if there is one, or else to a single request subscriber
+    fn send_request(&self, request: String) -> bool {
+        if let Ok(related) = RELATED_MESSAGES.try_with(Clone::clone) {
+            return related.send(request).is_ok();
+        }
+        let mut subscribers = self.request_subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| !subscriber.is_closed());
+        subscribers
+            .last()
+            .is_some_and(|subscriber| subscriber.send(request).is_ok())
+    }
+}
+
+/// A `garnix://` resource URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ResourceUri {
+    /// `garnix://commit/{sha}`
+    Commit(String),
+    /// `garnix://build/{id}`
+    Build(String),
+    /// `garnix://build/{id}/logs`
+    BuildLogs(String),
+}
+
+impl ResourceUri {
+    fn parse(uri: &str) -> Option<Self> {
+        let path = uri.strip_prefix("garnix://")?;
+        match path.split('/').collect::<Vec<_>>()[..] {
+            ["commit", sha] if !sha.is_empty() => Some(Self::Commit(sha.to_string())),
+            ["build", id] if !id.is_empty() => Some(Self::Build(id.to_string())),
+            ["build", id, "logs"] if !id.is_empty() => Some(Self::BuildLogs(id.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ResourceUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Commit(sha) => write!(f, "garnix://commit/{}", sha),
+            Self::Build(id) => write!(f, "garnix://build/{}", id),
+            Self::BuildLogs(id) => write!(f, "garnix://build/{}/logs", id),
+        }
+    }
+}
+
+/// Structured result of the `get_build_logs` tool
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+struct BuildLogsOutput {
+    /// The commit whose builds were selected
+    commit_id: String,
+    /// Logs of the selected builds
+    builds: Vec<BuildLogOutput>,
+}
+
+/// Log of a single build returned by the `get_build_logs` tool
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+struct BuildLogOutput {
+    /// The build
+    build: Build,
+    /// Whether the build has finished
+    finished: bool,
+    /// The log, reduced according to the requested mode and line limit
+    log: String,
+}
+
+/// Structured result of the `check_commit_ready` tool
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+struct ReadinessOutput {
+    /// The checked commit
+    commit_id: String,
+    /// Whether all builds passed and no run failed
+    ready: bool,
+    /// Number of builds
+    total_builds: u32,
+    /// Number of successful builds
+    succeeded: u32,
+    /// Percentage of successful builds
+    success_rate: f64,
+    /// Labels of failed evaluation runs
+    failed_runs: Vec<String>,
+}
+
+/// Write a message as a line to stdout
+async fn write_line(stdout: &mut tokio::io::Stdout, message: &str) -> GarnixResult<()> {
+    stdout
+        .write_all(format!("{}\n", message).as_bytes())
+        .await
+        .map_err(|e| GarnixError::NetworkError(format!("Failed to write response: {}", e)))?;
+    stdout
+        .flush()
+        .await
+        .map_err(|e| GarnixError::NetworkError(format!("Failed to flush stdout: {}", e)))
+}
+
+/// Number of items returned per page by `tools/list`, `resources/list` and `prompts/list`
+const PAGE_SIZE: usize = 50;
+
+/// Select the page of a list starting at the request's `cursor`
+///
+/// Cursors are opaque to clients; they encode the offset of the page.
+fn paginate(
+    items: Vec<Value>,
+    params: Option<&Value>,
+) -> Result<(Vec<Value>, Option<String>), McpError> {
+    paginate_by(items, params, PAGE_SIZE)
+}
+
+fn paginate_by(
+    items: Vec<Value>,
+    params: Option<&Value>,
+    page_size: usize,
+) -> Result<(Vec<Value>, Option<String>), McpError> {
+    let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    let start = match params.and_then(|p| p.get("cursor")).and_then(Value::as_str) {
+        Some(cursor) => engine
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .and_then(|offset| offset.strip_prefix("offset:")?.parse::<usize>().ok())
+            .filter(|offset| *offset <= items.len())
+            .ok_or_else(|| McpError::new(INVALID_PARAMS, format!("Invalid cursor: {}", cursor)))?,
+        None => 0,
+    };
+    let end = items.len().min(start + page_size);
+    let next_cursor = (end < items.len()).then(|| engine.encode(format!("offset:{}", end)));
+    let page = items.into_iter().skip(start).take(end - start).collect();
+    Ok((page, next_cursor))
+}
+
+/// Add the `nextCursor` of a paginated list result
+fn with_next_cursor(mut result: Value, next_cursor: Option<String>) -> Value {
+    if let Some(cursor) = next_cursor {
+        result["nextCursor"] = json!(cursor);
+    }
+    result
+}
+
+/// Number of commits summarised by the `branch_health` prompt by default
+const DEFAULT_HEALTH_COMMITS: usize = 10;
+
+/// Maximum number of commits summarised by the `branch_health` prompt
+const MAX_HEALTH_COMMITS: usize = 50;
+
+/// Prompts offered by `prompts/list`
+fn prompt_list() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "triage_failed_builds",
+            "title": "Triage failed builds",
+            "description": "Find the root cause of the failed builds of a commit, with their log excerpts and an automatic diagnosis",
+            "arguments": [
+                { "name": "commit_id", "description": "The commit SHA to triage", "required": true },
+                { "name": "package", "description": "Only triage builds of this package", "required": false },
+                { "name": "system", "description": "Only triage builds for this system (e.g. x86_64-linux)", "required": false }
+            ]
+        }),
+        json!({
+            "name": "deploy_readiness",
+            "title": "Is this commit safe to deploy?",
+            "description": "Decide whether a commit can be deployed, based on its builds and runs",
+            "arguments": [
+                { "name": "commit_id", "description": "The commit SHA to deploy", "required": true }
+            ]
+        }),
+        json!({
+            "name": "branch_health",
+            "title": "Summarise CI health of a branch",
+            "description": "Summarise the CI results of the most recent commits of a branch. Only works when the server runs inside a git checkout of the repository",
+            "arguments": [
+                { "name": "branch", "description": "Branch or other git revision to follow", "required": true },
+                {
+                    "name": "count",
+                    "description": format!("Number of commits to summarise (default {}, at most {})", DEFAULT_HEALTH_COMMITS, MAX_HEALTH_COMMITS),
+                    "required": false
+                }
+            ]
+        }),
+    ]
+}
+
+/// A user message of a prompt
+fn prompt_text(text: String) -> Value {
+    json!({ "role": "user", "content": { "type": "text", "text": text } })
+}
+
+/// A user message of a prompt embedding a markdown resource
+fn prompt_resource(uri: ResourceUri, markdown: String) -> Value {
+    json!({
+        "role": "user",
+        "content": {
+            "type": "resource",
+            "resource": { "uri": uri.to_string(), "mimeType": "text/markdown", "text": markdown }
+        }
+    })
+}
+
+/// A user message of a prompt embedding the status of a commit
+fn commit_resource(status: &GarnixResponse) -> Value {
+    prompt_resource(
+        ResourceUri::Commit(status.summary.git_commit.clone()),
+        format!(
+            "{}\n{}",
+            format_build_summary(status),
+            format_build_details(&status.builds)
+        ),
+    )
+}
+
+/// Overall result of the builds and runs of a commit
+fn commit_result(status: &GarnixResponse) -> &'static str {
+    let summary = &status.summary;
+    if summary.failed > 0 || !status.failed_runs().is_empty() {
+        "failed"
+    } else if summary.pending > 0 {
+        "pending"
+    } else if summary.cancelled > 0 {
+        "cancelled"
+    } else if status.builds.is_empty() {
+        "no builds"
+    } else {
+        "passed"
+    }
+}
+
+/// Decide whether a commit is ready for deployment, as text and structured output
+fn readiness(commit_id: &str, status: &GarnixResponse) -> (String, ReadinessOutput) {
+    let total_builds = status.builds.len() as u32;
+    let success_rate = status.success_rate();
+    let failed_runs = status.failed_runs();
+    let is_ready = success_rate == 100.0 && total_builds > 0 && failed_runs.is_empty();
+
+    let status_text = if !failed_runs.is_empty() {
+        format!(
+            "[FAIL] Commit {} is NOT ready. {} run(s) failed: {}",
+            commit_id,
+            failed_runs.len(),
+            failed_runs
+                .iter()
+                .map(|run| run.label())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    } else if is_ready {
+        format!(
+            "[OK] Commit {} is ready for deployment! All {} builds passed.",
+            commit_id, total_builds
+        )
+    } else if total_builds == 0 {
+        format!("[PENDING] Commit {} has no builds yet.", commit_id)
+    } else {
+        format!(
+            "[FAIL] Commit {} is NOT ready. {}/{} builds passed ({:.1}% success rate).",
+            commit_id, status.summary.succeeded, total_builds, success_rate
+        )
+    };
+
+    let output = ReadinessOutput {
+        commit_id: commit_id.to_string(),
+        ready: is_ready,
+        total_builds,
+        succeeded: status.summary.succeeded,
+        success_rate,
+        failed_runs: failed_runs.iter().map(|run| run.label()).collect(),
+    };
+    (status_text, output)
+}
+
+/// Structured result of the `diagnose_build_failures` tool
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+struct DiagnosisOutput {
+    /// The diagnosed commit
+    commit_id: String,
+    /// Diagnoses of the failed builds and runs
+    failures: Vec<FailureReport>,
+}
+
+/// Structured result of the `summarize_failure` tool
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+struct SummaryOutput {
+    /// The summarized build
+    build_id: String,
+    /// How the summary was produced
+    method: SummaryMethod,
+    /// Number of log chunks summarized by the client's model
+    chunks: usize,
+    /// Summary by the client's model, or an excerpt of the log around the first error
+    summary: String,
+    /// Heuristic diagnosis of the failure
+    diagnosis: Diagnosis,
+}
+
+/// How the `summarize_failure` tool produced its summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum SummaryMethod {
+    /// Written by the client's model through `sampling/createMessage`
+    Sampling,
+    /// Extracted heuristically, because sampling was unavailable or failed
+    Excerpt,
+}
+
+/// Sender waiting for the client's response to a request
+type ResponseSender = oneshot::Sender<Result<Value, McpError>>;
+
+/// A request in flight, keyed by its JSON-RPC id, forgotten when its waiter goes away
+///
+/// Tracks both requests sent to the client, waiting for their response, and
+/// requests from the client, which `notifications/cancelled` may abort.
+struct PendingRequest<'a, T> {
+    pending: &'a Mutex<HashMap<String, T>>,
+    key: String,
+}
+
+impl<'a, T> PendingRequest<'a, T> {
+    /// Register a request, or return `None` if its id is already in flight
+    fn new(pending: &'a Mutex<HashMap<String, T>>, key: String, value: T) -> Option<Self> {
+        let mut map = pending.lock().unwrap();
+        if map.contains_key(&key) {
+            return None;
+        }
+        map.insert(key.clone(), value);
+        Some(Self { pending, key })
+    }
+}
+
+impl<T> Drop for PendingRequest<'_, T> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Adjust the `token` argument of every tool in a `tools/list` result to the policy
+fn apply_token_policy(tools: &mut Value, policy: TokenPolicy) {
+    let Some(tools) = tools["tools"].as_array_mut() else {
+        return;
+    };
+    for tool in tools {
+        let schema = &mut tool["inputSchema"];
+        match policy {
+            TokenPolicy::Optional => {}
+            TokenPolicy::Required => {
+                schema["properties"]["token"]["description"] =
+                    json!("JWT token for Garnix API authentication");
+                if let Some(required) = schema["required"].as_array_mut() {
+                    required.push(json!("token"));
+                }
+            }
+            TokenPolicy::Disallowed => {
+                if let Some(properties) = schema["properties"].as_object_mut() {
+                    properties.remove("token");
+                }
+            }
+        }
+    }
+}
+
+/// How much of a build log the `get_build_logs` tool returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum LogMode {
+    /// The region around the first error
+    Excerpt,
+    /// The first lines
+    Head,
+    /// The last lines
+    Tail,
+}
+
+/// Reduce a build log to at most `max_lines` lines
+fn log_text(
+    logs: &LogResponse,
+    mode: LogMode,
+    max_lines: usize,
+    options: &ExcerptOptions,
+) -> String {
+    if logs.logs.is_empty() {
+        return "(no logs available)".to_string();
+    }
+
+    let lines: Vec<String> = match mode {
+        LogMode::Excerpt => excerpt::excerpt(logs, options)
+            .render(options)
+            .lines()
+            .map(String::from)
+            .collect(),
+        LogMode::Head | LogMode::Tail => logs
+            .logs
+            .iter()
+            .flat_map(|entry| entry.log_message.lines())
+            .map(String::from)
+            .collect(),
+    };
+    if lines.len() <= max_lines {
+        return lines.join("\n");
+    }
+
+    let omitted = format!("... {} lines omitted", lines.len() - max_lines);
+    match mode {
+        LogMode::Tail => format!(
+            "{}\n{}",
+            omitted,
+            lines[lines.len() - max_lines..].join("\n")
+        ),
+        LogMode::Head | LogMode::Excerpt => {
+            format!("{}\n{}", lines[..max_lines].join("\n"), omitted)
+        }
+    }
+}
+
+/// JSON-RPC 2.0 request structure for MCP
+#[derive(Debug, Deserialize)]
+struct McpRequest {
+    jsonrpc: String,
+    id: Option<Value>,
+    method: String,
+    params: Option<Value>,
+}
+
+/// JSON-RPC 2.0 response structure for MCP
+#[derive(Debug, Serialize)]
+struct McpResponse {
+    jsonrpc: String,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<McpError>,
+}
+
+impl McpResponse {
+    fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failure(id: Value, error: McpError) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Invalid JSON was received
+const PARSE_ERROR: i32 = -32700;
+/// The JSON sent is not a valid request object
+const INVALID_REQUEST: i32 = -32600;
+/// The method does not exist
+const METHOD_NOT_FOUND: i32 = -32601;
+/// The method parameters are invalid
+const INVALID_PARAMS: i32 = -32602;
+/// The requested resource does not exist
+const RESOURCE_NOT_FOUND: i32 = -32002;
+/// A request other than `initialize` or `ping` arrived before `initialize`
+const SERVER_NOT_INITIALIZED: i32 = -32003;
+
+/// JSON-RPC 2.0 error structure
+#[derive(Debug, Serialize)]
+struct McpError {
+    code: i32,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl McpError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn resource_not_found(uri: &str, message: &str) -> Self {
+        Self {
+            code: RESOURCE_NOT_FOUND,
+            message: format!("Resource not found: {}", message),
+            data: Some(json!({ "uri": uri })),
+        }
+    }
+
+    /// Build a tool error from a Garnix error, exposing its metadata in `data`
+    fn garnix(context: &str, error: &GarnixError) -> Self {
+        let mut data = json!({
+            "category": error.category(),
+            "retryable": error.is_retryable(),
+        });
+        if let Some(details) = error.http_details() {
+            data["status"] = json!(details.status);
+            data["url"] = json!(details.url);
+            data["request_id"] = json!(details.request_id);
+            data["retry_after_secs"] = json!(details.retry_after.map(|d| d.as_secs()));
+        }
+
+        Self {
+            code: -32000,
+            message: format!("{}: {}", context, error),
+            data: Some(data),
+        }
+    }
+}
+
+impl From<String> for McpError {
+    fn from(message: String) -> Self {
+        Self {
+            code: -32000,
+            message,
+            data: None,
+        }
+    }
+}
+
+impl From<&str> for McpError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_creation() {
+        let server = GarnixMcpServer::new();
+        // Just test that it can be created
+        drop(server);
+    }
+
+    #[test]
+    fn test_server_with_client() {
+        let client = GarnixClient::new();
+        let server = GarnixMcpServer::with_client(client);
+        drop(server);
+    }
+
+    #[test]
+    fn test_default_implementation() {
+        let server1 = GarnixMcpServer::new();
+        let server2 = GarnixMcpServer::default();
+
+        // Both should be created successfully
+        drop(server1);
+        drop(server2);
+    }
+
+    #[test]
+    fn test_request_deserialization() {
+        let json = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        let request: Result<McpRequest, _> = serde_json::from_str(json);
+        assert!(request.is_ok());
 
         let req = request.unwrap();
         assert_eq!(req.method, "initialize");
@@ -501,6 +2214,996 @@ mod tests {
 
         let result = server.handle_tool_call(None).await;
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Missing parameters"));
+        assert!(result.unwrap_err().message.contains("Missing parameters"));
+    }
+
+    #[test]
+    fn test_error_data_from_http_error() {
+        let error = GarnixError::http(
+            crate::error::HttpErrorDetails::new(503, "https://garnix.io/api/builds/abc", "down")
+                .request_id(Some("req-7".to_string())),
+        );
+        let mcp_error = McpError::garnix("Error getting build status", &error);
+
+        assert_eq!(mcp_error.code, -32000);
+        let data = mcp_error.data.unwrap();
+        assert_eq!(data["status"], 503);
+        assert_eq!(data["category"], "api");
+        assert_eq!(data["retryable"], true);
+        assert_eq!(data["request_id"], "req-7");
+    }
+
+    #[tokio::test]
+    async fn test_diagnose_tool_requires_commit() {
+        let server = GarnixMcpServer::new();
+        let result = server
+            .handle_tool_call(Some(json!({
+                "name": "diagnose_build_failures",
+                "arguments": {}
+            })))
+            .await;
+        assert!(result
+            .unwrap_err()
+            .message
+            .contains("Missing required argument: commit_id"));
+    }
+
+    #[test]
+    fn test_log_text_limits() {
+        let logs = LogResponse {
+            finished: true,
+            logs: vec![crate::types::LogEntry {
+                timestamp: "2024-01-01T00:00:00Z".to_string(),
+                log_message: (1..=10)
+                    .map(|i| format!("line {}", i))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            }],
+        };
+        let options = ExcerptOptions::new();
+
+        assert_eq!(
+            log_text(&logs, LogMode::Head, 2, &options),
+            "line 1\nline 2\n... 8 lines omitted"
+        );
+        assert_eq!(
+            log_text(&logs, LogMode::Tail, 2, &options),
+            "... 8 lines omitted\nline 9\nline 10"
+        );
+        assert_eq!(
+            log_text(&logs, LogMode::Tail, 10, &options).lines().count(),
+            10
+        );
+    }
+
+    /// Status of commit abc1234 with a successful "docs" and a failed "app" build
+    fn test_status() -> Value {
+        let build = |id: &str, package: &str, status: &str| {
+            json!({
+                "id": id, "repo_user": "testuser", "repo_name": "testrepo", "branch": "main",
+                "repo_is_public": true, "git_commit": "abc1234", "package": package,
+                "package_type": "derivation", "system": "x86_64-linux", "req_user": "testuser",
+                "status": status, "start_time": "2024-01-01T00:00:00Z",
+                "end_time": "2024-01-01T00:01:00Z", "drv_path": null, "output_paths": null,
+                "github_run_id": 1, "wants_incrementalism": false, "eval_host": "eval",
+                "uploaded_to_cache": false
+            })
+        };
+        json!({
+            "summary": {
+                "repo_owner": "testuser", "repo_name": "testrepo", "repo_is_public": true,
+                "git_commit": "abc1234", "branch": "main", "req_user": "testuser",
+                "start_time": "2024-01-01T00:00:00Z", "succeeded": 1, "failed": 1,
+                "pending": 0, "cancelled": 0
+            },
+            "builds": [build("ok-build", "docs", "Success"), build("bad-build", "app", "Failed")]
+        })
+    }
+
+    /// Log message of the failed "app" build
+    const FAILED_APP_LOG: &str = "Compiling app\nerror: could not compile `app`";
+
+    /// Finished log of the failed "app" build holding a single entry
+    fn failed_log(message: &str) -> Value {
+        json!({
+            "finished": true,
+            "logs": [{ "timestamp": "t", "log_message": message }]
+        })
+    }
+
+    /// Upstream answering GET requests with each route's JSON body, or a 404
+    /// for `Value::Null`, and a server using it
+    ///
+    /// Routes sharing a path are served once each in order, the last one for
+    /// any further request.
+    async fn mock_server(routes: &[(&str, Value)]) -> (mockito::ServerGuard, GarnixMcpServer) {
+        let mut upstream = mockito::Server::new_async().await;
+        for (path, body) in routes {
+            let mock = upstream.mock("GET", *path);
+            let mock = match body {
+                Value::Null => mock.with_status(404),
+                body => mock.with_status(200).with_body(body.to_string()),
+            };
+            mock.create_async().await;
+        }
+        let server = GarnixMcpServer::with_client(
+            GarnixClient::with_base_url(upstream.url()).with_token("test-token"),
+        );
+        (upstream, server)
+    }
+
+    #[tokio::test]
+    async fn test_get_build_logs_fetches_failed_logs() {
+        let (_upstream, server) = mock_server(&[
+            ("/builds/abc1234", test_status()),
+            (
+                "/builds/bad-build/logs",
+                failed_log("Compiling app\nerror[E0308]: mismatched types\ndone"),
+            ),
+        ])
+        .await;
+        let result = server
+            .handle_tool_call(Some(json!({
+                "name": "get_build_logs",
+                "arguments": { "commit_id": "abc1234" }
+            })))
+            .await
+            .unwrap();
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("## app (x86_64-linux)"));
+        assert!(text.contains(">2 | error[E0308]: mismatched types"));
+        assert!(!text.contains("## docs"));
+
+        let invalid = server
+            .handle_tool_call(Some(json!({
+                "name": "get_build_logs",
+                "arguments": { "commit_id": "abc1234", "mode": "middle" }
+            })))
+            .await;
+        let message = invalid.unwrap_err().message;
+        assert!(message.contains("unknown variant `middle`"), "{}", message);
+    }
+
+    async fn call(server: &GarnixMcpServer, message: Value) -> Value {
+        let response = server.handle_message(&message.to_string()).await.unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    async fn initialized_server() -> GarnixMcpServer {
+        let server = GarnixMcpServer::new();
+        initialize(&server, "2025-06-18").await;
+        server
+    }
+
+    async fn initialize(server: &GarnixMcpServer, version: &str) {
+        call(
+            server,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": version}}),
+        )
+        .await;
+        let notification = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        assert!(server
+            .handle_message(&notification.to_string())
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_requires_initialize() {
+        let server = GarnixMcpServer::new();
+
+        let response = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], SERVER_NOT_INITIALIZED);
+        assert_eq!(response["id"], 1);
+
+        let response = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": "p", "method": "ping"}),
+        )
+        .await;
+        assert_eq!(response["result"], json!({}));
+        assert!(response.get("error").is_none());
+
+        let response = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "initialize", "params": {"protocolVersion": "2025-06-18"}}),
+        )
+        .await;
+        assert_eq!(response["result"]["protocolVersion"], "2025-06-18");
+        assert_eq!(
+            *server.state.lock().unwrap(),
+            SessionState::Initializing(McpVersion::V2025_06_18)
+        );
+
+        let response = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 3, "method": "initialize"}),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        let server = initialized_server().await;
+        assert_eq!(
+            *server.state.lock().unwrap(),
+            SessionState::Ready(McpVersion::V2025_06_18)
+        );
+        let response = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 4, "method": "tools/list"}),
+        )
+        .await;
+        assert!(response["result"]["tools"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_version_falls_back() {
+        let server = GarnixMcpServer::new();
+        let response = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "1999-01-01"}}),
+        )
+        .await;
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+    }
+
+    #[tokio::test]
+    async fn test_notifications_are_not_answered() {
+        let server = initialized_server().await;
+        for method in [
+            "notifications/cancelled",
+            "notifications/unknown",
+            "tools/list",
+        ] {
+            let notification = json!({"jsonrpc": "2.0", "method": method, "params": {}});
+            assert!(server
+                .handle_message(&notification.to_string())
+                .await
+                .is_none());
+        }
+
+        // Responses to server-initiated requests are not answered either
+        let response = json!({"jsonrpc": "2.0", "id": 9, "result": {}});
+        assert!(server.handle_message(&response.to_string()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_messages() {
+        let server = initialized_server().await;
+
+        let response: Value =
+            serde_json::from_str(&server.handle_message("{not json").await.unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+
+        for message in [
+            json!([1, 2]),
+            json!({"jsonrpc": "1.0", "id": 1, "method": "ping"}),
+            json!({"jsonrpc": "2.0", "id": 1}),
+            json!({"jsonrpc": "2.0", "id": {"nested": true}, "method": "ping"}),
+        ] {
+            let response = call(&server, message).await;
+            assert_eq!(response["error"]["code"], INVALID_REQUEST);
+        }
+
+        let response = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 5, "method": "nope"}),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert!(response["error"].get("data").is_none());
+    }
+
+    #[test]
+    fn test_sessions_registry() {
+        let sessions =
+            McpSessions::new(GarnixClient::new(), McpVersion::V2025_03_26).with_max_sessions(2);
+        let (first, _) = sessions.create().unwrap();
+        let (second, _) = sessions.create().unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first.len(), 32);
+        assert_eq!(sessions.len(), 2);
+        assert!(matches!(sessions.create(), Err(GarnixError::RateLimit(_))));
+
+        assert!(sessions.get(&first).is_some());
+        assert!(sessions.remove(&first));
+        assert!(!sessions.remove(&first));
+        assert!(sessions.get(&first).is_none());
+        assert!(!sessions.is_empty());
+        assert!(sessions.create().is_ok());
+    }
+
+    #[test]
+    fn test_sessions_expire_when_idle() {
+        let sessions = McpSessions::new(GarnixClient::new(), McpVersion::V2025_03_26)
+            .with_idle_timeout(Duration::ZERO);
+        let (id, _) = sessions.create().unwrap();
+        assert!(sessions.get(&id).is_none());
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_resource_uri() {
+        for uri in [
+            "garnix://commit/abc1234",
+            "garnix://build/b-1",
+            "garnix://build/b-1/logs",
+        ] {
+            assert_eq!(ResourceUri::parse(uri).unwrap().to_string(), uri);
+        }
+        assert_eq!(ResourceUri::parse("garnix://commit/"), None);
+        assert_eq!(ResourceUri::parse("garnix://build/b-1/other"), None);
+        assert_eq!(ResourceUri::parse("https://garnix.io/commit/abc"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resources() {
+        let (_upstream, server) = mock_server(&[
+            ("/builds/abc1234", test_status()),
+            ("/builds/bad-build/logs", failed_log("error: boom")),
+        ])
+        .await;
+        let read = |uri: &str| server.read_resource(Some(json!({ "uri": uri })));
+
+        let templates = call(
+            &initialized_server().await,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "resources/templates/list"}),
+        )
+        .await;
+        assert_eq!(
+            templates["result"]["resourceTemplates"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        // Builds are only known once their commit has been read
+        let error = read("garnix://build/bad-build").await.unwrap_err();
+        assert_eq!(error.code, RESOURCE_NOT_FOUND);
+        assert_eq!(server.list_resources(), Vec::<Value>::new());
+
+        let commit = read("garnix://commit/abc1234").await.unwrap();
+        assert_eq!(commit["contents"][0]["mimeType"], "text/markdown");
+        assert!(commit["contents"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("# Build Summary for abc1234"));
+        assert_eq!(commit["contents"][1]["mimeType"], "application/json");
+
+        let uris: Vec<Value> = server
+            .list_resources()
+            .iter()
+            .map(|resource| resource["uri"].clone())
+            .collect();
+        assert_eq!(
+            uris,
+            vec![
+                json!("garnix://commit/abc1234"),
+                json!("garnix://build/ok-build"),
+                json!("garnix://build/bad-build"),
+                json!("garnix://build/bad-build/logs"),
+            ]
+        );
+
+        let build = read("garnix://build/bad-build").await.unwrap();
+        assert!(build["contents"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("### app"));
+
+        let logs = read("garnix://build/bad-build/logs").await.unwrap();
+        assert!(logs["contents"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains(">1 | error: boom"));
+
+        let error = read("garnix://nothing").await.unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_output_schemas_depend_on_version() {
+        let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+
+        let server = initialized_server().await;
+        let response = call(&server, list.clone()).await;
+        for tool in response["result"]["tools"].as_array().unwrap() {
+            assert_eq!(tool["outputSchema"]["type"], "object", "{}", tool["name"]);
+            if tool["name"] == "get_build_status" || tool["name"] == "wait_for_builds" {
+                let schema = &tool["outputSchema"];
+                assert!(schema["properties"]["wall_clock_secs"].is_object());
+                let build = &schema["$defs"]["TimedBuild"]["properties"];
+                assert!(build["duration"].is_object());
+                assert!(build["package"].is_object());
+            }
+        }
+
+        let server = GarnixMcpServer::new();
+        initialize(&server, "2025-03-26").await;
+        let response = call(&server, list).await;
+        for tool in response["result"]["tools"].as_array().unwrap() {
+            assert!(tool.get("outputSchema").is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_structured_content() {
+        let (_upstream, server) = mock_server(&[("/builds/abc1234", test_status())]).await;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": {"name": "check_commit_ready", "arguments": {"commit_id": "abc1234"}}
+        });
+
+        initialize(&server, "2025-06-18").await;
+        let result = call(&server, request.clone()).await["result"].clone();
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("is NOT ready"));
+        assert_eq!(result["structuredContent"]["ready"], false);
+        assert_eq!(result["structuredContent"]["total_builds"], 2);
+
+        let server = GarnixMcpServer::with_client(server.client.clone());
+        initialize(&server, "2024-11-05").await;
+        let result = call(&server, request).await["result"].clone();
+        assert!(result.get("structuredContent").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_token_policy() {
+        let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+        let token_schema = |tools: &Value| {
+            let schema = &tools["result"]["tools"][0]["inputSchema"];
+            (
+                schema["properties"].get("token").is_some(),
+                schema["required"]
+                    .as_array()
+                    .unwrap()
+                    .contains(&json!("token")),
+            )
+        };
+
+        let optional = initialized_server().await;
+        assert_eq!(
+            token_schema(&call(&optional, list.clone()).await),
+            (true, false)
+        );
+
+        let required = GarnixMcpServer::new().with_token_policy(TokenPolicy::Required);
+        initialize(&required, "2025-06-18").await;
+        assert_eq!(
+            token_schema(&call(&required, list.clone()).await),
+            (true, true)
+        );
+        let error = required
+            .handle_tool_call(Some(json!({
+                "name": "get_build_status",
+                "arguments": { "commit_id": "abc1234" }
+            })))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+        assert!(error.message.contains("token"));
+        let error = required
+            .read_resource(Some(json!({"uri": "garnix://commit/abc1234"})))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, INVALID_REQUEST);
+        assert!(error.message.contains("required token policy"));
+
+        let disallowed = GarnixMcpServer::new().with_token_policy(TokenPolicy::Disallowed);
+        initialize(&disallowed, "2025-06-18").await;
+        assert_eq!(token_schema(&call(&disallowed, list).await), (false, false));
+        let error = disallowed
+            .handle_tool_call(Some(json!({
+                "name": "check_commit_ready",
+                "arguments": { "commit_id": "abc1234", "token": "leaked" }
+            })))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_token_uses_server_token() {
+        let mut upstream = mockito::Server::new_async().await;
+        let _status = upstream
+            .mock("GET", "/builds/abc1234")
+            .match_header("authorization", "Bearer server-token")
+            .with_status(200)
+            .with_body(test_status().to_string())
+            .create_async()
+            .await;
+
+        let server = GarnixMcpServer::with_client(
+            GarnixClient::with_base_url(upstream.url()).with_token("server-token"),
+        )
+        .with_token_policy(TokenPolicy::Disallowed);
+        let result = server
+            .handle_tool_call(Some(json!({
+                "name": "get_build_status",
+                "arguments": { "commit_id": "abc1234" }
+            })))
+            .await
+            .unwrap();
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("abc1234"));
+    }
+
+    #[tokio::test]
+    async fn test_prompts() {
+        let (_upstream, server) = mock_server(&[
+            ("/builds/abc1234", test_status()),
+            ("/builds/bad-build/logs", failed_log(FAILED_APP_LOG)),
+        ])
+        .await;
+        initialize(&server, "2025-06-18").await;
+
+        let list = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "prompts/list"}),
+        )
+        .await;
+        let names: Vec<&str> = list["result"]["prompts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|prompt| prompt["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["triage_failed_builds", "deploy_readiness", "branch_health"]
+        );
+
+        let get = |name: &str, arguments: Value| json!({"jsonrpc": "2.0", "id": 3, "method": "prompts/get", "params": {"name": name, "arguments": arguments}});
+        let triage = call(
+            &server,
+            get("triage_failed_builds", json!({"commit_id": "abc1234"})),
+        )
+        .await;
+        let messages = triage["result"]["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert!(messages.iter().all(|message| message["role"] == "user"));
+        assert_eq!(
+            messages[1]["content"]["resource"]["uri"],
+            "garnix://commit/abc1234"
+        );
+        let logs = &messages[2]["content"]["resource"];
+        assert_eq!(logs["uri"], "garnix://build/bad-build/logs");
+        assert!(logs["text"]
+            .as_str()
+            .unwrap()
+            .contains(">2 | error: could not compile `app`"));
+        assert!(messages[3]["content"]["text"]
+            .as_str()
+            .unwrap()
+            .contains("Compiler error"));
+
+        let deploy = call(
+            &server,
+            get("deploy_readiness", json!({"commit_id": "abc1234"})),
+        )
+        .await;
+        let messages = deploy["result"]["messages"].as_array().unwrap();
+        assert!(messages[2]["content"]["text"]
+            .as_str()
+            .unwrap()
+            .contains("NOT ready"));
+
+        let missing = call(&server, get("deploy_readiness", json!({}))).await;
+        assert_eq!(missing["error"]["code"], INVALID_PARAMS);
+        let unknown = call(&server, get("nope", json!({}))).await;
+        assert_eq!(unknown["error"]["code"], INVALID_PARAMS);
+        let count = call(
+            &server,
+            get("branch_health", json!({"branch": "main", "count": "0"})),
+        )
+        .await;
+        assert_eq!(count["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_branch_health_table() {
+        let (_upstream, server) = mock_server(&[
+            ("/builds/abc1234", test_status()),
+            ("/builds/def5678", Value::Null),
+        ])
+        .await;
+        let commits = ["abc1234".to_string(), "def5678".to_string()];
+
+        let origin: RepoSlug = "TestUser/testrepo".parse().unwrap();
+        let messages = server
+            .health_messages("main", &commits, &origin)
+            .await
+            .unwrap();
+        let table = messages[1]["content"]["text"].as_str().unwrap();
+        assert!(table.starts_with("# CI health of main (last 2 commits"));
+        assert!(table.contains("| abc1234 | failed | 1 | 1 | 0 | app |"));
+        assert!(table.contains("| def5678 | no CI run |"));
+
+        let other: RepoSlug = "someone/else".parse().unwrap();
+        let error = server
+            .health_messages("main", &commits, &other)
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+        assert!(error.message.contains("belongs to testuser/testrepo"));
+    }
+
+    #[tokio::test]
+    async fn test_request_ids_in_flight() {
+        let (_upstream, server) = mock_server(&[("/builds/abc1234", pending_status())]).await;
+        initialize(&server, "2025-06-18").await;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "tools/call",
+            "params": {
+                "name": "wait_for_builds",
+                "arguments": { "commit_id": "abc1234", "interval_secs": 1 }
+            }
+        });
+
+        let message = request.to_string();
+        let mut waiting = Box::pin(server.handle_message(&message));
+        tokio::select! {
+            _ = &mut waiting => panic!("wait_for_builds finished while builds are pending"),
+            _ = tokio::time::sleep(Duration::from_millis(200)) => {}
+        }
+        let response = call(&server, request).await;
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+        assert!(response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("already in use"));
+
+        // Dropping a request, e.g. on disconnect, forgets it
+        drop(waiting);
+        assert!(server.requests.lock().unwrap().is_empty());
+    }
+
+    /// [`test_status`] with the "app" build still pending
+    fn pending_status() -> Value {
+        let mut status = test_status();
+        status["builds"][1]["status"] = json!("Pending");
+        status["summary"]["failed"] = json!(0);
+        status["summary"]["pending"] = json!(1);
+        status
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_builds_reports_progress() {
+        let (_upstream, server) = mock_server(&[
+            ("/builds/abc1234", pending_status()),
+            ("/builds/abc1234", test_status()),
+        ])
+        .await;
+        let mut messages = server.subscribe();
+
+        let result = server
+            .handle_tool_call(Some(json!({
+                "name": "wait_for_builds",
+                "arguments": { "commit_id": "abc1234", "interval_secs": 1 },
+                "_meta": { "progressToken": "wait-1" }
+            })))
+            .await
+            .unwrap();
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("All builds of commit abc1234 finished."));
+
+        let progress: Value = serde_json::from_str(&messages.try_recv().unwrap()).unwrap();
+        assert_eq!(progress["method"], "notifications/progress");
+        assert_eq!(progress["params"]["progressToken"], "wait-1");
+        assert_eq!(progress["params"]["progress"], 1);
+        assert!(progress["params"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Pending → Failed"));
+        assert!(messages.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_wait_for_builds() {
+        let (_upstream, server) = mock_server(&[("/builds/abc1234", pending_status())]).await;
+        initialize(&server, "2025-03-26").await;
+
+        let wait = json!({
+            "jsonrpc": "2.0", "id": 7, "method": "tools/call",
+            "params": { "name": "wait_for_builds", "arguments": { "commit_id": "abc1234", "interval_secs": 60 } }
+        })
+        .to_string();
+        let cancel = json!({
+            "jsonrpc": "2.0", "method": "notifications/cancelled",
+            "params": { "requestId": 7, "reason": "user aborted" }
+        })
+        .to_string();
+        let (response, _) = tokio::join!(server.handle_message(&wait), async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            server.handle_message(&cancel).await
+        });
+        assert!(response.is_none());
+        assert!(server.requests.lock().unwrap().is_empty());
+
+        // Cancelling a request that is not in flight is ignored
+        assert!(server.handle_message(&cancel).await.is_none());
+        let ping = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 8, "method": "ping"}),
+        )
+        .await;
+        assert_eq!(ping["result"], json!({}));
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<Value> = (0..5).map(|i| json!(i)).collect();
+
+        let (page, cursor) = paginate_by(items.clone(), None, 2).unwrap();
+        assert_eq!(page, vec![json!(0), json!(1)]);
+        let params = json!({ "cursor": cursor.unwrap() });
+        let (page, cursor) = paginate_by(items.clone(), Some(&params), 2).unwrap();
+        assert_eq!(page, vec![json!(2), json!(3)]);
+        let params = json!({ "cursor": cursor.unwrap() });
+        let (page, cursor) = paginate_by(items.clone(), Some(&params), 2).unwrap();
+        assert_eq!(page, vec![json!(4)]);
+        assert_eq!(cursor, None);
+
+        let invalid = json!({ "cursor": "not-a-cursor" });
+        let error = paginate_by(items, Some(&invalid), 2).unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_enabled_tools() {
+        let server = GarnixMcpServer::new().with_enabled_tools(Some(vec![
+            "get_build_status".to_string(),
+            "check_commit_ready".to_string(),
+        ]));
+        let mut messages = server.subscribe();
+        initialize(&server, "2025-06-18").await;
+
+        let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+        let response = call(&server, list.clone()).await;
+        let tools = response["result"]["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0]["annotations"]["readOnlyHint"], true);
+        assert_eq!(tools[0]["annotations"]["openWorldHint"], true);
+        assert!(response["result"].get("nextCursor").is_none());
+
+        let disabled = call(
+            &server,
+            json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "get_build_logs", "arguments": {"commit_id": "abc1234"}}}),
+        )
+        .await;
+        assert_eq!(disabled["error"]["code"], INVALID_PARAMS);
+
+        // Setting the same tools again does not notify
+        server.set_enabled_tools(Some(vec![
+            "check_commit_ready".to_string(),
+            "get_build_status".to_string(),
+        ]));
+        assert!(messages.try_recv().is_err());
+
+        server.set_enabled_tools(None);
+        let notification: Value = serde_json::from_str(&messages.try_recv().unwrap()).unwrap();
+        assert_eq!(notification["method"], "notifications/tools/list_changed");
+        let response = call(&server, list).await;
+        assert_eq!(
+            response["result"]["tools"].as_array().unwrap().len(),
+            TOOLS.iter().filter(|tool| !tool.opt_in).count()
+        );
+
+        assert!(check_tool_names(&["wait_for_builds".to_string()]).is_ok());
+        assert!(matches!(
+            check_tool_names(&["rm_rf".to_string()]),
+            Err(GarnixError::ConfigError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_compare_commits() {
+        let mut base = test_status();
+        base["summary"]["git_commit"] = json!("main123");
+        base["builds"][1]["status"] = json!("Success");
+        let (_upstream, server) = mock_server(&[
+            ("/builds/main123", base),
+            ("/builds/abc1234", test_status()),
+        ])
+        .await;
+        initialize(&server, "2025-06-18").await;
+        let result = call(
+            &server,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/call",
+                "params": {
+                    "name": "compare_commits",
+                    "arguments": { "base_commit": "main123", "head_commit": "abc1234" }
+                }
+            }),
+        )
+        .await["result"]
+            .clone();
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("## Newly failing\n- **app (x86_64-linux)**: Success → Failed"));
+        let structured = &result["structuredContent"];
+        assert_eq!(structured["base_commit"], "main123");
+        assert_eq!(structured["newly_failing"][0]["build_id"], "bad-build");
+        assert_eq!(structured["newly_fixed"], json!([]));
+    }
+
+    fn summarize_request(id: u64) -> String {
+        json!({
+            "jsonrpc": "2.0", "id": id, "method": "tools/call",
+            "params": {
+                "name": "summarize_failure",
+                "arguments": { "build_id": "bad-build" },
+                "_meta": { "progressToken": "summary" }
+            }
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_summarize_failure_without_sampling() {
+        let (_upstream, server) =
+            mock_server(&[("/builds/bad-build/logs", failed_log(FAILED_APP_LOG))]).await;
+
+        // The tool is opt-in
+        initialize(&server, "2025-06-18").await;
+        assert!(!server.enabled_tool_names().contains(&"summarize_failure"));
+        let response: Value =
+            serde_json::from_str(&server.handle_message(&summarize_request(2)).await.unwrap())
+                .unwrap();
+        assert_eq!(
+            response["error"]["message"],
+            "Unknown tool: summarize_failure"
+        );
+
+        let server = GarnixMcpServer::with_client(server.client.clone())
+            .with_enabled_tools(Some(vec!["summarize_failure".to_string()]));
+        initialize(&server, "2025-06-18").await;
+        let response: Value =
+            serde_json::from_str(&server.handle_message(&summarize_request(2)).await.unwrap())
+                .unwrap();
+        let result = &response["result"];
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("- **Cause:** Compiler error"));
+        assert!(text.contains("excerpt of the log"));
+        assert_eq!(result["structuredContent"]["method"], "excerpt");
+        assert_eq!(result["structuredContent"]["chunks"], 0);
+        assert!(result["structuredContent"]["summary"]
+            .as_str()
+            .unwrap()
+            .contains(">2 | error: could not compile"));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_failure_with_sampling() {
+        let (_upstream, server) =
+            mock_server(&[("/builds/bad-build/logs", failed_log(FAILED_APP_LOG))]).await;
+        let server = server.with_enabled_tools(Some(vec!["summarize_failure".to_string()]));
+        call(
+            &server,
+            json!({
+                "jsonrpc": "2.0", "id": 1, "method": "initialize",
+                "params": { "protocolVersion": "2025-06-18", "capabilities": { "sampling": {} } }
+            }),
+        )
+        .await;
+        let mut messages = server.subscribe();
+        let mut requests = server.subscribe_requests();
+
+        // Answer the sampling request like a client would, forwarding the progress
+        let client = async {
+            loop {
+                tokio::select! {
+                    request = requests.recv() => {
+                        let request: Value = serde_json::from_str(&request.unwrap()).unwrap();
+                        assert_eq!(request["method"], "sampling/createMessage");
+                        assert!(request["params"]["messages"][0]["content"]["text"]
+                            .as_str()
+                            .unwrap()
+                            .contains("error: could not compile `app`"));
+                        let response = json!({
+                            "jsonrpc": "2.0", "id": request["id"],
+                            "result": {
+                                "role": "assistant", "model": "test-model", "stopReason": "endTurn",
+                                "content": { "type": "text", "text": "`app` does not compile." }
+                            }
+                        });
+                        assert!(server.handle_message(&response.to_string()).await.is_none());
+                    }
+                    message = messages.recv() => {
+                        let message: Value = serde_json::from_str(&message.unwrap()).unwrap();
+                        assert_eq!(message["method"], "notifications/progress");
+                        return vec![message["params"].clone()];
+                    }
+                }
+            }
+        };
+        let request = summarize_request(2);
+        let (response, progress) = tokio::join!(server.handle_message(&request), client);
+
+        let response: Value = serde_json::from_str(&response.unwrap()).unwrap();
+        let result = &response["result"];
+        assert!(result["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .ends_with("\n`app` does not compile.\n"));
+        assert_eq!(result["structuredContent"]["method"], "sampling");
+        assert_eq!(result["structuredContent"]["chunks"], 1);
+        assert_eq!(
+            result["structuredContent"]["diagnosis"]["kind"],
+            "compiler_error"
+        );
+        assert_eq!(progress[0]["progress"], 1);
+        assert_eq!(progress[0]["total"], 1);
+        assert!(server.outgoing.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_client_request_errors() {
+        let server = initialized_server().await;
+        // Nobody is listening
+        let error = server
+            .request_client("sampling/createMessage", json!({}), Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert!(error.message.starts_with("No client is listening"));
+
+        // Only the most recent subscriber gets the request
+        let mut first = server.subscribe_requests();
+        let mut requests = server.subscribe_requests();
+        let (result, _) = tokio::join!(
+            server.request_client("sampling/createMessage", json!({}), Duration::from_secs(5)),
+            async {
+                let request: Value = serde_json::from_str(&requests.recv().await.unwrap()).unwrap();
+                let response = json!({
+                    "jsonrpc": "2.0", "id": request["id"],
+                    "error": { "code": -1, "message": "User rejected sampling request" }
+                });
+                server.handle_message(&response.to_string()).await
+            }
+        );
+        let error = result.unwrap_err();
+        assert_eq!(error.code, -1);
+        assert_eq!(error.message, "User rejected sampling request");
+        assert!(first.try_recv().is_err());
+
+        let error = server
+            .request_client(
+                "sampling/createMessage",
+                json!({}),
+                Duration::from_millis(10),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.message.contains("did not answer"));
+        assert!(server.outgoing.lock().unwrap().is_empty());
+
+        // Once it goes away the earlier subscriber takes over
+        drop(requests);
+        let error = server
+            .request_client("roots/list", json!({}), Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(error.message.contains("did not answer"));
+        let request: Value = serde_json::from_str(&first.try_recv().unwrap()).unwrap();
+        assert_eq!(request["method"], "roots/list");
     }
 }
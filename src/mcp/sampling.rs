@@ -0,0 +1,193 @@
+//! Summaries of build logs written by the client's model
+//!
+//! Clients advertising the `sampling` capability can be asked to run a prompt
+//! through their model with `sampling/createMessage`. Long logs are split into
+//! chunks that are summarised one by one, and the partial summaries are then
+//! combined into one.
+
+use crate::excerpt::is_error_line;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Maximum size of a chunk of log sent to the client's model, in bytes
+const CHUNK_BYTES: usize = 24_000;
+/// Maximum number of chunks summarised per log
+const MAX_CHUNKS: usize = 8;
+/// Maximum number of tokens the client's model may generate per summary
+const MAX_TOKENS: u64 = 600;
+/// Time the client has to answer a sampling request, including user approval
+pub(super) const SAMPLING_TIMEOUT: Duration = Duration::from_secs(120);
+/// Time all sampling requests summarising one log may take together, after
+/// which the summary falls back to an excerpt
+pub(super) const SAMPLING_DEADLINE: Duration = Duration::from_secs(300);
+
+const SYSTEM_PROMPT: &str = "You summarise logs of failed Nix builds on Garnix CI. \
+Name the root cause (not its consequences, such as dependent derivations failing), \
+quote the offending lines verbatim and suggest a fix if one is apparent. Be brief.";
+
+/// Split a log into chunks of whole lines, keeping at most [`MAX_CHUNKS`]
+///
+/// When the log has more chunks, those containing error lines are kept,
+/// together with the last chunk, which usually holds the final error summary.
+/// A log without error lines keeps its last chunks. Overlong lines are cut.
+pub(super) fn log_chunks<S: AsRef<str>>(lines: &[S]) -> Vec<String> {
+    let mut chunks: Vec<(String, bool)> = Vec::new();
+    let mut current = String::new();
+    let mut has_error = false;
+    for line in lines {
+        let line = truncate(line.as_ref(), CHUNK_BYTES - 1);
+        if !current.is_empty() && current.len() + line.len() + 1 > CHUNK_BYTES {
+            chunks.push((std::mem::take(&mut current), has_error));
+            has_error = false;
+        }
+        current.push_str(line);
+        current.push('\n');
+        has_error |= is_error_line(line);
+    }
+    if !current.is_empty() {
+        chunks.push((current, has_error));
+    }
+
+    if chunks.len() > MAX_CHUNKS {
+        let last = chunks.len() - 1;
+        let errors: Vec<usize> = (0..last).filter(|&i| chunks[i].1).collect();
+        let keep: Vec<usize> = if errors.is_empty() {
+            (chunks.len() - MAX_CHUNKS..chunks.len()).collect()
+        } else {
+            errors
+                .into_iter()
+                .take(MAX_CHUNKS - 1)
+                .chain([last])
+                .collect()
+        };
+        chunks = keep
+            .into_iter()
+            .map(|i| std::mem::take(&mut chunks[i]))
+            .collect();
+    }
+    chunks.into_iter().map(|(chunk, _)| chunk).collect()
+}
+
+/// Cut a line to at most `max` bytes, on a character boundary
+fn truncate(line: &str, max: usize) -> &str {
+    if line.len() <= max {
+        return line;
+    }
+    let mut end = max;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Parameters of a `sampling/createMessage` request summarising a chunk of log
+pub(super) fn chunk_request(build_id: &str, chunk: &str, index: usize, count: usize) -> Value {
+    let part = match count {
+        1 => String::new(),
+        _ => format!(" (part {} of {})", index + 1, count),
+    };
+    request(format!(
+        "Summarise why build {} failed, based on this log{}:\n\n```\n{}```",
+        build_id, part, chunk
+    ))
+}
+
+/// Parameters of a `sampling/createMessage` request combining partial summaries
+pub(super) fn combine_request(build_id: &str, summaries: &[String]) -> Value {
+    let parts: Vec<String> = summaries
+        .iter()
+        .enumerate()
+        .map(|(index, summary)| format!("## Part {}\n{}", index + 1, summary))
+        .collect();
+    request(format!(
+        "These are summaries of consecutive parts of the log of the failed build {}. \
+Combine them into one summary of why the build failed:\n\n{}",
+        build_id,
+        parts.join("\n\n")
+    ))
+}
+
+fn request(text: String) -> Value {
+    json!({
+        "messages": [{
+            "role": "user",
+            "content": { "type": "text", "text": text }
+        }],
+        "systemPrompt": SYSTEM_PROMPT,
+        "includeContext": "none",
+        "maxTokens": MAX_TOKENS,
+        "modelPreferences": { "speedPriority": 0.8, "intelligencePriority": 0.5 }
+    })
+}
+
+/// Text of a `sampling/createMessage` result, if it is a non-empty text message
+pub(super) fn sampled_text(result: &Value) -> Option<String> {
+    let content = result.get("content")?;
+    if content.get("type").and_then(Value::as_str) != Some("text") {
+        return None;
+    }
+    content
+        .get("text")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_chunks() {
+        assert!(log_chunks::<&str>(&[]).is_empty());
+        assert_eq!(log_chunks(&["a", "b"]), ["a\nb\n"]);
+
+        // 1000-byte lines, 24 per chunk, 20 chunks of which the last 8 are kept
+        let mut lines: Vec<String> = (0..24 * 20).map(|i| format!("{:<999}", i)).collect();
+        let chunks = log_chunks(&lines);
+        assert_eq!(chunks.len(), MAX_CHUNKS);
+        assert!(chunks[0].starts_with("288 "));
+        assert!(chunks[MAX_CHUNKS - 1].starts_with("456 "));
+
+        lines[50] = "error: builder for '/nix/store/abc-foo.drv' failed".to_string();
+        let chunks = log_chunks(&lines);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].starts_with("48 "));
+        assert!(chunks[0].contains("error: builder for"));
+        assert!(chunks[1].starts_with("456 "));
+
+        let long = "é".repeat(CHUNK_BYTES);
+        let chunks = log_chunks(&[long.as_str(), "next"]);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].len() <= CHUNK_BYTES);
+    }
+
+    #[test]
+    fn test_requests() {
+        let request = chunk_request("build-1", "error: boom\n", 1, 3);
+        assert_eq!(request["messages"][0]["role"], "user");
+        let text = request["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.contains("build build-1 failed, based on this log (part 2 of 3)"));
+        assert!(text.contains("```\nerror: boom\n```"));
+        assert_eq!(request["maxTokens"], MAX_TOKENS);
+
+        let combined = combine_request("build-1", &["one".to_string(), "two".to_string()]);
+        let text = combined["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.ends_with("## Part 1\none\n\n## Part 2\ntwo"));
+
+        assert_eq!(
+            sampled_text(
+                &json!({"role": "assistant", "content": {"type": "text", "text": " ok "}})
+            ),
+            Some("ok".to_string())
+        );
+        assert_eq!(
+            sampled_text(
+                &json!({"content": {"type": "image", "data": "", "mimeType": "image/png"}})
+            ),
+            None
+        );
+        assert_eq!(sampled_text(&json!({})), None);
+    }
+}
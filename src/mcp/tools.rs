@@ -0,0 +1,316 @@
+//! Registry of the tools offered by the MCP server
+//!
+//! Every tool is described by a [`ToolDescriptor`]. Its input schema is derived
+//! from the struct its arguments are parsed into, so the advertised schema and
+//! the accepted arguments cannot drift apart.
+
+use super::{
+    BuildLogsOutput, DiagnosisOutput, LogMode, McpError, ReadinessOutput, SummaryOutput,
+    INVALID_PARAMS,
+};
+use crate::compare::Comparison;
+use crate::types::BuildStatusReport;
+use schemars::{JsonSchema, Schema};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Arguments of the `get_build_status` tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(super) struct BuildStatusArguments {
+    /// The commit SHA to check build status for
+    pub commit_id: String,
+    /// JWT token for Garnix API authentication (defaults to the server's configured token)
+    pub token: Option<String>,
+}
+
+/// Arguments of the `get_build_logs` tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(super) struct BuildLogsArguments {
+    /// The commit SHA to get logs for
+    pub commit_id: String,
+    /// Only include builds of this package
+    pub package: Option<String>,
+    /// Only include builds for this system (e.g. x86_64-linux)
+    pub system: Option<String>,
+    /// Only include failed builds (defaults to true unless package or system is given)
+    pub failed_only: Option<bool>,
+    /// Show the region around the first error (default), or the first or last lines of each log
+    pub mode: Option<LogMode>,
+    /// Maximum number of log lines per build (default 200)
+    #[schemars(range(min = 1))]
+    pub max_lines: Option<u64>,
+    /// Lines of context around the error in excerpt mode (default 10)
+    pub context: Option<u64>,
+    /// JWT token for Garnix API authentication (defaults to the server's configured token)
+    pub token: Option<String>,
+}
+
+/// Arguments of the `check_commit_ready` tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(super) struct ReadinessArguments {
+    /// The commit SHA to check readiness for
+    pub commit_id: String,
+    /// JWT token for Garnix API authentication (defaults to the server's configured token)
+    pub token: Option<String>,
+}
+
+/// Arguments of the `diagnose_build_failures` tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(super) struct DiagnoseArguments {
+    /// The commit SHA to diagnose
+    pub commit_id: String,
+    /// Only diagnose builds of this package
+    pub package: Option<String>,
+    /// Only diagnose builds for this system (e.g. x86_64-linux)
+    pub system: Option<String>,
+    /// JWT token for Garnix API authentication (defaults to the server's configured token)
+    pub token: Option<String>,
+}
+
+/// Arguments of the `wait_for_builds` tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(super) struct WaitArguments {
+    /// The commit SHA to wait for
+    pub commit_id: String,
+    /// Seconds between two polls of the build status (default 10)
+    #[schemars(range(min = 1))]
+    pub interval_secs: Option<u64>,
+    /// Maximum number of seconds to wait, 0 to wait indefinitely (default 1800)
+    pub timeout_secs: Option<u64>,
+    /// JWT token for Garnix API authentication (defaults to the server's configured token)
+    pub token: Option<String>,
+}
+
+/// Arguments of the `compare_commits` tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(super) struct CompareArguments {
+    /// The commit SHA to compare against, e.g. the latest commit on main
+    pub base_commit: String,
+    /// The commit SHA to check, e.g. the latest commit of a branch
+    pub head_commit: String,
+    /// JWT token for Garnix API authentication (defaults to the server's configured token)
+    pub token: Option<String>,
+}
+
+/// Arguments of the `summarize_failure` tool
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(super) struct SummarizeArguments {
+    /// The ID of the failed build to summarize
+    pub build_id: String,
+    /// JWT token for Garnix API authentication (defaults to the server's configured token)
+    pub token: Option<String>,
+}
+
+/// Hints about the behaviour of a tool, as defined by MCP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ToolAnnotations {
+    /// The tool does not modify its environment
+    pub read_only_hint: bool,
+    /// The tool may perform destructive updates
+    pub destructive_hint: bool,
+    /// Repeated calls with the same arguments have no additional effect
+    pub idempotent_hint: bool,
+    /// The tool interacts with external entities
+    pub open_world_hint: bool,
+}
+
+/// Annotations of tools that only query the Garnix API
+const QUERY: ToolAnnotations = ToolAnnotations {
+    read_only_hint: true,
+    destructive_hint: false,
+    idempotent_hint: true,
+    open_world_hint: true,
+};
+
+/// Description of a tool offered by the MCP server
+pub(super) struct ToolDescriptor {
+    /// Unique name of the tool
+    pub name: &'static str,
+    /// Human-readable name of the tool
+    pub title: &'static str,
+    /// What the tool does
+    pub description: &'static str,
+    /// Schema of the arguments
+    pub input_schema: fn() -> Schema,
+    /// Schema of the `structuredContent` of results
+    pub output_schema: fn() -> Schema,
+    /// Hints about the behaviour of the tool
+    pub annotations: ToolAnnotations,
+    /// Only offered when explicitly enabled
+    pub opt_in: bool,
+}
+
+/// All tools, in the order they are listed
+pub(super) const TOOLS: &[ToolDescriptor] = &[
+    ToolDescriptor {
+        name: "get_build_status",
+        title: "Get build status",
+        description: "Get the build status for a specific commit from Garnix",
+        input_schema: schema::<BuildStatusArguments>,
+        output_schema: schema::<BuildStatusReport>,
+        annotations: QUERY,
+        opt_in: false,
+    },
+    ToolDescriptor {
+        name: "get_build_logs",
+        title: "Get build logs",
+        description: "Get the build logs of a commit from Garnix. By default only failed builds are included and each log is reduced to an excerpt around the first error",
+        input_schema: schema::<BuildLogsArguments>,
+        output_schema: schema::<BuildLogsOutput>,
+        annotations: QUERY,
+        opt_in: false,
+    },
+    ToolDescriptor {
+        name: "check_commit_ready",
+        title: "Check deployment readiness",
+        description: "Check if a commit is ready for deployment (all builds passed)",
+        input_schema: schema::<ReadinessArguments>,
+        output_schema: schema::<ReadinessOutput>,
+        annotations: QUERY,
+        opt_in: false,
+    },
+    ToolDescriptor {
+        name: "diagnose_build_failures",
+        title: "Diagnose build failures",
+        description: "Classify why the builds of a commit failed (hash mismatch, out of memory, timeout, network, evaluation, test or compiler error, failed dependency) and show the offending log lines",
+        input_schema: schema::<DiagnoseArguments>,
+        output_schema: schema::<DiagnosisOutput>,
+        annotations: QUERY,
+        opt_in: false,
+    },
+    ToolDescriptor {
+        name: "wait_for_builds",
+        title: "Wait for builds",
+        description: "Wait until no builds of a commit are pending anymore and return the final build status. Sends notifications/progress as builds finish when the request has a progressToken, and can be cancelled with notifications/cancelled",
+        input_schema: schema::<WaitArguments>,
+        output_schema: schema::<BuildStatusReport>,
+        annotations: QUERY,
+        opt_in: false,
+    },
+    ToolDescriptor {
+        name: "compare_commits",
+        title: "Compare commits",
+        description: "Compare the builds of a head commit with those of a base commit, per package and system: newly failing, newly fixed, still failing, added and removed builds, and large changes in build duration",
+        input_schema: schema::<CompareArguments>,
+        output_schema: schema::<Comparison>,
+        annotations: QUERY,
+        opt_in: false,
+    },
+    ToolDescriptor {
+        name: "summarize_failure",
+        title: "Summarize build failure",
+        description: "Summarize why a build failed. The log is split into chunks that the client's model summarizes through sampling; without sampling, the heuristic diagnosis and an excerpt around the first error are returned instead",
+        input_schema: schema::<SummarizeArguments>,
+        output_schema: schema::<SummaryOutput>,
+        annotations: QUERY,
+        opt_in: true,
+    },
+];
+
+fn schema<T: JsonSchema>() -> Schema {
+    schemars::schema_for!(T)
+}
+
+impl ToolDescriptor {
+    /// Find a tool by name
+    pub(super) fn find(name: &str) -> Option<&'static ToolDescriptor> {
+        TOOLS.iter().find(|tool| tool.name == name)
+    }
+
+    /// The tool as listed by `tools/list`
+    pub(super) fn to_json(&self, structured_output: bool) -> Value {
+        let mut tool = json!({
+            "name": self.name,
+            "title": self.title,
+            "description": self.description,
+            "inputSchema": (self.input_schema)(),
+            "annotations": self.annotations,
+        });
+        tool["annotations"]["title"] = json!(self.title);
+        if structured_output {
+            tool["outputSchema"] = json!((self.output_schema)());
+        }
+        tool
+    }
+
+    /// Parse the arguments of a call to the tool
+    ///
+    /// # Errors
+    /// Returns an invalid params error naming the first missing required
+    /// argument, or describing why the arguments do not match the schema
+    pub(super) fn arguments<T: DeserializeOwned>(&self, arguments: Value) -> Result<T, McpError> {
+        let schema = (self.input_schema)();
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str);
+        for name in required {
+            if arguments.get(name).is_none_or(Value::is_null) {
+                return Err(McpError::new(
+                    INVALID_PARAMS,
+                    format!("Missing required argument: {}", name),
+                ));
+            }
+        }
+        serde_json::from_value(arguments).map_err(|e| {
+            McpError::new(
+                INVALID_PARAMS,
+                format!("Invalid arguments for {}: {}", self.name, e),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_schemas_are_derived() {
+        let tool = ToolDescriptor::find("get_build_logs").unwrap();
+        let schema = (tool.input_schema)();
+        assert_eq!(schema.get("required"), Some(&json!(["commit_id"])));
+        let properties = schema.get("properties").unwrap();
+        assert_eq!(
+            properties["commit_id"]["description"],
+            "The commit SHA to get logs for"
+        );
+        assert!(properties["token"].is_object());
+        assert_eq!(properties["max_lines"]["minimum"], 1);
+
+        let listed = tool.to_json(false);
+        assert_eq!(listed["annotations"]["readOnlyHint"], true);
+        assert_eq!(listed["annotations"]["title"], "Get build logs");
+        assert!(listed.get("outputSchema").is_none());
+        assert!(tool.to_json(true)["outputSchema"].is_object());
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let tool = ToolDescriptor::find("get_build_logs").unwrap();
+        let arguments: BuildLogsArguments = tool
+            .arguments(json!({"commit_id": "abc", "mode": "tail", "max_lines": 5}))
+            .unwrap();
+        assert_eq!(arguments.mode, Some(LogMode::Tail));
+        assert_eq!(arguments.max_lines, Some(5));
+
+        let missing = tool.arguments::<BuildLogsArguments>(json!({"commit_id": null}));
+        assert_eq!(
+            missing.unwrap_err().message,
+            "Missing required argument: commit_id"
+        );
+        let invalid = tool
+            .arguments::<BuildLogsArguments>(json!({"commit_id": "abc", "max_lines": "many"}))
+            .unwrap_err();
+        assert_eq!(invalid.code, INVALID_PARAMS);
+        assert!(invalid
+            .message
+            .starts_with("Invalid arguments for get_build_logs"));
+        assert!(ToolDescriptor::find("rm_rf").is_none());
+        assert!(ToolDescriptor::find("summarize_failure").unwrap().opt_in);
+    }
+}
@@ -0,0 +1,132 @@
+//! Retry policy for requests made to the Garnix.io API
+
+use rand::Rng;
+use std::time::Duration;
+
+/// Retry policy applied to every request made by [`GarnixClient`](crate::GarnixClient)
+///
+/// Failed requests are retried when [`GarnixError::is_retryable`](crate::GarnixError::is_retryable)
+/// returns `true`, waiting an exponentially growing delay between attempts. A
+/// `Retry-After` header sent by the API takes precedence over the computed delay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for every subsequent retry
+    pub base_delay: Duration,
+    /// Upper bound for the computed backoff delay
+    pub max_delay: Duration,
+    /// Fraction (0.0 - 1.0) of the backoff delay that is randomised
+    pub jitter: f64,
+    /// Overall time budget for a request including all retries
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.2,
+            deadline: Some(Duration::from_secs(120)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy that never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Set the maximum number of attempts (values below 1 are treated as 1)
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the delay before the first retry
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Set the upper bound for the computed backoff delay
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set the jitter fraction, clamped to the range 0.0 - 1.0
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the overall deadline, or `None` to only limit the number of attempts
+    pub fn deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Compute the backoff delay after the given (1-based) failed attempt
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1u32 << exponent)
+            .min(self.max_delay);
+
+        if self.jitter <= 0.0 || delay.is_zero() {
+            return delay;
+        }
+
+        let factor = rand::rng().random_range(-self.jitter..=self.jitter);
+        delay.mul_f64(1.0 + factor).min(self.max_delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.max_attempts, 3);
+        assert!(policy.deadline.is_some());
+
+        assert_eq!(RetryPolicy::none().max_attempts, 1);
+        assert_eq!(RetryPolicy::none().max_attempts(0).max_attempts, 1);
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy::default()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(350))
+            .jitter(0.0);
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(40), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_backoff_jitter_stays_in_bounds() {
+        let policy = RetryPolicy::default()
+            .base_delay(Duration::from_millis(1000))
+            .jitter(0.5);
+
+        for _ in 0..100 {
+            let delay = policy.backoff(1);
+            assert!(delay >= Duration::from_millis(500));
+            assert!(delay <= Duration::from_millis(1500));
+        }
+    }
+}
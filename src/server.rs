@@ -2,21 +2,33 @@
 
 use crate::client::GarnixClient;
 use crate::error::{GarnixError, GarnixResult};
-use crate::types::BuildStatusRequest;
+use crate::mcp::{McpSessions, McpVersion};
+use crate::types::{BuildFilter, BuildStatusRequest};
 use actix_web::{
     middleware::Logger,
     web::{self, Data},
-    App, HttpResponse, HttpServer, Result as ActixResult,
+    App, HttpRequest, HttpResponse, HttpServer, Result as ActixResult,
 };
-use serde_json::json;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{error, info, warn};
 
+/// Header carrying the MCP session ID
+const MCP_SESSION_HEADER: &str = "Mcp-Session-Id";
+/// Interval between keep-alive comments on MCP event streams
+const MCP_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
 /// HTTP server for Garnix Fetcher
 pub struct GarnixHttpServer {
     client: Arc<GarnixClient>,
     bind_address: String,
     port: u16,
+    mcp_sessions: Option<McpSessions>,
+    mcp_secret: Option<String>,
 }
 
 impl Default for GarnixHttpServer {
@@ -28,11 +40,7 @@ impl Default for GarnixHttpServer {
 impl GarnixHttpServer {
     /// Create a new HTTP server instance
     pub fn new() -> Self {
-        Self {
-            client: Arc::new(GarnixClient::new()),
-            bind_address: "127.0.0.1".to_string(),
-            port: 8080,
-        }
+        Self::with_client(GarnixClient::new())
     }
 
     /// Create a new HTTP server with custom client
@@ -41,9 +49,30 @@ impl GarnixHttpServer {
             client: Arc::new(client),
             bind_address: "127.0.0.1".to_string(),
             port: 8080,
+            mcp_sessions: None,
+            mcp_secret: None,
         }
     }
 
+    /// Serve `/mcp` from an existing session registry, e.g. to set its version
+    /// and token policy or to change its tools at runtime
+    ///
+    /// Without one, sessions use the stable protocol version and the default
+    /// token policy.
+    pub fn with_mcp_sessions(mut self, sessions: McpSessions) -> Self {
+        self.mcp_sessions = Some(sessions);
+        self
+    }
+
+    /// Require `Authorization: Bearer <secret>` on every request to `/mcp`
+    ///
+    /// Sessions act with the server-side Garnix token, so [`Self::run`]
+    /// refuses to serve them on a non-loopback address without a secret.
+    pub fn mcp_secret<T: Into<String>>(mut self, secret: T) -> Self {
+        self.mcp_secret = Some(secret.into());
+        self
+    }
+
     /// Set the bind address for the server
     pub fn bind_address<T: Into<String>>(mut self, address: T) -> Self {
         self.bind_address = address.into();
@@ -57,15 +86,36 @@ impl GarnixHttpServer {
     }
 
     /// Run the HTTP server
+    ///
+    /// # Errors
+    /// Returns a configuration error if `/mcp` would expose the server-side
+    /// token on a non-loopback address without an MCP secret, or an error if
+    /// the address cannot be bound
     pub async fn run(self) -> GarnixResult<()> {
         let bind_addr = format!("{}:{}", self.bind_address, self.port);
         info!("Starting Garnix HTTP server on {}", bind_addr);
 
         let client = self.client.clone();
+        let sessions = self.mcp_sessions.clone().unwrap_or_else(|| {
+            McpSessions::new(GarnixClient::clone(&client), McpVersion::V2025_03_26)
+        });
+        if self.mcp_secret.is_none()
+            && sessions.client().token_provider().is_some()
+            && !is_loopback(&self.bind_address)
+        {
+            return Err(GarnixError::config(format!(
+                "Refusing to serve /mcp with the server-side token on {} without an MCP secret; \
+                 set one or bind to a loopback address",
+                self.bind_address
+            )));
+        }
+        let secret = McpSecret(self.mcp_secret.clone());
 
         HttpServer::new(move || {
             App::new()
                 .app_data(Data::new(client.clone()))
+                .app_data(Data::new(sessions.clone()))
+                .app_data(Data::new(secret.clone()))
                 .wrap(Logger::default())
                 .service(
                     web::scope("/api/v1")
@@ -74,8 +124,10 @@ impl GarnixHttpServer {
                         .route(
                             "/build-status/{commit_id}",
                             web::get().to(get_build_status_by_path),
-                        ),
+                        )
+                        .route("/diagnose/{commit_id}", web::get().to(diagnose)),
                 )
+                .service(mcp_resource())
                 .route("/", web::get().to(index))
                 .default_service(web::route().to(not_found))
         })
@@ -131,10 +183,8 @@ async fn get_build_status(
         })));
     }
 
-    match client
-        .fetch_build_status(&request.jwt_token, &request.commit_id)
-        .await
-    {
+    let client = GarnixClient::clone(&client).with_token(request.jwt_token.clone());
+    match client.fetch_build_status(&request.commit_id).await {
         Ok(response) => {
             info!(
                 "Successfully fetched build status for commit: {}",
@@ -142,41 +192,71 @@ async fn get_build_status(
             );
             Ok(HttpResponse::Ok().json(response))
         }
-        Err(GarnixError::AuthenticationError(msg)) => {
+        Err(e) => Ok(error_response(e)),
+    }
+}
+
+/// Map a client error to an HTTP response
+fn error_response(error: GarnixError) -> HttpResponse {
+    match error {
+        GarnixError::AuthenticationError(msg) => {
             warn!("Authentication failed: {}", msg);
-            Ok(HttpResponse::Unauthorized().json(json!({
+            HttpResponse::Unauthorized().json(json!({
                 "error": msg,
                 "code": "AUTHENTICATION_FAILED"
-            })))
+            }))
         }
-        Err(GarnixError::NotFound(msg)) => {
+        GarnixError::NotFound(msg) => {
             warn!("Resource not found: {}", msg);
-            Ok(HttpResponse::NotFound().json(json!({
+            HttpResponse::NotFound().json(json!({
                 "error": msg,
                 "code": "NOT_FOUND"
-            })))
+            }))
         }
-        Err(GarnixError::RateLimit(msg)) => {
+        GarnixError::RateLimit(msg) => {
             warn!("Rate limited: {}", msg);
-            Ok(HttpResponse::TooManyRequests().json(json!({
+            HttpResponse::TooManyRequests().json(json!({
                 "error": msg,
                 "code": "RATE_LIMITED"
-            })))
+            }))
+        }
+        GarnixError::HttpError(details) if details.is_rate_limited() => {
+            warn!("Rate limited: {}", details);
+            let mut response = HttpResponse::TooManyRequests();
+            if let Some(retry_after) = details.retry_after {
+                response.insert_header(("Retry-After", retry_after.as_secs().to_string()));
+            }
+            response.json(json!({
+                "error": "Rate limit exceeded",
+                "code": "RATE_LIMITED",
+                "retry_after_secs": details.retry_after.map(|d| d.as_secs()),
+                "request_id": details.request_id
+            }))
         }
-        Err(GarnixError::NetworkError(msg)) => {
+        GarnixError::HttpError(details) => {
+            error!("Upstream error: {}", details);
+            HttpResponse::BadGateway().json(json!({
+                "error": "Garnix API returned an error",
+                "code": "UPSTREAM_ERROR",
+                "upstream_status": details.status,
+                "request_id": details.request_id,
+                "details": details.body
+            }))
+        }
+        GarnixError::NetworkError(msg) => {
             error!("Network error: {}", msg);
-            Ok(HttpResponse::BadGateway().json(json!({
+            HttpResponse::BadGateway().json(json!({
                 "error": "Failed to connect to Garnix API",
                 "code": "NETWORK_ERROR",
                 "details": msg
-            })))
+            }))
         }
-        Err(e) => {
+        e => {
             error!("Unexpected error: {}", e);
-            Ok(HttpResponse::InternalServerError().json(json!({
+            HttpResponse::InternalServerError().json(json!({
                 "error": "Internal server error",
                 "code": "INTERNAL_ERROR"
-            })))
+            }))
         }
     }
 }
@@ -220,6 +300,388 @@ async fn get_build_status_by_path(
     get_build_status(client, web::Json(request)).await
 }
 
+/// Classify the failed builds of a commit from their logs
+///
+/// Accepts `token` and optional `package` and `system` query parameters.
+async fn diagnose(
+    client: Data<Arc<GarnixClient>>,
+    path: web::Path<String>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> ActixResult<HttpResponse> {
+    let commit_id = path.into_inner();
+    info!("Failure diagnosis requested for commit: {}", commit_id);
+
+    let jwt_token = match query.get("token") {
+        Some(token) if !token.is_empty() => token,
+        _ => {
+            warn!("Missing JWT token in query parameters");
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "JWT token is required as 'token' query parameter",
+                "code": "MISSING_TOKEN"
+            })));
+        }
+    };
+
+    if !commit_id.chars().all(|c| c.is_ascii_hexdigit()) || commit_id.len() < 7 {
+        warn!("Invalid commit ID format: {}", commit_id);
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid commit ID format",
+            "code": "INVALID_COMMIT_ID"
+        })));
+    }
+
+    let filter = BuildFilter {
+        package: query.get("package").cloned(),
+        system: query.get("system").cloned(),
+        failed_only: true,
+    };
+    let client = GarnixClient::clone(&client).with_token(jwt_token.clone());
+    match client.diagnose_commit(&commit_id, &filter).await {
+        Ok(failures) => {
+            info!(
+                "Diagnosed {} failure(s) for commit: {}",
+                failures.len(),
+                commit_id
+            );
+            Ok(HttpResponse::Ok().json(json!({
+                "commit_id": commit_id,
+                "failures": failures
+            })))
+        }
+        Err(e) => Ok(error_response(e)),
+    }
+}
+
+/// The `/mcp` endpoint implementing the MCP streamable HTTP transport
+fn mcp_resource() -> actix_web::Resource {
+    web::resource("/mcp")
+        .route(web::post().to(mcp_post))
+        .route(web::get().to(mcp_events))
+        .route(web::delete().to(mcp_delete))
+}
+
+/// JSON-RPC error response for MCP transport failures
+fn mcp_error(mut response: actix_web::HttpResponseBuilder, message: &str) -> HttpResponse {
+    response.json(json!({
+        "jsonrpc": "2.0",
+        "id": null,
+        "error": { "code": -32600, "message": message }
+    }))
+}
+
+/// Secret that requests to `/mcp` must present as bearer token, if any
+#[derive(Clone)]
+struct McpSecret(Option<String>);
+
+/// Check if an address to bind to only accepts local connections
+fn is_loopback(address: &str) -> bool {
+    address == "localhost"
+        || address
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Check the bearer token of a request to `/mcp` against the configured secret
+fn mcp_authorized(req: &HttpRequest, secret: &McpSecret) -> bool {
+    let Some(secret) = &secret.0 else {
+        return true;
+    };
+    let presented = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Compare in constant time to not leak the secret through timing
+    presented.len() == secret.len()
+        && presented
+            .bytes()
+            .zip(secret.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Check the origin and bearer token of a request to `/mcp`
+fn mcp_guard(req: &HttpRequest, secret: &McpSecret) -> Result<(), HttpResponse> {
+    if !mcp_origin_allowed(req) {
+        warn!("Rejecting MCP request from foreign origin");
+        return Err(mcp_error(HttpResponse::Forbidden(), "Origin not allowed"));
+    }
+    if !mcp_authorized(req, secret) {
+        warn!("Rejecting MCP request without a valid bearer token");
+        let mut response = HttpResponse::Unauthorized();
+        response.insert_header(("WWW-Authenticate", "Bearer"));
+        return Err(mcp_error(response, "Missing or invalid bearer token"));
+    }
+    Ok(())
+}
+
+/// Reject cross-origin browser requests to guard against DNS rebinding
+fn mcp_origin_allowed(req: &HttpRequest) -> bool {
+    let Some(origin) = req.headers().get("Origin") else {
+        return true;
+    };
+    let origin = origin.to_str().unwrap_or_default();
+    let origin_host = origin.split_once("://").map_or(origin, |(_, host)| host);
+    origin_host == req.connection_info().host()
+}
+
+/// Look up the session named by the `Mcp-Session-Id` header
+fn mcp_session(
+    req: &HttpRequest,
+    sessions: &McpSessions,
+) -> Result<(String, Arc<crate::mcp::GarnixMcpServer>), HttpResponse> {
+    let id = match req.headers().get(MCP_SESSION_HEADER) {
+        Some(id) => id.to_str().unwrap_or_default().to_string(),
+        None => {
+            return Err(mcp_error(
+                HttpResponse::BadRequest(),
+                "Missing Mcp-Session-Id header",
+            ))
+        }
+    };
+    match sessions.get(&id) {
+        Some(session) => Ok((id, session)),
+        None => Err(mcp_error(HttpResponse::NotFound(), "Unknown MCP session")),
+    }
+}
+
+/// Handle a JSON-RPC message posted to the MCP endpoint
+///
+/// An `initialize` request without a session starts a new one; every other
+/// message must carry the `Mcp-Session-Id` returned by it.
+async fn mcp_post(
+    req: HttpRequest,
+    sessions: Data<McpSessions>,
+    secret: Data<McpSecret>,
+    body: web::Bytes,
+) -> ActixResult<HttpResponse> {
+    if let Err(response) = mcp_guard(&req, &secret) {
+        return Ok(response);
+    }
+
+    let message = String::from_utf8_lossy(&body);
+    let parsed = serde_json::from_str::<Value>(&message);
+    let is_initialize = parsed
+        .as_ref()
+        .is_ok_and(|value| value.get("method").and_then(Value::as_str) == Some("initialize"));
+
+    let created = is_initialize && req.headers().get(MCP_SESSION_HEADER).is_none();
+    let (session_id, session) = if created {
+        match sessions.create() {
+            Ok((id, session)) => {
+                info!("Started MCP session {}", id);
+                (id, session)
+            }
+            Err(e) => {
+                warn!("Cannot start MCP session: {}", e);
+                return Ok(mcp_error(
+                    HttpResponse::ServiceUnavailable(),
+                    "Too many MCP sessions",
+                ));
+            }
+        }
+    } else {
+        match mcp_session(&req, &sessions) {
+            Ok(session) => session,
+            Err(response) => return Ok(response),
+        }
+    };
+
+    // Requests asking for progress are answered with an event stream carrying
+    // their progress notifications, then the response
+    let wants_progress = parsed.as_ref().is_ok_and(|value| {
+        value.get("id").is_some() && value.pointer("/params/_meta/progressToken").is_some()
+    });
+    if wants_progress && !created && accepts_events(&req) {
+        return Ok(mcp_request_events(
+            session,
+            session_id,
+            message.into_owned(),
+        ));
+    }
+
+    // Notifications and responses are acknowledged without a body
+    let Some(reply) = session.handle_message(&message).await else {
+        if created {
+            sessions.remove(&session_id);
+        }
+        return Ok(HttpResponse::Accepted().finish());
+    };
+    // A session whose initialization failed cannot be used any further
+    if created
+        && serde_json::from_str::<Value>(&reply).is_ok_and(|reply| reply.get("error").is_some())
+    {
+        sessions.remove(&session_id);
+        return Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(reply));
+    }
+    let mut response = if parsed.is_err() {
+        HttpResponse::BadRequest()
+    } else {
+        HttpResponse::Ok()
+    };
+    Ok(response
+        .insert_header((MCP_SESSION_HEADER, session_id))
+        .content_type("application/json")
+        .body(reply))
+}
+
+/// Check if the client accepts a server-sent event stream
+fn accepts_events(req: &HttpRequest) -> bool {
+    req.headers()
+        .get("Accept")
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"))
+}
+
+/// Format a JSON-RPC message as a server-sent event
+fn mcp_event(message: &str) -> web::Bytes {
+    web::Bytes::from(format!("event: message\ndata: {}\n\n", message))
+}
+
+/// A request being answered with an event stream
+///
+/// Dropping it, e.g. when the client disconnects, drops the request's future
+/// and with it the request.
+struct RequestEvents {
+    reply: Option<Pin<Box<dyn Future<Output = Option<String>>>>>,
+    response: Option<String>,
+    related: mpsc::UnboundedReceiver<String>,
+}
+
+impl RequestEvents {
+    /// The next message of the stream: related messages in order, then the response
+    async fn next_message(&mut self) -> Option<String> {
+        loop {
+            let Some(reply) = self.reply.as_mut() else {
+                return self
+                    .related
+                    .try_recv()
+                    .ok()
+                    .or_else(|| self.response.take());
+            };
+            tokio::select! {
+                biased;
+                Some(message) = self.related.recv() => return Some(message),
+                response = reply => {
+                    self.reply = None;
+                    self.response = response;
+                }
+            }
+        }
+    }
+}
+
+/// Answer a request with an event stream of the messages related to it, then its response
+fn mcp_request_events(
+    session: Arc<crate::mcp::GarnixMcpServer>,
+    session_id: String,
+    message: String,
+) -> HttpResponse {
+    let (sender, related) = mpsc::unbounded_channel();
+    let reply = async move { session.handle_message_streaming(&message, sender).await };
+    let events = futures::stream::unfold(
+        RequestEvents {
+            reply: Some(Box::pin(reply)),
+            response: None,
+            related,
+        },
+        |mut events| async move {
+            let message = events.next_message().await?;
+            Some((Ok::<_, actix_web::Error>(mcp_event(&message)), events))
+        },
+    );
+
+    HttpResponse::Ok()
+        .insert_header((MCP_SESSION_HEADER, session_id))
+        .insert_header(("Cache-Control", "no-cache"))
+        .content_type("text/event-stream")
+        .streaming(events)
+}
+
+/// Open a server-sent event stream for messages the session sends on its own
+async fn mcp_events(
+    req: HttpRequest,
+    sessions: Data<McpSessions>,
+    secret: Data<McpSecret>,
+) -> ActixResult<HttpResponse> {
+    if let Err(response) = mcp_guard(&req, &secret) {
+        return Ok(response);
+    }
+    if !accepts_events(&req) {
+        return Ok(mcp_error(
+            HttpResponse::NotAcceptable(),
+            "Event stream requires Accept: text/event-stream",
+        ));
+    }
+    let (session_id, session) = match mcp_session(&req, &sessions) {
+        Ok(session) => session,
+        Err(response) => return Ok(response),
+    };
+    info!("Opening MCP event stream for session {}", session_id);
+
+    let keep_alive =
+        tokio::time::interval_at(tokio::time::Instant::now() + MCP_KEEP_ALIVE, MCP_KEEP_ALIVE);
+    let events = futures::stream::unfold(
+        (
+            session.subscribe(),
+            session.subscribe_requests(),
+            keep_alive,
+        ),
+        |(mut messages, mut requests, mut keep_alive)| async move {
+            let event = loop {
+                tokio::select! {
+                    message = messages.recv() => match message {
+                        Ok(message) => break mcp_event(&message),
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            warn!("MCP event stream dropped {} messages", skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    },
+                    request = requests.recv() => match request {
+                        Some(request) => break mcp_event(&request),
+                        None => return None,
+                    },
+                    _ = keep_alive.tick() => break web::Bytes::from(": keep-alive\n\n"),
+                }
+            };
+            Some((
+                Ok::<_, actix_web::Error>(event),
+                (messages, requests, keep_alive),
+            ))
+        },
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header((MCP_SESSION_HEADER, session_id))
+        .insert_header(("Cache-Control", "no-cache"))
+        .content_type("text/event-stream")
+        .streaming(events))
+}
+
+/// End an MCP session
+async fn mcp_delete(
+    req: HttpRequest,
+    sessions: Data<McpSessions>,
+    secret: Data<McpSecret>,
+) -> ActixResult<HttpResponse> {
+    if let Err(response) = mcp_guard(&req, &secret) {
+        return Ok(response);
+    }
+    match mcp_session(&req, &sessions) {
+        Ok((session_id, _)) => {
+            sessions.remove(&session_id);
+            info!("Ended MCP session {}", session_id);
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(response) => Ok(response),
+    }
+}
+
 /// Root endpoint with API documentation
 async fn index() -> ActixResult<HttpResponse> {
     let html = r#"
@@ -262,6 +724,21 @@ async fn index() -> ActixResult<HttpResponse> {
             <pre>curl "http://localhost:8080/api/v1/build-status/abc123...?token=your-jwt-token"</pre>
         </div>
         
+        <div class="endpoint">
+            <h3>GET /api/v1/diagnose/{commit_id}?token=jwt_token[&amp;package=name][&amp;system=system]</h3>
+            <p>Classify the failed builds of a commit (hash mismatch, OOM, timeout, test failure, ...) from their logs</p>
+            <pre>curl "http://localhost:8080/api/v1/diagnose/abc123...?token=your-jwt-token"</pre>
+        </div>
+        
+        <div class="endpoint">
+            <h3>POST | GET | DELETE /mcp</h3>
+            <p>Model Context Protocol over the streamable HTTP transport. POST an <code>initialize</code> request to start a session, then send its <code>Mcp-Session-Id</code> header with every message. GET opens an event stream for server messages, DELETE ends the session.</p>
+            <pre>curl -i -X POST http://localhost:8080/mcp \
+  -H "Content-Type: application/json" \
+  -H "Accept: application/json, text/event-stream" \
+  -d '{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "2025-03-26", "capabilities": {}, "clientInfo": {"name": "curl", "version": "1"}}}'</pre>
+        </div>
+        
         <h2>Response Format</h2>
         <p>Successful responses return JSON with build summary and individual build details:</p>
         <pre>{
@@ -312,7 +789,11 @@ async fn not_found() -> ActixResult<HttpResponse> {
             "GET /",
             "GET /api/v1/health",
             "POST /api/v1/build-status",
-            "GET /api/v1/build-status/{commit_id}"
+            "GET /api/v1/build-status/{commit_id}",
+            "GET /api/v1/diagnose/{commit_id}",
+            "POST /mcp",
+            "GET /mcp",
+            "DELETE /mcp"
         ]
     })))
 }
@@ -335,8 +816,10 @@ mod tests {
                         .route(
                             "/build-status/{commit_id}",
                             web::get().to(get_build_status_by_path),
-                        ),
+                        )
+                        .route("/diagnose/{commit_id}", web::get().to(diagnose)),
                 )
+                .service(mcp_resource())
                 .route("/", web::get().to(index))
                 .default_service(web::route().to(not_found)),
         )
@@ -497,6 +980,387 @@ mod tests {
         assert_eq!(body["code"], "MISSING_TOKEN");
     }
 
+    #[actix_web::test]
+    async fn test_build_status_upstream_error() {
+        let mut upstream = mockito::Server::new_async().await;
+        let _mock = upstream
+            .mock("GET", "/builds/7f1a9b3c5e2d8f4a6c1b9e8d3f7a2c6b9e4d8f1a")
+            .with_status(500)
+            .with_header("x-request-id", "req-9")
+            .create_async()
+            .await;
+
+        let client = Arc::new(
+            GarnixClient::with_base_url(upstream.url())
+                .with_retry_policy(crate::retry::RetryPolicy::none()),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(client))
+                .route("/build-status", web::post().to(get_build_status)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/build-status")
+            .set_json(json!({
+                "jwt_token": "test-token",
+                "commit_id": "7f1a9b3c5e2d8f4a6c1b9e8d3f7a2c6b9e4d8f1a"
+            }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 502);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "UPSTREAM_ERROR");
+        assert_eq!(body["upstream_status"], 500);
+        assert_eq!(body["request_id"], "req-9");
+    }
+
+    #[actix_web::test]
+    async fn test_diagnose_validation_and_errors() {
+        let mut upstream = mockito::Server::new_async().await;
+        let _mock = upstream
+            .mock("GET", "/builds/7f1a9b3c5e2d8f4a6c1b9e8d3f7a2c6b9e4d8f1a")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let client = Arc::new(
+            GarnixClient::with_base_url(upstream.url())
+                .with_retry_policy(crate::retry::RetryPolicy::none()),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(client))
+                .route("/diagnose/{commit_id}", web::get().to(diagnose)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/diagnose/7f1a9b3c5e2d8f4a6c1b9e8d3f7a2c6b9e4d8f1a")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+
+        let req = test::TestRequest::get()
+            .uri("/diagnose/not-a-commit?token=test-token")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "INVALID_COMMIT_ID");
+
+        let req = test::TestRequest::get()
+            .uri("/diagnose/7f1a9b3c5e2d8f4a6c1b9e8d3f7a2c6b9e4d8f1a?token=test-token")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn test_mcp_http_session() {
+        let sessions = McpSessions::new(GarnixClient::new(), McpVersion::V2025_03_26);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(sessions.clone()))
+                .app_data(Data::new(McpSecret(None)))
+                .service(mcp_resource()),
+        )
+        .await;
+
+        // Everything but initialize needs a session
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .set_json(json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .set_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {"protocolVersion": "2025-03-26"}
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let session_id = resp
+            .headers()
+            .get(MCP_SESSION_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["result"]["protocolVersion"], "2025-03-26");
+
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .insert_header((MCP_SESSION_HEADER, session_id.as_str()))
+            .set_json(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 202);
+
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .insert_header((MCP_SESSION_HEADER, session_id.as_str()))
+            .set_json(json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["result"]["tools"].is_array());
+
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .insert_header((MCP_SESSION_HEADER, session_id.as_str()))
+            .set_payload("{not json")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+
+        let req = test::TestRequest::get()
+            .uri("/mcp")
+            .insert_header((MCP_SESSION_HEADER, session_id.as_str()))
+            .insert_header(("Accept", "text/event-stream"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+
+        let req = test::TestRequest::delete()
+            .uri("/mcp")
+            .insert_header((MCP_SESSION_HEADER, session_id.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 204);
+        assert!(sessions.is_empty());
+
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .insert_header((MCP_SESSION_HEADER, session_id.as_str()))
+            .set_json(json!({"jsonrpc": "2.0", "id": 3, "method": "ping"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn test_mcp_http_rejects_foreign_origin() {
+        let sessions = McpSessions::new(GarnixClient::new(), McpVersion::V2025_03_26);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(sessions.clone()))
+                .app_data(Data::new(McpSecret(None)))
+                .service(mcp_resource()),
+        )
+        .await;
+
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"});
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .insert_header(("Host", "localhost:8080"))
+            .insert_header(("Origin", "https://evil.example"))
+            .set_json(&initialize)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+        assert!(sessions.is_empty());
+
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .insert_header(("Host", "localhost:8080"))
+            .insert_header(("Origin", "http://localhost:8080"))
+            .set_json(&initialize)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[actix_web::test]
+    async fn test_mcp_http_requires_secret() {
+        let sessions = McpSessions::new(GarnixClient::new(), McpVersion::V2025_03_26);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(sessions.clone()))
+                .app_data(Data::new(McpSecret(Some("s3cret".to_string()))))
+                .service(mcp_resource()),
+        )
+        .await;
+
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"});
+        for authorization in [None, Some("Bearer wrong"), Some("s3cret")] {
+            let mut req = test::TestRequest::post().uri("/mcp").set_json(&initialize);
+            if let Some(authorization) = authorization {
+                req = req.insert_header(("Authorization", authorization));
+            }
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), 401);
+            assert_eq!(resp.headers().get("WWW-Authenticate").unwrap(), "Bearer");
+        }
+        assert!(sessions.is_empty());
+
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .insert_header(("Authorization", "Bearer s3cret"))
+            .set_json(&initialize)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(sessions.len(), 1);
+    }
+
+    /// Build status of a commit with a single build in the given state
+    fn single_build_status(status: &str, pending: u32) -> String {
+        json!({
+            "summary": {
+                "repo_owner": "testuser", "repo_name": "testrepo", "repo_is_public": true,
+                "git_commit": "abc1234", "branch": "main", "req_user": "testuser",
+                "start_time": "2024-01-01T00:00:00Z", "succeeded": 1 - pending, "failed": 0,
+                "pending": pending, "cancelled": 0
+            },
+            "builds": [{
+                "id": "b-1", "repo_user": "testuser", "repo_name": "testrepo", "branch": "main",
+                "repo_is_public": true, "git_commit": "abc1234", "package": "app",
+                "package_type": "derivation", "system": "x86_64-linux", "req_user": "testuser",
+                "status": status, "start_time": "2024-01-01T00:00:00Z", "end_time": null,
+                "drv_path": null, "output_paths": null, "github_run_id": 1,
+                "wants_incrementalism": false, "eval_host": "eval", "uploaded_to_cache": false
+            }]
+        })
+        .to_string()
+    }
+
+    #[actix_web::test]
+    async fn test_mcp_http_progress_events() {
+        let mut upstream = mockito::Server::new_async().await;
+        let _pending = upstream
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(single_build_status("Pending", 1))
+            .expect(1)
+            .create_async()
+            .await;
+        let _settled = upstream
+            .mock("GET", "/builds/abc1234")
+            .with_status(200)
+            .with_body(single_build_status("Success", 0))
+            .create_async()
+            .await;
+        let client = GarnixClient::with_base_url(upstream.url()).with_token("test-token");
+        let sessions = McpSessions::new(client, McpVersion::V2025_06_18);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(sessions.clone()))
+                .app_data(Data::new(McpSecret(None)))
+                .service(mcp_resource()),
+        )
+        .await;
+
+        let (session_id, session) = sessions.create().unwrap();
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"});
+        session.handle_message(&initialize.to_string()).await;
+        let initialized = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        session.handle_message(&initialized.to_string()).await;
+        let mut subscriber = session.subscribe();
+
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .insert_header((MCP_SESSION_HEADER, session_id.as_str()))
+            .insert_header(("Accept", "application/json, text/event-stream"))
+            .set_json(json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/call",
+                "params": {
+                    "name": "wait_for_builds",
+                    "arguments": { "commit_id": "abc1234", "interval_secs": 1 },
+                    "_meta": { "progressToken": "p-1" }
+                }
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+        let body = test::read_body(resp).await;
+        let events: Vec<Value> = String::from_utf8_lossy(&body)
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| serde_json::from_str(data).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["method"], "notifications/progress");
+        assert_eq!(events[0]["params"]["progressToken"], "p-1");
+        assert_eq!(events[1]["id"], 2);
+        assert!(events[1]["result"]["content"].is_array());
+
+        // The progress went to the request's stream only
+        assert!(subscriber.try_recv().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_mcp_http_session_limits() {
+        let sessions =
+            McpSessions::new(GarnixClient::new(), McpVersion::V2025_03_26).with_max_sessions(1);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(sessions.clone()))
+                .app_data(Data::new(McpSecret(None)))
+                .service(mcp_resource()),
+        )
+        .await;
+
+        // A failed initialize does not leave a session behind
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .set_json(json!({"jsonrpc": "2.0", "id": true, "method": "initialize"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.headers().get(MCP_SESSION_HEADER).is_none());
+        assert!(sessions.is_empty());
+
+        let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize"});
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .set_json(&initialize)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+        let req = test::TestRequest::post()
+            .uri("/mcp")
+            .set_json(&initialize)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 503);
+    }
+
+    #[tokio::test]
+    async fn test_server_refuses_exposed_token() {
+        let result = GarnixHttpServer::with_client(GarnixClient::new().with_token("token"))
+            .bind_address("0.0.0.0")
+            .port(0)
+            .run()
+            .await;
+        assert!(matches!(result, Err(GarnixError::ConfigError(_))));
+
+        assert!(is_loopback("127.0.0.1"));
+        assert!(is_loopback("::1"));
+        assert!(is_loopback("[::1]"));
+        assert!(is_loopback("localhost"));
+        assert!(!is_loopback("0.0.0.0"));
+        assert!(!is_loopback("192.168.1.10"));
+    }
+
     #[tokio::test]
     async fn test_server_creation() {
         let server = GarnixHttpServer::new();
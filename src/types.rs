@@ -1,5 +1,6 @@
 //! Type definitions for Garnix API responses and requests
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -13,7 +14,7 @@ pub struct GarnixRequest {
 }
 
 /// Summary information for a build
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, schemars::JsonSchema)]
 pub struct Summary {
     /// Repository owner/organization
     pub repo_owner: String,
@@ -27,8 +28,9 @@ pub struct Summary {
     pub branch: String,
     /// User who requested the build
     pub req_user: String,
-    /// Build start time
-    pub start_time: String,
+    /// Build start time, or `None` before the first build was queued
+    #[serde(default, deserialize_with = "optional_datetime")]
+    pub start_time: Option<DateTime<Utc>>,
     /// Number of successful builds
     pub succeeded: u32,
     /// Number of failed builds
@@ -40,7 +42,7 @@ pub struct Summary {
 }
 
 /// Individual build information
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, schemars::JsonSchema)]
 pub struct Build {
     /// Unique build ID
     pub id: String,
@@ -64,10 +66,12 @@ pub struct Build {
     pub req_user: String,
     /// Build status ("Success", "Failed", "Pending", etc.)
     pub status: String,
-    /// Build start time
-    pub start_time: String,
-    /// Build end time
-    pub end_time: String,
+    /// Build start time, or `None` while the build is still queued
+    #[serde(default, deserialize_with = "optional_datetime")]
+    pub start_time: Option<DateTime<Utc>>,
+    /// Build end time, or `None` while the build is still running
+    #[serde(default, deserialize_with = "optional_datetime")]
+    pub end_time: Option<DateTime<Utc>>,
     /// Nix derivation path
     pub drv_path: Option<String>,
     /// Map of output names to store paths
@@ -83,7 +87,7 @@ pub struct Build {
 }
 
 /// Log entry from build logs
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, schemars::JsonSchema)]
 pub struct LogEntry {
     /// Timestamp of the log entry
     pub timestamp: String,
@@ -92,7 +96,7 @@ pub struct LogEntry {
 }
 
 /// Response from the logs API endpoint
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, schemars::JsonSchema)]
 pub struct LogResponse {
     /// Whether the build has finished
     pub finished: bool,
@@ -100,15 +104,203 @@ pub struct LogResponse {
     pub logs: Vec<LogEntry>,
 }
 
+/// Logs of a single build together with the build they belong to
+#[derive(Debug, Serialize, Clone, schemars::JsonSchema)]
+pub struct BuildLogs {
+    /// The build the logs belong to
+    pub build: Build,
+    /// The build's logs
+    pub logs: LogResponse,
+}
+
+/// Criteria for selecting builds of a commit
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildFilter {
+    /// Only builds of this package
+    pub package: Option<String>,
+    /// Only builds for this system (e.g. "x86_64-linux")
+    pub system: Option<String>,
+    /// Only failed builds
+    pub failed_only: bool,
+}
+
+impl BuildFilter {
+    /// Create a filter matching all builds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match builds of the given package
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.package = Some(package.into());
+        self
+    }
+
+    /// Only match builds for the given system
+    pub fn system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    /// Only match failed builds
+    pub fn failed_only(mut self, failed_only: bool) -> Self {
+        self.failed_only = failed_only;
+        self
+    }
+
+    /// Check if a build matches all criteria
+    pub fn matches(&self, build: &Build) -> bool {
+        self.package
+            .as_ref()
+            .is_none_or(|package| &build.package == package)
+            && self
+                .system
+                .as_ref()
+                .is_none_or(|system| build.system.as_ref() == Some(system))
+            && (!self.failed_only || build.is_failed())
+    }
+}
+
 /// Main response structure from Garnix API
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, schemars::JsonSchema)]
 pub struct GarnixResponse {
     /// Build summary information
     pub summary: Summary,
     /// List of individual builds
     pub builds: Vec<Build>,
-    /// Additional run information (raw JSON values)
-    pub runs: Vec<serde_json::Value>,
+    /// Evaluation runs that produced the builds
+    #[serde(default)]
+    pub runs: Vec<Run>,
+}
+
+/// Build status annotated with durations, as reported in JSON output
+#[derive(Debug, Serialize, Clone, schemars::JsonSchema)]
+pub struct BuildStatusReport {
+    /// Build summary information
+    pub summary: Summary,
+    /// List of individual builds with their durations
+    pub builds: Vec<TimedBuild>,
+    /// Evaluation runs that produced the builds
+    pub runs: Vec<Run>,
+    /// Time from the first build start to the last build end, e.g. `3m12s`
+    pub wall_clock: Option<String>,
+    /// [`Self::wall_clock`] in seconds
+    pub wall_clock_secs: Option<i64>,
+}
+
+/// A build annotated with its duration
+#[derive(Debug, Serialize, Clone, schemars::JsonSchema)]
+pub struct TimedBuild {
+    /// The build
+    #[serde(flatten)]
+    pub build: Build,
+    /// How long the build took, e.g. `3m12s` (`null` while running)
+    pub duration: Option<String>,
+    /// [`Self::duration`] in seconds
+    pub duration_secs: Option<i64>,
+}
+
+impl From<&GarnixResponse> for BuildStatusReport {
+    fn from(response: &GarnixResponse) -> Self {
+        let wall_clock = response.wall_clock();
+        Self {
+            summary: response.summary.clone(),
+            builds: response
+                .builds
+                .iter()
+                .map(|build| {
+                    let duration = build.duration();
+                    TimedBuild {
+                        build: build.clone(),
+                        duration: duration.map(crate::format_duration),
+                        duration_secs: duration.map(|d| d.num_seconds()),
+                    }
+                })
+                .collect(),
+            runs: response.runs.clone(),
+            wall_clock: wall_clock.map(crate::format_duration),
+            wall_clock_secs: wall_clock.map(|d| d.num_seconds()),
+        }
+    }
+}
+
+/// Evaluation run (e.g. a flake check run) that produced builds for a commit
+///
+/// The shape of runs is not documented, so every field is optional and unknown
+/// fields are kept in `extra`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq, schemars::JsonSchema)]
+#[serde(default)]
+pub struct Run {
+    /// Unique run ID
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: Option<String>,
+    /// GitHub Actions run ID linking the run to its builds
+    pub github_run_id: Option<u64>,
+    /// Name of the check or evaluation step
+    #[serde(alias = "check_name")]
+    pub name: Option<String>,
+    /// Run status ("Success", "Failed", "Pending", etc.)
+    pub status: Option<String>,
+    /// Run start time
+    #[serde(deserialize_with = "optional_datetime")]
+    pub start_time: Option<DateTime<Utc>>,
+    /// Run end time
+    #[serde(deserialize_with = "optional_datetime")]
+    pub end_time: Option<DateTime<Utc>>,
+    /// Error message, e.g. from a failed evaluation
+    #[serde(alias = "eval_error")]
+    pub error: Option<String>,
+    /// Any other fields returned by the API
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Accept a missing, `null` or empty timestamp as `None`
+fn optional_datetime<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) if !s.trim().is_empty() => s
+            .parse::<DateTime<Utc>>()
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+/// Accept an ID given either as a string or as a number
+fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::String(s)) => Some(s),
+            Some(serde_json::Value::Null) | None => None,
+            Some(other) => Some(other.to_string()),
+        },
+    )
+}
+
+/// Account information returned by the `/user` endpoint
+///
+/// All fields are optional since the endpoint is undocumented; unknown fields
+/// are kept in `extra`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct UserInfo {
+    /// Login name of the account
+    #[serde(default, alias = "login", skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Display name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// E-mail address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Any other fields returned by the API
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Request structure for the HTTP API endpoint
@@ -159,6 +351,34 @@ impl std::fmt::Display for BuildStatus {
     }
 }
 
+/// A change in a build's status observed between two polls
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct BuildTransition {
+    /// Unique build ID
+    pub build_id: String,
+    /// Package/derivation name
+    pub package: String,
+    /// Target system (e.g., "x86_64-linux")
+    pub system: Option<String>,
+    /// Previous status, or `None` if the build was not known before
+    pub from: Option<String>,
+    /// New status
+    pub to: String,
+}
+
+impl std::fmt::Display for BuildTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) went {} → {}",
+            self.package,
+            self.system.as_deref().unwrap_or("unknown"),
+            self.from.as_deref().unwrap_or("new"),
+            self.to
+        )
+    }
+}
+
 impl Build {
     /// Get the build status as a typed enum
     pub fn status_enum(&self) -> BuildStatus {
@@ -180,6 +400,12 @@ impl Build {
         matches!(self.status_enum(), BuildStatus::Pending)
     }
 
+    /// Time the build took, or `None` while it is still queued or running
+    pub fn duration(&self) -> Option<chrono::Duration> {
+        let (start_time, end_time) = self.start_time.zip(self.end_time)?;
+        Some((end_time - start_time).max(chrono::Duration::zero()))
+    }
+
     /// Get a human-readable status with emoji
     pub fn status_with_emoji(&self) -> String {
         let emoji = match self.status_enum() {
@@ -193,7 +419,69 @@ impl Build {
     }
 }
 
+impl Run {
+    /// Get the run status as a typed enum
+    pub fn status_enum(&self) -> BuildStatus {
+        BuildStatus::from(self.status.as_deref().unwrap_or("Unknown"))
+    }
+
+    /// Check if the run failed, either by status or by reporting an error
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status_enum(), BuildStatus::Failed) || self.error.is_some()
+    }
+
+    /// Check if the run is still in progress
+    pub fn is_pending(&self) -> bool {
+        matches!(self.status_enum(), BuildStatus::Pending)
+    }
+
+    /// Check if the run has finished, successfully or not
+    pub fn is_finished(&self) -> bool {
+        self.is_failed()
+            || self.end_time.is_some()
+            || matches!(
+                self.status_enum(),
+                BuildStatus::Success | BuildStatus::Cancelled
+            )
+    }
+
+    /// Short name identifying the run in summaries
+    pub fn label(&self) -> String {
+        match (&self.name, &self.id, self.github_run_id) {
+            (Some(name), _, _) => name.clone(),
+            (None, Some(id), _) => format!("run {}", id),
+            (None, None, Some(github_run_id)) => format!("GitHub run {}", github_run_id),
+            (None, None, None) => "run".to_string(),
+        }
+    }
+}
+
 impl GarnixResponse {
+    /// Get all failed runs, such as evaluation errors
+    pub fn failed_runs(&self) -> Vec<&Run> {
+        self.runs.iter().filter(|run| run.is_failed()).collect()
+    }
+
+    /// Get the builds produced by a run, linked via the GitHub run ID
+    pub fn builds_for_run(&self, run: &Run) -> Vec<&Build> {
+        match run.github_run_id {
+            Some(github_run_id) => self
+                .builds
+                .iter()
+                .filter(|build| build.github_run_id == github_run_id)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Get all builds matching a filter
+    pub fn filter_builds(&self, filter: &BuildFilter) -> Vec<&Build> {
+        self.builds
+            .iter()
+            .filter(|build| filter.matches(build))
+            .collect()
+    }
+
     /// Get all failed builds
     pub fn failed_builds(&self) -> Vec<&Build> {
         self.builds
@@ -226,9 +514,65 @@ impl GarnixResponse {
         (self.summary.succeeded as f64 / self.builds.len() as f64) * 100.0
     }
 
-    /// Check if all builds were successful
+    /// Check if all builds and runs were successful
     pub fn all_successful(&self) -> bool {
-        self.summary.failed == 0 && self.summary.cancelled == 0 && self.summary.pending == 0
+        self.summary.failed == 0
+            && self.summary.cancelled == 0
+            && self.summary.pending == 0
+            && self.failed_runs().is_empty()
+    }
+
+    /// Time from the start of the commit's builds until the last build finished
+    ///
+    /// Returns `None` if no build has finished yet.
+    pub fn wall_clock(&self) -> Option<chrono::Duration> {
+        let finished = self
+            .builds
+            .iter()
+            .filter_map(|build| build.end_time)
+            .max()?;
+        let started = self
+            .builds
+            .iter()
+            .filter_map(|build| build.start_time)
+            .chain(self.summary.start_time)
+            .min()?;
+        Some((finished - started).max(chrono::Duration::zero()))
+    }
+
+    /// Check if evaluation produced builds or finished, and no builds are pending anymore
+    ///
+    /// A commit without builds and without a finished run has not been
+    /// evaluated yet, so it is not settled even though nothing is pending.
+    pub fn is_settled(&self) -> bool {
+        self.summary.pending == 0
+            && (!self.builds.is_empty() || self.runs.iter().any(Run::is_finished))
+    }
+
+    /// List the builds whose status changed since a previous response
+    pub fn transitions_since(&self, previous: &GarnixResponse) -> Vec<BuildTransition> {
+        let previous: HashMap<&str, &str> = previous
+            .builds
+            .iter()
+            .map(|build| (build.id.as_str(), build.status.as_str()))
+            .collect();
+
+        self.builds
+            .iter()
+            .filter_map(|build| {
+                let from = previous.get(build.id.as_str()).copied();
+                if from == Some(build.status.as_str()) {
+                    return None;
+                }
+                Some(BuildTransition {
+                    build_id: build.id.clone(),
+                    package: build.package.clone(),
+                    system: build.system.clone(),
+                    from: from.map(str::to_string),
+                    to: build.status.clone(),
+                })
+            })
+            .collect()
     }
 }
 
@@ -272,8 +616,8 @@ mod tests {
             system: Some("x86_64-linux".to_string()),
             req_user: "user".to_string(),
             status: "Success".to_string(),
-            start_time: "2024-01-01T00:00:00Z".to_string(),
-            end_time: "2024-01-01T00:01:00Z".to_string(),
+            start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+            end_time: Some("2024-01-01T00:01:00Z".parse().unwrap()),
             drv_path: None,
             output_paths: None,
             github_run_id: 123,
@@ -298,7 +642,7 @@ mod tests {
                 git_commit: "abc123".to_string(),
                 branch: "main".to_string(),
                 req_user: "user".to_string(),
-                start_time: "2024-01-01T00:00:00Z".to_string(),
+                start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
                 succeeded: 1,
                 failed: 1,
                 pending: 0,
@@ -317,8 +661,8 @@ mod tests {
                     system: Some("x86_64-linux".to_string()),
                     req_user: "user".to_string(),
                     status: "Success".to_string(),
-                    start_time: "2024-01-01T00:00:00Z".to_string(),
-                    end_time: "2024-01-01T00:01:00Z".to_string(),
+                    start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+                    end_time: Some("2024-01-01T00:01:00Z".parse().unwrap()),
                     drv_path: None,
                     output_paths: None,
                     github_run_id: 123,
@@ -338,8 +682,8 @@ mod tests {
                     system: Some("x86_64-linux".to_string()),
                     req_user: "user".to_string(),
                     status: "Failed".to_string(),
-                    start_time: "2024-01-01T00:00:00Z".to_string(),
-                    end_time: "2024-01-01T00:01:00Z".to_string(),
+                    start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+                    end_time: Some("2024-01-01T00:01:00Z".parse().unwrap()),
                     drv_path: None,
                     output_paths: None,
                     github_run_id: 124,
@@ -348,11 +692,34 @@ mod tests {
                     uploaded_to_cache: false,
                 },
             ],
-            runs: vec![],
+            runs: vec![Run {
+                github_run_id: Some(124),
+                status: Some("Failed".to_string()),
+                ..Run::default()
+            }],
         };
 
         assert_eq!(response.failed_builds().len(), 1);
         assert_eq!(response.successful_builds().len(), 1);
+        assert_eq!(
+            response.builds[0].duration(),
+            Some(chrono::Duration::seconds(60))
+        );
+        assert_eq!(response.wall_clock(), Some(chrono::Duration::seconds(60)));
+        let run_builds = response.builds_for_run(&response.runs[0]);
+        assert_eq!(run_builds.len(), 1);
+        assert_eq!(run_builds[0].package, "pkg2");
+
+        assert_eq!(response.filter_builds(&BuildFilter::new()).len(), 2);
+        let failed = response.filter_builds(&BuildFilter::new().failed_only(true));
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].package, "pkg2");
+        let filter = BuildFilter::new().package("pkg1").system("x86_64-linux");
+        assert_eq!(response.filter_builds(&filter).len(), 1);
+        let filter = BuildFilter::new().package("pkg1").system("aarch64-darwin");
+        assert!(response.filter_builds(&filter).is_empty());
+        let filter = BuildFilter::new().package("pkg1").failed_only(true);
+        assert!(response.filter_builds(&filter).is_empty());
         assert_eq!(response.pending_builds().len(), 0);
         assert!((response.success_rate() - 50.0).abs() < 0.01);
         assert!(!response.all_successful());
@@ -368,7 +735,7 @@ mod tests {
                 git_commit: "abc123".to_string(),
                 branch: "main".to_string(),
                 req_user: "user".to_string(),
-                start_time: "2024-01-01T00:00:00Z".to_string(),
+                start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
                 succeeded: 1,
                 failed: 0,
                 pending: 0,
@@ -380,5 +747,249 @@ mod tests {
 
         assert!(response.all_successful());
         assert!((response.success_rate() - 100.0).abs() < f64::EPSILON);
+        assert_eq!(response.wall_clock(), None);
+
+        // Nothing is pending before evaluation, but nothing has finished either
+        assert!(!response.is_settled());
+        let evaluated = GarnixResponse {
+            runs: vec![Run {
+                status: Some("Success".to_string()),
+                ..Run::default()
+            }],
+            ..response
+        };
+        assert!(evaluated.is_settled());
+    }
+
+    #[test]
+    fn test_transitions_since() {
+        let build = |id: &str, status: &str| Build {
+            id: id.to_string(),
+            repo_user: "test".to_string(),
+            repo_name: "test".to_string(),
+            branch: "main".to_string(),
+            repo_is_public: true,
+            git_commit: "abc123".to_string(),
+            package: format!("pkg-{}", id),
+            package_type: "derivation".to_string(),
+            system: Some("x86_64-linux".to_string()),
+            req_user: "user".to_string(),
+            status: status.to_string(),
+            start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+            end_time: None,
+            drv_path: None,
+            output_paths: None,
+            github_run_id: 123,
+            wants_incrementalism: false,
+            eval_host: "host".to_string(),
+            uploaded_to_cache: false,
+        };
+        let summary = Summary {
+            repo_owner: "test".to_string(),
+            repo_name: "test".to_string(),
+            repo_is_public: true,
+            git_commit: "abc123".to_string(),
+            branch: "main".to_string(),
+            req_user: "user".to_string(),
+            start_time: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+            succeeded: 0,
+            failed: 0,
+            pending: 2,
+            cancelled: 0,
+        };
+
+        let before = GarnixResponse {
+            summary: summary.clone(),
+            builds: vec![build("1", "Pending"), build("2", "Pending")],
+            runs: vec![],
+        };
+        let after = GarnixResponse {
+            summary: Summary {
+                pending: 1,
+                failed: 1,
+                ..summary
+            },
+            builds: vec![
+                build("1", "Failed"),
+                build("2", "Pending"),
+                build("3", "Pending"),
+            ],
+            runs: vec![],
+        };
+
+        let transitions = after.transitions_since(&before);
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(transitions[0].from.as_deref(), Some("Pending"));
+        assert_eq!(transitions[0].to, "Failed");
+        assert_eq!(
+            transitions[0].to_string(),
+            "pkg-1 (x86_64-linux) went Pending → Failed"
+        );
+        assert_eq!(transitions[1].from, None);
+        assert!(!after.is_settled());
+    }
+
+    #[test]
+    fn test_runs_deserialization() {
+        let response: GarnixResponse = serde_json::from_value(serde_json::json!({
+            "summary": {
+                "repo_owner": "test",
+                "repo_name": "test",
+                "repo_is_public": true,
+                "git_commit": "abc123",
+                "branch": "main",
+                "req_user": "user",
+                "start_time": "2024-01-01T00:00:00Z",
+                "succeeded": 0,
+                "failed": 0,
+                "pending": 0,
+                "cancelled": 0
+            },
+            "builds": [],
+            "runs": [
+                {
+                    "id": 17,
+                    "github_run_id": 123,
+                    "check_name": "flake check",
+                    "status": "Success",
+                    "runner": "eval-1"
+                },
+                {
+                    "id": "run-2",
+                    "github_run_id": 124,
+                    "status": "Failed",
+                    "eval_error": "error: attribute 'foo' missing",
+                    "start_time": "2024-01-01T00:00:00Z"
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(response.runs.len(), 2);
+        assert_eq!(response.runs[0].id.as_deref(), Some("17"));
+        assert_eq!(response.runs[0].label(), "flake check");
+        assert_eq!(
+            response.runs[0].extra.get("runner"),
+            Some(&serde_json::json!("eval-1"))
+        );
+
+        let failed = response.failed_runs();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].label(), "run run-2");
+        assert!(failed[0].error.as_deref().unwrap().contains("missing"));
+        assert!(response.builds_for_run(failed[0]).is_empty());
+        assert!(!response.all_successful());
+    }
+
+    #[test]
+    fn test_missing_runs_default_to_empty() {
+        let response: GarnixResponse = serde_json::from_value(serde_json::json!({
+            "summary": {
+                "repo_owner": "test",
+                "repo_name": "test",
+                "repo_is_public": true,
+                "git_commit": "abc123",
+                "branch": "main",
+                "req_user": "user",
+                "start_time": "2024-01-01T00:00:00Z",
+                "succeeded": 0,
+                "failed": 0,
+                "pending": 0,
+                "cancelled": 0
+            },
+            "builds": []
+        }))
+        .unwrap();
+
+        assert!(response.runs.is_empty());
+        assert!(response.all_successful());
+        assert_eq!(Run::default().label(), "run");
+        assert!(!Run::default().is_failed());
+    }
+
+    #[test]
+    fn test_build_timestamps() {
+        let build = |end_time: Option<serde_json::Value>| {
+            let mut value = serde_json::json!({
+                "id": "1",
+                "repo_user": "test",
+                "repo_name": "test",
+                "branch": "main",
+                "repo_is_public": true,
+                "git_commit": "abc123",
+                "package": "pkg",
+                "package_type": "derivation",
+                "system": "x86_64-linux",
+                "req_user": "user",
+                "status": "Pending",
+                "start_time": "2024-01-01T00:00:00+02:00",
+                "drv_path": null,
+                "output_paths": null,
+                "github_run_id": 1,
+                "wants_incrementalism": false,
+                "eval_host": "host",
+                "uploaded_to_cache": false
+            });
+            if let Some(end_time) = end_time {
+                value["end_time"] = end_time;
+            }
+            serde_json::from_value::<Build>(value)
+        };
+
+        let finished = build(Some(serde_json::json!("2024-01-01T00:03:12+02:00"))).unwrap();
+        assert_eq!(
+            finished.start_time.unwrap().to_rfc3339(),
+            "2023-12-31T22:00:00+00:00"
+        );
+        assert_eq!(finished.duration(), Some(chrono::Duration::seconds(192)));
+
+        assert_eq!(build(Some(serde_json::json!(""))).unwrap().duration(), None);
+        assert_eq!(build(Some(serde_json::Value::Null)).unwrap().end_time, None);
+        assert_eq!(build(None).unwrap().end_time, None);
+        assert!(build(Some(serde_json::json!("yesterday"))).is_err());
+    }
+
+    #[test]
+    fn test_queued_build_without_start_time() {
+        let response: GarnixResponse = serde_json::from_value(serde_json::json!({
+            "summary": {
+                "repo_owner": "test",
+                "repo_name": "test",
+                "repo_is_public": true,
+                "git_commit": "abc123",
+                "branch": "main",
+                "req_user": "user",
+                "start_time": null,
+                "succeeded": 0,
+                "failed": 0,
+                "pending": 1,
+                "cancelled": 0
+            },
+            "builds": [{
+                "id": "1",
+                "repo_user": "test",
+                "repo_name": "test",
+                "branch": "main",
+                "repo_is_public": true,
+                "git_commit": "abc123",
+                "package": "pkg",
+                "package_type": "derivation",
+                "system": "x86_64-linux",
+                "req_user": "user",
+                "status": "Pending",
+                "drv_path": null,
+                "output_paths": null,
+                "github_run_id": 1,
+                "wants_incrementalism": false,
+                "eval_host": "host",
+                "uploaded_to_cache": false
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(response.summary.start_time, None);
+        assert_eq!(response.builds[0].start_time, None);
+        assert_eq!(response.builds[0].duration(), None);
+        assert_eq!(response.wall_clock(), None);
     }
 }